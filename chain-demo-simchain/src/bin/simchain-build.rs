@@ -31,13 +31,50 @@ struct Opts {
     //error_bounds
     #[structopt(long, default_value = "5.0")]
     error_bounds: FloatType,
+
+    //size of the accumulator modulus in bits
+    #[structopt(long, default_value = "2048")]
+    accumulator_bits: usize,
+
+    //file holding the hex modulus of an external accumulator setup, used instead of generating one
+    #[structopt(long)]
+    accumulator_modulus: Option<PathBuf>,
+
+    //hash function of the chain: blake2b, sha256 or sha3-256
+    #[structopt(long, default_value = "blake2b")]
+    hash: HashAlgorithm,
+
+    //file to keep the secret of the producer in, for simchain-server to append blocks with
+    #[structopt(long)]
+    producer_key: Option<PathBuf>,
+
+    //file to keep the keys of the senders in, for simchain-light to sign appended transactions with
+    #[structopt(long)]
+    account_keys: Option<PathBuf>,
 }
 
-fn build_chian(data_path: &Path, out_db_path: &Path, param: &mut Parameter) -> Result<()> {
+fn build_chian(
+    data_path: &Path,
+    out_db_path: &Path,
+    accumulator: RsaAccumulator,
+    producer_key: Option<&Path>,
+    account_keys: Option<&Path>,
+    param: &mut Parameter,
+) -> Result<()> {
     info!("build chain using data from {:?}", data_path);
     info!("output db path: {:?}",out_db_path);
     info!("param: {:?}",param);
-    let raw_txs = load_raw_tx_from_file(data_path)?;
+    let mut raw_txs = load_raw_tx_from_file(data_path)?;
+    let key_pairs = sign_raw_txs(&mut raw_txs, &mut OsRng);
+    if let Some(path) = account_keys {
+        save_account_keys(path, &key_pairs)?;
+    }
+    let producer = Keypair::generate(&mut OsRng);
+    if let Some(path) = producer_key {
+        std::fs::write(path, producer.to_bytes())?;
+    }
+    param.producer_key = producer.public;
+    param.accumulator = accumulator;
     let mut chain = SimChain::create(out_db_path, param.clone())?;
     
     let mut block_count: IdType = 0;
@@ -51,11 +88,11 @@ fn build_chian(data_path: &Path, out_db_path: &Path, param: &mut Parameter) -> R
         info!("build block {}", id);
         let mut sorted_txs = tx.clone();
         sorted_txs.sort_by_key(|tx| tx.key.clone());
-        let block_header = build_block(*id, pre_hash, sorted_txs.iter(),  &mut chain,&mut configs_map)?;
+        let block_header = build_block(*id, pre_hash, sorted_txs.iter(), &producer, &mut chain,&mut configs_map)?;
         // intra_index size
         // index_size += intra_index_size;
         block_headers.push(block_header.clone());
-        pre_hash = block_header.to_digest();
+        pre_hash = block_header.to_digest_with(param.hasher());
         block_count += 1;
     }
     let configs_vec=Vec::from(configs_map);
@@ -66,7 +103,7 @@ fn build_chian(data_path: &Path, out_db_path: &Path, param: &mut Parameter) -> R
     param.start_block_id = start_block_id;
     chain.set_parameter(param.clone())?;
     let timer = howlong::HighResolutionTimer::new();
-    let inter_index_size: IdType =  build_inter_index(block_headers, &mut chain)?;
+    let inter_index_size: IdType =  build_inter_index(block_headers, &producer, &mut chain)?;
     info!("build inter_index time {:#?}", timer.elapsed());
     info!("intra_index size storage cost {:?} B eq {:?} KB eq {:?} MB", index_size, index_size/1024, index_size/1024/1024);
     index_size += inter_index_size;
@@ -87,9 +124,17 @@ fn main() -> Result<()> {
         start_block_id: 0,
         block_count: 0,
         inter_index_timestamps: Vec::new(),
+        producer_key: PkType::default(),
+        accumulator: RsaAccumulator::default(),
+        hash_algorithm: opts.hash,
+    };
+    let accumulator = match &opts.accumulator_modulus {
+        Some(path) => RsaAccumulator::from_hex(&std::fs::read_to_string(path)?)?,
+        // the factorization is dropped right away, but this process did know it
+        None => RsaAccumulator::setup(&mut OsRng, opts.accumulator_bits)?,
     };
     let timer_total = howlong::HighResolutionTimer::new();
-    build_chian(&opts.input_data_path, &opts.db_path, &mut param)?;
+    build_chian(&opts.input_data_path, &opts.db_path, accumulator, opts.producer_key.as_deref(), opts.account_keys.as_deref(), &mut param)?;
     info!("build total time {:#?}", timer_total.elapsed());
     Ok(())
 }
\ No newline at end of file
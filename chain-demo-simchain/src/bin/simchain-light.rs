@@ -0,0 +1,160 @@
+#[macro_use]
+extern crate log;
+
+use anyhow::{bail, Result};
+use howlong::Duration;
+use rand_core::OsRng;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use chain_demo::*;
+use chain_demo_simchain::LightNode;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "simchain-light")]
+struct Opts {
+    /// light node db path
+    #[structopt(short = "-db", long, parse(from_os_str))]
+    db_path: PathBuf,
+
+    /// address of the simchain-server
+    #[structopt(short, long, default_value = "http://127.0.0.1:8000")]
+    server: String,
+
+    /// query param in json, e.g. {"query_attribute":["value"],"range":[["10","20"]],"bloom_filter":false,"intra_index":true}
+    #[structopt(short, long)]
+    query: Option<String>,
+
+    /// query in the query language, e.g. "value BETWEEN 10 AND 20 USING intra_index"
+    #[structopt(short, long, conflicts_with = "query")]
+    text: Option<String>,
+
+    /// id of a block header to fetch, checked against the latest header only
+    #[structopt(long)]
+    header: Option<IdType>,
+
+    /// address whose verified history and balance to show
+    #[structopt(long)]
+    account: Option<String>,
+
+    /// start of the timestamp window of --account
+    #[structopt(long, requires = "account")]
+    from: Option<TsType>,
+
+    /// end of the timestamp window of --account
+    #[structopt(long, requires = "account")]
+    to: Option<TsType>,
+
+    /// matches per page, the query being fetched page by page
+    #[structopt(long)]
+    limit: Option<usize>,
+
+    /// stream the pages of the query, one json line each
+    #[structopt(long)]
+    stream: bool,
+
+    /// file of transactions in the input format of simchain-build, signed
+    /// and appended through the server block by block
+    #[structopt(long, parse(from_os_str), requires = "account-keys")]
+    append: Option<PathBuf>,
+
+    /// keys of the senders written by simchain-build, a key being added for a new address
+    #[structopt(long, parse(from_os_str))]
+    account_keys: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct VerifiedQueryResponse {
+    query_result: OverallResult,
+    pass: bool,
+    fail_detail: VerifyResult,
+    verify_time_in_ms: u64,
+}
+
+#[derive(Serialize)]
+struct VerifiedAccountResponse {
+    account: AccountView,
+    pass: bool,
+    fail_detail: VerifyResult,
+    verify_time_in_ms: u64,
+}
+
+#[actix_rt::main]
+async fn main() -> Result<()> {
+    env_logger::init_from_env(env_logger::Env::default().filter_or("RUST_LOG", "info"));
+    let opts = Opts::from_args();
+    let mut light_node = LightNode::open(&opts.db_path, &opts.server)?;
+    light_node.sync().await?;
+    if let (Some(path), Some(keys_path)) = (&opts.append, &opts.account_keys) {
+        let mut raw_txs = load_raw_tx_from_file(path)?;
+        let mut key_pairs = if keys_path.exists() { load_account_keys(keys_path)? } else { HashMap::new() };
+        sign_raw_txs_with(&mut raw_txs, &mut key_pairs, &mut OsRng);
+        save_account_keys(keys_path, &key_pairs)?;
+        for raw_txs in raw_txs.values() {
+            let header = light_node.append(raw_txs).await?;
+            info!("appended block {}", header.block_id);
+        }
+        light_node.sync().await?;
+    }
+    if let Some(id) = opts.header {
+        let header = light_node.verified_block_header(id).await?;
+        println!("{}", serde_json::to_string_pretty(&header)?);
+    }
+    let q_param = match (opts.query, opts.text) {
+        (Some(query), _) => Some(serde_json::from_str::<QueryParam>(&query)?),
+        (None, Some(text)) => match parse_query(&text) {
+            Ok(q_param) => Some(q_param),
+            Err(e) => bail!("{}\n{}", e, e.pointer(&text)),
+        },
+        (None, None) => None,
+    };
+    if let Some(mut q_param) = q_param {
+        if opts.limit.is_some() {
+            q_param.limit = opts.limit;
+        }
+        // pages are printed as json lines as they are verified
+        let print_page = |result: OverallResult, verify_result: VerifyResult, time: Duration| {
+            info!("verify result {:?} in {:?}", verify_result, time);
+            let response = VerifiedQueryResponse {
+                query_result: result,
+                pass: verify_result.is_ok(),
+                fail_detail: verify_result,
+                verify_time_in_ms: time.as_millis() as u64,
+            };
+            println!("{}", serde_json::to_string(&response)?);
+            Ok(())
+        };
+        if opts.stream {
+            light_node.verified_stream(&q_param, print_page).await?;
+        } else if q_param.limit.is_some() {
+            light_node.verified_pages(&q_param, print_page).await?;
+        } else {
+            let (result, verify_result, time) = light_node.verified_query(&q_param).await?;
+            info!("verify result {:?} in {:?}", verify_result, time);
+            let response = VerifiedQueryResponse {
+                query_result: result,
+                pass: verify_result.is_ok(),
+                fail_detail: verify_result,
+                verify_time_in_ms: time.as_millis() as u64,
+            };
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+    }
+    if let Some(address) = opts.account {
+        let window = match (opts.from, opts.to) {
+            (None, None) => None,
+            (from, to) => Some((from.unwrap_or(TsType::MIN), to.unwrap_or(TsType::MAX))),
+        };
+        let q_param = AccountView::query_param(&address);
+        let (result, verify_result, time) = light_node.verified_query(&q_param).await?;
+        let response = VerifiedAccountResponse {
+            account: AccountView::from_result(&address, window, &result),
+            pass: verify_result.is_ok(),
+            fail_detail: verify_result,
+            verify_time_in_ms: time.as_millis() as u64,
+        };
+        println!("{}", serde_json::to_string_pretty(&response)?);
+    }
+    Ok(())
+}
@@ -2,10 +2,12 @@
 extern crate log;
 
 use actix_cors::Cors;
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder};
 use chain_demo_simchain::SimChain;
+use futures::channel::mpsc;
 use futures::StreamExt;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -69,14 +71,246 @@ async fn web_get_inter_indexs(chain: web::Data<Arc<Mutex<SimChain>>>) -> actix_w
     Ok(HttpResponse::Ok().json(data))
 }
 
-async fn web_query(query_param: web::Json<QueryParam>,chain: web::Data<Arc<Mutex<SimChain>>>) -> actix_web::Result<impl Responder>{
+async fn web_get_mmr_proof(req: web::Path<(IdType,)>, chain: web::Data<Arc<Mutex<SimChain>>>) -> actix_web::Result<impl Responder> {
+    let id = req.into_inner().0;
+    info!("call mmr_proof with {:?}", id);
+    let chain_ref = chain.lock().unwrap();
+    let data = mmr_proof(id, &*chain_ref).map_err(handle_err)?;
+    Ok(HttpResponse::Ok().json(data))
+}
+
+async fn web_get_mmr_consistency(req: web::Path<(IdType, IdType)>, chain: web::Data<Arc<Mutex<SimChain>>>) -> actix_web::Result<impl Responder> {
+    let (old_id, new_id) = req.into_inner();
+    info!("call mmr_consistency_proof with {:?}", (old_id, new_id));
+    let chain_ref = chain.lock().unwrap();
+    let data = mmr_consistency_proof(old_id, new_id, &*chain_ref).map_err(handle_err)?;
+    Ok(HttpResponse::Ok().json(data))
+}
+
+/// Timestamp window of an account, unbounded on a missing side.
+#[derive(Debug, Deserialize)]
+struct AccountWindow {
+    from: Option<TsType>,
+    to: Option<TsType>,
+}
+
+#[derive(Serialize)]
+struct AccountResponse {
+    account: AccountView,
+    // the query the account is built from, for a light node to verify
+    result: OverallResult,
+}
+
+async fn web_get_account(
+    req: web::Path<(String,)>,
+    window: web::Query<AccountWindow>,
+    chain: web::Data<Arc<Mutex<SimChain>>>,
+) -> actix_web::Result<impl Responder> {
+    let address = req.into_inner().0;
+    info!("call account_view with {:?} {:?}", address, window);
+    let window = match (window.from, window.to) {
+        (None, None) => None,
+        (from, to) => Some((from.unwrap_or(TsType::MIN), to.unwrap_or(TsType::MAX))),
+    };
+    if matches!(window, Some((from, to)) if from > to) {
+        return Err(actix_web::error::ErrorBadRequest("the timestamp window is empty"));
+    }
+    let mut chain_ref = chain.lock().unwrap();
+    let chain = chain_ref.deref_mut();
+    let (account, result) = account_view(&address, window, chain).map_err(handle_err)?;
+    Ok(HttpResponse::Ok().json(AccountResponse { account, result }))
+}
+
+/// matches of a streamed page, unless the query limits them
+const DEFAULT_PAGE_LIMIT: usize = 1000;
+
+/// Page of a query, overriding its own cursor and limit.
+#[derive(Debug, Deserialize)]
+pub struct Page {
+    cursor: Option<IdType>,
+    limit: Option<usize>,
+}
+
+/// The `QueryParam` of a request, in json or as a textual query with content
+/// type `text/plain`, or the response rejecting it.
+fn request_query_param(req: &HttpRequest, body: &[u8], page: &Page) -> Result<QueryParam, HttpResponse> {
+    let mut query_param: QueryParam = if req.content_type() == "text/plain" {
+        let query = std::str::from_utf8(body)
+            .map_err(|e| HttpResponse::from_error(actix_web::error::ErrorBadRequest(e)))?;
+        parse_query(query).map_err(|e| HttpResponse::BadRequest().json(e))?
+    } else {
+        serde_json::from_slice(body).map_err(|e| HttpResponse::from_error(actix_web::error::ErrorBadRequest(e)))?
+    };
+    if page.cursor.is_some() {
+        query_param.cursor = page.cursor;
+    }
+    if page.limit.is_some() {
+        query_param.limit = page.limit;
+    }
+    Ok(query_param)
+}
+
+/// Takes a `QueryParam` in json, or a textual query with content type `text/plain`,
+/// paged with `?cursor=<block id>&limit=<matches>`.
+async fn web_query(
+    req: HttpRequest,
+    body: web::Bytes,
+    page: web::Query<Page>,
+    chain: web::Data<Arc<Mutex<SimChain>>>,
+    cache: web::Data<Arc<Mutex<QueryCache>>>,
+) -> actix_web::Result<HttpResponse>{
     info!("into web_query");
+    let query_param = match request_query_param(&req, &body, &page) {
+        Ok(query_param) => query_param,
+        Err(response) => return Ok(response),
+    };
+    // mistyped values and unknown attributes are the client's fault
+    query_param.selection().map_err(actix_web::error::ErrorBadRequest)?;
     let mut chain_ref = chain.lock().unwrap();
     let chain = chain_ref.deref_mut();
-    let result = historical_query(&query_param, chain).map_err(handle_err)?;
+    let result = cache.lock().unwrap().query(&query_param, chain).map_err(handle_err)?;
     Ok(HttpResponse::Ok().json(result))
 }
 
+async fn web_get_cache_stats(cache: web::Data<Arc<Mutex<QueryCache>>>) -> actix_web::Result<impl Responder> {
+    info!("call cache_stats");
+    let stats = cache.lock().unwrap().stats();
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+/// Streams the pages of a query as json lines, each page being a result of
+/// its own, of `DEFAULT_PAGE_LIMIT` matches unless the query limits them.
+async fn web_query_stream(
+    req: HttpRequest,
+    body: web::Bytes,
+    page: web::Query<Page>,
+    chain: web::Data<Arc<Mutex<SimChain>>>,
+) -> actix_web::Result<HttpResponse>{
+    info!("into web_query_stream");
+    let mut query_param = match request_query_param(&req, &body, &page) {
+        Ok(query_param) => query_param,
+        Err(response) => return Ok(response),
+    };
+    query_param.limit.get_or_insert(DEFAULT_PAGE_LIMIT);
+    query_param.selection().map_err(actix_web::error::ErrorBadRequest)?;
+    let chain = chain.get_ref().clone();
+    // the chain is locked page by page, other requests being served in between
+    let pages = futures::stream::unfold(Some(query_param), move |query_param| {
+        let chain = chain.clone();
+        async move {
+            let mut query_param = query_param?;
+            let page = {
+                let mut chain_ref = chain.lock().unwrap();
+                historical_query(&query_param, chain_ref.deref_mut())
+            };
+            let line = page.and_then(|result| {
+                let mut line = serde_json::to_vec(&result)?;
+                line.push(b'\n');
+                Ok((line, result.next_cursor))
+            });
+            match line {
+                Ok((line, next_cursor)) => {
+                    let next = next_cursor.map(|cursor| {
+                        query_param.cursor = Some(cursor);
+                        query_param
+                    });
+                    Some((Ok(web::Bytes::from(line)), next))
+                }
+                Err(e) => Some((Err(handle_err(e)), None)),
+            }
+        }
+    });
+    Ok(HttpResponse::Ok().content_type("application/x-ndjson").streaming(Box::pin(pages)))
+}
+
+/// Standing queries of the connected clients, with the channels their
+/// notifications are sent on.
+#[derive(Default)]
+struct Subscribers {
+    registry: SubscriptionRegistry,
+    channels: HashMap<SubscriptionId, mpsc::UnboundedSender<Notification>>,
+}
+
+impl Subscribers {
+    /// Sends out `notifications`, dropping the subscriptions whose client is gone.
+    fn notify(&mut self, notifications: Vec<Notification>) {
+        for notification in notifications {
+            let id = notification.subscription;
+            let sent = self.channels.get(&id)
+                .is_some_and(|channel| channel.unbounded_send(notification).is_ok());
+            if !sent {
+                info!("drop subscription {}", id);
+                self.channels.remove(&id);
+                self.registry.unsubscribe(id);
+            }
+        }
+    }
+}
+
+/// Server sent event of `name` carrying `data` in json.
+fn sse_event(name: &str, data: &impl Serialize) -> Result<web::Bytes, actix_web::Error> {
+    let data = serde_json::to_string(data).map_err(handle_err)?;
+    Ok(web::Bytes::from(format!("event: {}\ndata: {}\n\n", name, data)))
+}
+
+/// Subscribes to a query, taken as by `/query`, and answers with server sent
+/// events: `subscribed` with the subscription id, then a `notification` with
+/// the matching transactions of every appended block that has some.
+async fn web_subscribe(
+    req: HttpRequest,
+    body: web::Bytes,
+    subscribers: web::Data<Arc<Mutex<Subscribers>>>,
+) -> actix_web::Result<HttpResponse> {
+    info!("into web_subscribe");
+    let query_param = match request_query_param(&req, &body, &Page { cursor: None, limit: None }) {
+        Ok(query_param) => query_param,
+        Err(response) => return Ok(response),
+    };
+    let (sender, receiver) = mpsc::unbounded();
+    let id = {
+        let mut subscribers = subscribers.lock().unwrap();
+        let id = subscribers.registry.subscribe(&query_param).map_err(actix_web::error::ErrorBadRequest)?;
+        subscribers.channels.insert(id, sender);
+        id
+    };
+    info!("subscription {} to {:?}", id, query_param);
+    let subscribed = sse_event("subscribed", &serde_json::json!({ "subscription": id }));
+    let events = futures::stream::once(futures::future::ready(subscribed))
+        .chain(receiver.map(|notification| sse_event("notification", &notification)));
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("cache-control", "no-cache")
+        .streaming(events))
+}
+
+/// Key the blocks appended through `/append` are signed with, if the server was given one.
+struct Producer(Option<Keypair>);
+
+/// Appends a block of transactions signed by their senders, given in json
+/// with the next block id, and notifies the subscriptions it matches.
+async fn web_append(
+    body: web::Bytes,
+    producer: web::Data<Producer>,
+    chain: web::Data<Arc<Mutex<SimChain>>>,
+    subscribers: web::Data<Arc<Mutex<Subscribers>>>,
+) -> actix_web::Result<HttpResponse> {
+    info!("into web_append");
+    let producer = producer.0.as_ref()
+        .ok_or_else(|| actix_web::error::ErrorForbidden("the server has no producer key"))?;
+    let raw_txs: Vec<RawTransaction> = serde_json::from_slice(&body).map_err(actix_web::error::ErrorBadRequest)?;
+    if let Some(raw_tx) = raw_txs.iter().find(|raw_tx| raw_tx.key != raw_tx.value.address) {
+        return Err(actix_web::error::ErrorBadRequest(format!("transaction of {} sent as {}", raw_tx.value.address, raw_tx.key)));
+    }
+    let mut chain_ref = chain.lock().unwrap();
+    let chain = chain_ref.deref_mut();
+    // a signature not made by the key bound to its address is rejected here
+    let header = append_block(&raw_txs, producer, chain).map_err(actix_web::error::ErrorBadRequest)?;
+    let mut subscribers = subscribers.lock().unwrap();
+    let notifications = subscribers.registry.check_block(header.block_id, chain).map_err(handle_err)?;
+    subscribers.notify(notifications);
+    Ok(HttpResponse::Ok().json(header))
+}
+
 #[derive(Serialize)]
 pub struct VerifyResponse {
     pass: bool,
@@ -85,22 +319,38 @@ pub struct VerifyResponse {
     vo_size:usize,
 }
 
-// async fn web_verify(mut body: web::Payload) -> actix_web::Result<impl Responder>{
-//     // transfer from stream to OverallResult
-//     let mut bytes = web::BytesMut::new();
-//     while let Some(item) = body.next().await {
-//         bytes.extend_from_slice(&item?);
-//     }
-//     let query_res: OverallResult = serde_json::from_slice(&bytes).map_err(handle_err)?;
-//     let (verify_result, time) = query_res.verify(get_chain()).await.map_err(handle_err)?;
-//     let response = VerifyResponse{
-//         pass: verify_result.is_ok(),
-//         fail_detail: verify_result,
-//         verify_time_in_ms: time.as_millis() as u64,
-//         vo_size:query_res.vo_size
-//     };
-//     Ok(HttpResponse::Ok().json(response))
-// }
+/// The chain as the verifier reads it, locked for each read rather than
+/// across the awaits of a verification.
+struct SharedChain(Arc<Mutex<SimChain>>);
+
+#[async_trait::async_trait]
+impl LightNodeInterface for SharedChain {
+    async fn lightnode_get_parameter(&self) -> anyhow::Result<Parameter> {
+        self.0.lock().unwrap().get_parameter()
+    }
+    async fn lightnode_read_block_header(&self, id: IdType) -> anyhow::Result<BlockHeader> {
+        self.0.lock().unwrap().read_block_header(id)
+    }
+}
+
+async fn web_verify(mut body: web::Payload, chain: web::Data<Arc<Mutex<SimChain>>>) -> actix_web::Result<impl Responder>{
+    info!("into web_verify");
+    // transfer from stream to OverallResult
+    let mut bytes = web::BytesMut::new();
+    while let Some(item) = body.next().await {
+        bytes.extend_from_slice(&item?);
+    }
+    let query_res: OverallResult = serde_json::from_slice(&bytes).map_err(handle_err)?;
+    let chain = SharedChain(chain.get_ref().clone());
+    let (verify_result, time) = query_res.verify(&chain).await.map_err(handle_err)?;
+    let response = VerifyResponse{
+        pass: verify_result.is_ok(),
+        fail_detail: verify_result,
+        verify_time_in_ms: time.as_millis() as u64,
+        vo_size:query_res.vo_size
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
 
 
 #[derive(StructOpt, Debug)]
@@ -113,16 +363,44 @@ struct Opts {
     /// server binding address
     #[structopt(short, long, default_value = "127.0.0.1:8000")]
     binding: String,
+
+    /// workers a query evaluates its blocks with, 0 for one per core
+    #[structopt(short, long, default_value = "0")]
+    workers: usize,
+
+    /// query results kept in the cache, 0 to evaluate every query
+    #[structopt(long, default_value = "256")]
+    cache_size: usize,
+
+    /// file of the producer key written by simchain-build, to append blocks with
+    #[structopt(long, parse(from_os_str))]
+    producer_key: Option<PathBuf>,
 }
 
 #[actix_rt::main]
 async fn main() -> actix_web::Result<()> {
     env_logger::init_from_env(env_logger::Env::default().filter_or("RUST_LOG", "info"));
     let opts = Opts::from_args();
+    QUERY_WORKERS.store(opts.workers, std::sync::atomic::Ordering::Relaxed);
     let chain_arc=Arc::new(Mutex::new(SimChain::open(&opts.db_path).map_err(handle_err)?));
+    let producer = match &opts.producer_key {
+        Some(path) => {
+            let bytes = std::fs::read(path)?;
+            let key = bytes.try_into().ok().and_then(Keypair::from_bytes)
+                .ok_or_else(|| handle_err("invalid producer key"))?;
+            Some(key)
+        }
+        None => None,
+    };
+    let producer = web::Data::new(Producer(producer));
+    let subscribers = Arc::new(Mutex::new(Subscribers::default()));
+    let cache = Arc::new(Mutex::new(QueryCache::new(opts.cache_size)));
     HttpServer::new(move|| {
         App::new()
             .app_data(web::Data::new(chain_arc.clone()))
+            .app_data(web::Data::new(subscribers.clone()))
+            .app_data(web::Data::new(cache.clone()))
+            .app_data(producer.clone())
             .wrap(
                 Cors::default()
                     .send_wildcard()
@@ -135,8 +413,15 @@ async fn main() -> actix_web::Result<()> {
             .route("/get/inter_index/{timestamp}", web::get().to(web_get_inter_index))
             .route("/get/inter_indexs", web::get().to(web_get_inter_indexs))
             .route("/get/tx/{id}", web::get().to(web_get_transaction))
+            .route("/get/mmr_proof/{id}", web::get().to(web_get_mmr_proof))
+            .route("/get/mmr_consistency/{old_id}/{new_id}", web::get().to(web_get_mmr_consistency))
+            .route("/get/account/{address}", web::get().to(web_get_account))
+            .route("/get/cache_stats", web::get().to(web_get_cache_stats))
             .route("/query", web::post().to(web_query))
-            // .route("/verify", web::post().to(web_verify))
+            .route("/query/stream", web::post().to(web_query_stream))
+            .route("/verify", web::post().to(web_verify))
+            .route("/subscribe", web::post().to(web_subscribe))
+            .route("/append", web::post().to(web_append))
     })
     .bind(opts.binding)?
     .run()
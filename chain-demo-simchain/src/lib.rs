@@ -9,18 +9,38 @@ use chain_demo::*;
 use rocksdb::{WriteBatch,IteratorMode};
 use core::result::Result::Ok;
 
+pub mod light_node;
+pub use light_node::*;
+
+#[cfg(test)]
+mod tests;
+
 pub struct SimChain {
     root_path: PathBuf,
     param: Parameter,
     block_header_db: DB,
     block_data_db: DB,
+    block_bloom_db: DB,
     intra_index_db:DB,
     index_cost_db:DB,
     inter_index_db:DB,
     index_config_db:DB,
+    mmr_db: DB,
+    account_key_db: DB,
     tx_db: DB,
 }
 
+// the peaks of the merkle mountain range are kept under a single key
+const MMR_PEAKS_KEY: &[u8] = b"peaks";
+
+// and its nodes under their height and index
+fn mmr_node_key(height: u32, index: u64) -> Vec<u8> {
+    let mut key = b"node".to_vec();
+    key.extend_from_slice(&height.to_le_bytes());
+    key.extend_from_slice(&index.to_le_bytes());
+    key
+}
+
 impl SimChain {
     pub fn create(path: &Path, param: Parameter) -> Result<Self> {
         info!("create db at {:?}", path);
@@ -37,10 +57,13 @@ impl SimChain {
             param,
             block_header_db: DB::open(&opts, path.join("blk_header.db"))?,
             block_data_db: DB::open(&opts, path.join("blk_data.db"))?,
+            block_bloom_db: DB::open(&opts, path.join("blk_bloom.db"))?,
             intra_index_db: DB::open(&opts, path.join("intra_index.db"))?,
             index_cost_db: DB::open(&opts, path.join("index_cost.db"))?,
             inter_index_db:DB::open(&opts,path.join("inter_index_db"))?,
             index_config_db:DB::open(&opts,path.join("index_config_db"))?,
+            mmr_db: DB::open(&opts, path.join("mmr.db"))?,
+            account_key_db: DB::open(&opts, path.join("account_key.db"))?,
             tx_db: DB::open(&opts, path.join("tx.db"))?,
         })
     }
@@ -53,11 +76,14 @@ impl SimChain {
             param: serde_json::from_str::<Parameter>(&fs::read_to_string(path.join("param.json"))?)?,
             block_header_db: DB::open_default(path.join("blk_header.db"))?,
             block_data_db: DB::open_default(path.join("blk_data.db"))?,
+            block_bloom_db: DB::open_default(path.join("blk_bloom.db"))?,
             intra_index_db: DB::open_default(path.join("intra_index.db"))?,
             tx_db: DB::open_default(path.join("tx.db"))?,
             index_cost_db: DB::open_default(path.join("index_cost.db"))?,
             inter_index_db: DB::open_default(path.join("inter_index_db"))?,
             index_config_db: DB::open_default(path.join("index_config_db"))?,
+            mmr_db: DB::open_default(path.join("mmr.db"))?,
+            account_key_db: DB::open_default(path.join("account_key.db"))?,
         })
     }
 }
@@ -90,6 +116,13 @@ impl ReadInterface for SimChain {
             .context("failed to read block data")?;
         Ok(bincode::deserialize::<BlockData>(&data[..])?)
     }
+    fn read_block_bloom(&self, id: IdType) -> Result<BlockBloom>{
+        let data = self
+            .block_bloom_db
+            .get(id.to_le_bytes())?
+            .context("failed to read block bloom")?;
+        Ok(bincode::deserialize::<BlockBloom>(&data[..])?)
+    }
     fn read_intra_index(&self, id: IdType) -> Result<IntraIndex> {
         let data_result = self.intra_index_db.get(id.to_le_bytes());
     
@@ -148,6 +181,26 @@ impl ReadInterface for SimChain {
             .context("failed to read index config")?;
         Ok(bincode::deserialize::<IndexConfigs>(&data[..])?)
     }
+    fn read_mmr_peaks(&self) -> Result<MmrPeaks> {
+        match self.mmr_db.get(MMR_PEAKS_KEY)? {
+            Some(data) => Ok(bincode::deserialize::<MmrPeaks>(&data[..])?),
+            // no block appended yet
+            None => Ok(MmrPeaks::default()),
+        }
+    }
+    fn read_mmr_node(&self, height: u32, index: u64) -> Result<Digest> {
+        let data = self
+            .mmr_db
+            .get(mmr_node_key(height, index))?
+            .context("failed to read mmr node")?;
+        Ok(bincode::deserialize::<Digest>(&data[..])?)
+    }
+    fn read_account_key(&self, address: &KeyType) -> Result<Option<PkType>> {
+        match self.account_key_db.get(address.as_bytes())? {
+            Some(data) => Ok(Some(bincode::deserialize::<PkType>(&data[..])?)),
+            None => Ok(None),
+        }
+    }
 }
 
 impl WriteInterface for SimChain {
@@ -169,6 +222,12 @@ impl WriteInterface for SimChain {
             .put(data.block_id.to_le_bytes(), bytes)?;
         Ok(())
     }
+    fn write_block_bloom(&mut self, bloom: BlockBloom) -> Result<()>{
+        let bytes = bincode::serialize(&bloom)?;
+        self.block_bloom_db
+            .put(bloom.block_id.to_le_bytes(), bytes)?;
+        Ok(())
+    }
     fn write_intra_index(&mut self, index: IntraIndex) -> Result<()>{
         let bytes = bincode::serialize(&index)?;
         self.intra_index_db
@@ -213,4 +272,19 @@ impl WriteInterface for SimChain {
             .put(config.attribute.as_bytes(), bytes)?;
         Ok(())
     }
+    fn write_mmr_peaks(&mut self, peaks: MmrPeaks) -> Result<()> {
+        let bytes = bincode::serialize(&peaks)?;
+        self.mmr_db.put(MMR_PEAKS_KEY, bytes)?;
+        Ok(())
+    }
+    fn write_mmr_node(&mut self, node: MmrNode) -> Result<()> {
+        let bytes = bincode::serialize(&node.digest)?;
+        self.mmr_db.put(mmr_node_key(node.height, node.index), bytes)?;
+        Ok(())
+    }
+    fn write_account_key(&mut self, address: &KeyType, key: PkType) -> Result<()> {
+        let bytes = bincode::serialize(&key)?;
+        self.account_key_db.put(address.as_bytes(), bytes)?;
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,300 @@
+use actix_web::client::Client;
+use futures::StreamExt;
+use anyhow::{anyhow, bail, Context, Result};
+use howlong::Duration;
+use rocksdb::{self, DB};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chain_demo::*;
+
+// query results carry their vo, allow them to be large
+const MAX_RESPONSE_SIZE: usize = 1 << 30;
+
+/// A light node keeping the block headers of a `simchain-server` in a local
+/// store, and verifying the query results of the server against them.
+pub struct LightNode {
+    root_path: PathBuf,
+    server: String,
+    param: Option<Parameter>,
+    block_header_db: DB,
+}
+
+impl LightNode {
+    pub fn open(path: &Path, server: &str) -> Result<Self> {
+        info!("open light node db at {:?}", path);
+        fs::create_dir_all(path).context(format!("failed to create dir {:?}", path))?;
+        let param = match fs::read_to_string(path.join("param.json")) {
+            Ok(data) => Some(serde_json::from_str::<Parameter>(&data)?),
+            Err(_) => None,
+        };
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        Ok(Self {
+            root_path: path.to_owned(),
+            server: server.trim_end_matches('/').to_owned(),
+            param,
+            block_header_db: DB::open(&opts, path.join("blk_header.db"))?,
+        })
+    }
+
+    /// Fetch the headers the local store misses from the server, checking the
+    /// `pre_hash` link and the producer signature of each one. Returns the
+    /// number of headers added.
+    ///
+    /// The accumulator modulus, producer key and hash function are not
+    /// committed by any header, they are taken from the first sync and a
+    /// server changing them later is rejected, as is one dropping synced
+    /// blocks or moving the first block of its chain.
+    pub async fn sync(&mut self) -> Result<IdType> {
+        let param: Parameter = self.get("/get/param").await?;
+        if let Some(local) = &self.param {
+            check_pinned(local, &param)?;
+        }
+        if param.block_count == 0 {
+            return Ok(0);
+        }
+        let end_id = param.start_block_id + param.block_count;
+        let (mut next_id, mut pre_hash) = match &self.param {
+            Some(local) if local.block_count > 0 => {
+                let last_id = local.start_block_id + local.block_count - 1;
+                (last_id + 1, Some(self.read_block_header(last_id)?.to_digest_with(param.hasher())))
+            }
+            _ => (param.start_block_id, None),
+        };
+        let mut synced: IdType = 0;
+        while next_id < end_id {
+            let header: BlockHeader = self.get(&format!("/get/blk_header/{}", next_id)).await?;
+            if header.block_id != next_id {
+                bail!("server returned header {} instead of {}", header.block_id, next_id);
+            }
+            // the first block is linked to the default digest
+            let expected = pre_hash.unwrap_or_default();
+            if header.pre_hash != expected {
+                bail!("header {} is not linked to its previous header", next_id);
+            }
+            if !header.signature.verify(&param.producer_key, &mut header.signing_transcript(param.hasher())) {
+                bail!("header {} is not signed by the producer", next_id);
+            }
+            pre_hash = Some(header.to_digest_with(param.hasher()));
+            self.write_block_header(&header)?;
+            next_id += 1;
+            synced += 1;
+        }
+        info!("synced {} block headers", synced);
+        self.set_parameter(param)?;
+        Ok(synced)
+    }
+
+    /// Fetch the header `id` from the server and check it against the merkle
+    /// mountain range of the latest local header only.
+    pub async fn verified_block_header(&self, id: IdType) -> Result<BlockHeader> {
+        let param = self.get_parameter()?;
+        let tip = self.read_block_header(param.start_block_id + param.block_count - 1)?;
+        let header: BlockHeader = self.get(&format!("/get/blk_header/{}", id)).await?;
+        if header == tip {
+            return Ok(header);
+        }
+        let proof: MmrProof = self.get(&format!("/get/mmr_proof/{}", id)).await?;
+        if !proof.verify_header(&header, &tip, &param) {
+            bail!("header {} is not committed by the latest header {}", id, tip.block_id);
+        }
+        Ok(header)
+    }
+
+    /// Check that the chain of the server extends the one of the local header
+    /// `old_tip_id`, up to the latest local header.
+    pub async fn check_consistency(&self, old_tip_id: IdType) -> Result<()> {
+        let param = self.get_parameter()?;
+        let tip = self.read_block_header(param.start_block_id + param.block_count - 1)?;
+        let old_tip = self.read_block_header(old_tip_id)?;
+        let proof: ConsistencyProof = self
+            .get(&format!("/get/mmr_consistency/{}/{}", old_tip_id, tip.block_id))
+            .await?;
+        if !proof.verify_headers(&old_tip, &tip, &param) {
+            bail!("header {} is not a prefix of the latest header {}", old_tip_id, tip.block_id);
+        }
+        Ok(())
+    }
+
+    /// Send `q_param` to the server.
+    pub async fn query(&self, q_param: &QueryParam) -> Result<OverallResult> {
+        self.post("/query", q_param).await
+    }
+
+    /// Send `q_param` to the server and verify its result against the local headers.
+    pub async fn verified_query(&self, q_param: &QueryParam) -> Result<(OverallResult, VerifyResult, Duration)> {
+        let result = self.query(q_param).await?;
+        let (verify_result, time) = result.verify(self).await?;
+        Ok((result, verify_result, time))
+    }
+
+    /// Query the pages of `q_param` one after the other, handing each to `f`
+    /// once verified.
+    pub async fn verified_pages(
+        &self,
+        q_param: &QueryParam,
+        mut f: impl FnMut(OverallResult, VerifyResult, Duration) -> Result<()>,
+    ) -> Result<()> {
+        let mut q_param = q_param.clone();
+        loop {
+            let (result, verify_result, time) = self.verified_query(&q_param).await?;
+            check_page(&result, &q_param, q_param.cursor)?;
+            let next_cursor = result.next_cursor;
+            f(result, verify_result, time)?;
+            match next_cursor {
+                Some(cursor) => q_param.cursor = Some(cursor),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Stream the pages of `q_param` from the server, handing each to `f` once
+    /// verified, without holding the whole result.
+    pub async fn verified_stream(
+        &self,
+        q_param: &QueryParam,
+        mut f: impl FnMut(OverallResult, VerifyResult, Duration) -> Result<()>,
+    ) -> Result<()> {
+        let mut response = Client::default()
+            .post(format!("{}/query/stream", self.server))
+            .send_json(q_param)
+            .await
+            .map_err(|e| anyhow!("failed to request /query/stream: {}", e))?;
+        if !response.status().is_success() {
+            bail!("server responded {} to /query/stream", response.status());
+        }
+        let mut cursor = q_param.cursor;
+        let mut done = false;
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response.next().await {
+            buffer.extend_from_slice(&chunk.map_err(|e| anyhow!("failed to read /query/stream: {}", e))?);
+            while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                if done {
+                    bail!("page after the last one");
+                }
+                let result: OverallResult = serde_json::from_slice(&line)
+                    .map_err(|e| anyhow!("failed to parse a page of /query/stream: {}", e))?;
+                check_page(&result, q_param, cursor)?;
+                let (verify_result, time) = result.verify(self).await?;
+                cursor = result.next_cursor;
+                done = cursor.is_none();
+                f(result, verify_result, time)?;
+            }
+        }
+        if !done || !buffer.is_empty() {
+            bail!("stream ended before the last page");
+        }
+        Ok(())
+    }
+
+    /// Append a block of `raw_txs`, signed by their senders, through the
+    /// server, returning the header the server built it with.
+    pub async fn append(&self, raw_txs: &[RawTransaction]) -> Result<BlockHeader> {
+        self.post("/append", &raw_txs).await
+    }
+
+    fn get_parameter(&self) -> Result<Parameter> {
+        self.param.clone().context("light node is not synced")
+    }
+
+    fn set_parameter(&mut self, param: Parameter) -> Result<()> {
+        fs::write(
+            self.root_path.join("param.json"),
+            serde_json::to_string_pretty(&param)?
+        )?;
+        self.param = Some(param);
+        Ok(())
+    }
+
+    fn read_block_header(&self, id: IdType) -> Result<BlockHeader> {
+        let data = self
+            .block_header_db
+            .get(id.to_le_bytes())?
+            .context("failed to read block header")?;
+        Ok(bincode::deserialize::<BlockHeader>(&data[..])?)
+    }
+
+    fn write_block_header(&mut self, header: &BlockHeader) -> Result<()> {
+        let bytes = bincode::serialize(header)?;
+        self.block_header_db
+            .put(header.block_id.to_le_bytes(), bytes)?;
+        Ok(())
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let mut response = Client::default()
+            .get(format!("{}{}", self.server, path))
+            .send()
+            .await
+            .map_err(|e| anyhow!("failed to request {}: {}", path, e))?;
+        if !response.status().is_success() {
+            bail!("server responded {} to {}", response.status(), path);
+        }
+        response
+            .json::<T>()
+            .limit(MAX_RESPONSE_SIZE)
+            .await
+            .map_err(|e| anyhow!("failed to parse response of {}: {}", path, e))
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        let mut response = Client::default()
+            .post(format!("{}{}", self.server, path))
+            .send_json(body)
+            .await
+            .map_err(|e| anyhow!("failed to request {}: {}", path, e))?;
+        if !response.status().is_success() {
+            bail!("server responded {} to {}", response.status(), path);
+        }
+        response
+            .json::<T>()
+            .limit(MAX_RESPONSE_SIZE)
+            .await
+            .map_err(|e| anyhow!("failed to parse response of {}: {}", path, e))
+    }
+}
+
+/// Checks that `result` is the page of `q_param` resuming at `cursor`, the
+/// server choosing the limit of streamed pages.
+fn check_page(result: &OverallResult, q_param: &QueryParam, cursor: Option<IdType>) -> Result<()> {
+    let unpaged = |q_param: &QueryParam| QueryParam { cursor: None, limit: None, ..q_param.clone() };
+    if result.query_param.cursor != cursor || unpaged(&result.query_param) != unpaged(q_param) {
+        bail!("page of another query than {:?} from cursor {:?}", q_param, cursor);
+    }
+    Ok(())
+}
+
+/// Checks that `param` keeps the parameters of `pinned` the light node trusts
+/// without a header committing them, and extends the chain synced under it.
+fn check_pinned(pinned: &Parameter, param: &Parameter) -> Result<()> {
+    if param.start_block_id != pinned.start_block_id {
+        bail!("server moved the first block from {} to {}", pinned.start_block_id, param.start_block_id);
+    }
+    if param.block_count < pinned.block_count {
+        bail!("server has {} blocks, fewer than the {} synced", param.block_count, pinned.block_count);
+    }
+    if param.accumulator != pinned.accumulator {
+        bail!("server changed the accumulator modulus");
+    }
+    if param.producer_key != pinned.producer_key {
+        bail!("server changed the producer key");
+    }
+    if param.hash_algorithm != pinned.hash_algorithm {
+        bail!("server changed the hash function from {} to {}", pinned.hash_algorithm, param.hash_algorithm);
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl LightNodeInterface for LightNode {
+    async fn lightnode_get_parameter(&self) -> Result<Parameter> {
+        self.get_parameter()
+    }
+    async fn lightnode_read_block_header(&self, id: IdType) -> Result<BlockHeader> {
+        self.read_block_header(id)
+    }
+}
+
@@ -0,0 +1,172 @@
+use std::sync::{Arc, Mutex};
+use actix_web::{test, web, App};
+use rand_core::OsRng;
+use super::*;
+
+/// Headers of blocks `1..=count`, each linked to the previous one and signed
+/// by `producer`.
+fn linked_headers(count: IdType, param: &Parameter, producer: &Keypair) -> Vec<BlockHeader> {
+    let mut headers: Vec<BlockHeader> = Vec::new();
+    for block_id in 1..=count {
+        let pre_hash = headers.last().map(|header| header.to_digest_with(param.hasher())).unwrap_or_default();
+        let mut header = BlockHeader {
+            block_id,
+            pre_hash,
+            time_stamp: block_id * 100,
+            BMT_root: Digest::default(),
+            rmt_root: Digest::default(),
+            intra_index_root: Digest::default(),
+            inter_index_root: Digest::default(),
+            mmr_root: Digest::default(),
+            address_acc: Digest::default(),
+            max_value: 0,
+            signature: Signature::default(),
+        };
+        header.signature = producer.sign(&mut header.signing_transcript(param.hasher()));
+        headers.push(header);
+    }
+    headers
+}
+
+/// A server answering `/get/param` and `/get/blk_header` from `state`.
+fn start_server(state: Arc<Mutex<(Parameter, Vec<BlockHeader>)>>) -> test::TestServer {
+    test::start(move || {
+        let param_state = state.clone();
+        let header_state = state.clone();
+        App::new()
+            .route("/get/param", web::get().to(move || {
+                let param = param_state.lock().unwrap().0.clone();
+                async move { web::Json(param) }
+            }))
+            .route("/get/blk_header/{id}", web::get().to(move |id: web::Path<IdType>| {
+                let header = header_state.lock().unwrap().1[id.into_inner() as usize - 1].clone();
+                async move { web::Json(header) }
+            }))
+    })
+}
+
+/// Parameter of a chain of `block_count` blocks from block 1 produced by
+/// `producer`.
+fn test_parameter(block_count: IdType, producer: &Keypair) -> Parameter {
+    Parameter {
+        error_bounds: 1.0,
+        inter_index: false,
+        intra_index: false,
+        start_block_id: 1,
+        block_count,
+        inter_index_timestamps: Vec::new(),
+        producer_key: producer.public,
+        accumulator: RsaAccumulator::setup(&mut OsRng, 512).unwrap(),
+        hash_algorithm: HashAlgorithm::Blake2b,
+    }
+}
+
+/// An empty directory for the store of a light node.
+fn light_node_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("light-node-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+#[actix_rt::test]
+async fn test_sync_rejects_changed_parameter() {
+    let producer = Keypair::generate(&mut OsRng);
+    let param = test_parameter(2, &producer);
+    let headers = linked_headers(3, &param, &producer);
+    let state = Arc::new(Mutex::new((param.clone(), headers)));
+    let server = start_server(state.clone());
+    let path = light_node_path("parameter");
+    let mut light_node = LightNode::open(&path, &server.url("")).unwrap();
+    assert_eq!(light_node.sync().await.unwrap(), 2);
+
+    // a new block under a swapped modulus is not synced
+    let mut changed = param.clone();
+    changed.block_count = 3;
+    changed.accumulator = RsaAccumulator::setup(&mut OsRng, 512).unwrap();
+    state.lock().unwrap().0 = changed.clone();
+    assert!(light_node.sync().await.is_err());
+    let pinned = light_node.lightnode_get_parameter().await.unwrap();
+    assert_eq!(pinned.accumulator, param.accumulator);
+    assert_eq!(pinned.block_count, 2);
+
+    // neither under another producer key or hash function
+    changed.accumulator = param.accumulator.clone();
+    changed.producer_key = Keypair::generate(&mut OsRng).public;
+    state.lock().unwrap().0 = changed.clone();
+    assert!(light_node.sync().await.is_err());
+    changed.producer_key = param.producer_key;
+    changed.hash_algorithm = HashAlgorithm::Sha256;
+    state.lock().unwrap().0 = changed.clone();
+    assert!(light_node.sync().await.is_err());
+
+    changed.hash_algorithm = param.hash_algorithm;
+    state.lock().unwrap().0 = changed;
+    assert_eq!(light_node.sync().await.unwrap(), 1);
+    drop(light_node);
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_sync_rejects_rewritten_history() {
+    let producer = Keypair::generate(&mut OsRng);
+    let param = test_parameter(3, &producer);
+    let headers = linked_headers(4, &param, &producer);
+    let state = Arc::new(Mutex::new((param.clone(), headers)));
+    let server = start_server(state.clone());
+    let path = light_node_path("history");
+    let mut light_node = LightNode::open(&path, &server.url("")).unwrap();
+    assert_eq!(light_node.sync().await.unwrap(), 3);
+
+    // a chain cut back below the synced tip, to fork it from there
+    let mut shrunk = param.clone();
+    shrunk.block_count = 2;
+    state.lock().unwrap().0 = shrunk;
+    assert!(light_node.sync().await.is_err());
+    assert_eq!(light_node.lightnode_get_parameter().await.unwrap().block_count, 3);
+
+    // a chain starting at another block, which would be synced from scratch
+    let mut rebased = param.clone();
+    rebased.start_block_id = 2;
+    rebased.block_count = 3;
+    state.lock().unwrap().0 = rebased;
+    assert!(light_node.sync().await.is_err());
+
+    let mut extended = param.clone();
+    extended.block_count = 4;
+    state.lock().unwrap().0 = extended;
+    assert_eq!(light_node.sync().await.unwrap(), 1);
+    drop(light_node);
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_sync_rejects_forged_header() {
+    let producer = Keypair::generate(&mut OsRng);
+    let param = test_parameter(2, &producer);
+    let mut headers = linked_headers(3, &param, &producer);
+    // a header linked to the synced ones but signed by someone else
+    let forger = Keypair::generate(&mut OsRng);
+    headers[2].max_value = 1_000;
+    headers[2].signature = forger.sign(&mut headers[2].signing_transcript(param.hasher()));
+    let state = Arc::new(Mutex::new((param.clone(), headers)));
+    let server = start_server(state.clone());
+    let path = light_node_path("forged");
+    let mut light_node = LightNode::open(&path, &server.url("")).unwrap();
+    assert_eq!(light_node.sync().await.unwrap(), 2);
+
+    let mut extended = param.clone();
+    extended.block_count = 3;
+    state.lock().unwrap().0 = extended;
+    assert!(light_node.sync().await.is_err());
+    assert_eq!(light_node.lightnode_get_parameter().await.unwrap().block_count, 2);
+
+    // nor is an unsigned one
+    state.lock().unwrap().1[2].signature = Signature::default();
+    assert!(light_node.sync().await.is_err());
+
+    let signed = linked_headers(3, &param, &producer);
+    state.lock().unwrap().1 = signed;
+    assert_eq!(light_node.sync().await.unwrap(), 1);
+    drop(light_node);
+    fs::remove_dir_all(&path).unwrap();
+}
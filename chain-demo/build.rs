@@ -0,0 +1,33 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "serialization-protobuf")]
+    build_protobuf_schemata();
+}
+
+/// Generates the protobuf messages of the proofs into a directory of
+/// `OUT_DIR` each, along with a `mod.rs` the modules converting them include.
+#[cfg(feature = "serialization-protobuf")]
+fn build_protobuf_schemata() {
+    println!("cargo:rerun-if-changed=protobuf");
+
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    let schemata = [
+        ("protobuf/proof.proto", "merkle_tree"),
+        ("protobuf/bloom_proof.proto", "bloom_merkle_tree"),
+    ];
+    for (input, module) in schemata {
+        let module_dir = out_dir.join(module);
+        std::fs::create_dir_all(&module_dir).expect("failed to create the protobuf output directory");
+        protobuf_codegen_pure::Codegen::new()
+            .out_dir(&module_dir)
+            .inputs([input])
+            .include("protobuf")
+            .customize(protobuf_codegen_pure::Customize {
+                gen_mod_rs: Some(true),
+                ..Default::default()
+            })
+            .run()
+            .expect("failed to generate protobuf code");
+    }
+}
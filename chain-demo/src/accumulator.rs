@@ -0,0 +1,231 @@
+use anyhow::{bail, Context, Result};
+use num_bigint::{prime::probably_prime, BigInt, BigUint, ExtendedGcd, ModInverse, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+use rand_core::{CryptoRng, RngCore};
+use rsa::{PublicKeyParts, RsaPrivateKey};
+use serde::{Deserialize, Serialize};
+use crate::digest::{Digest, Digestible};
+use crate::hasher::Hasher;
+
+// rounds of Miller-Rabin when hashing to a prime
+const PRIME_ROUNDS: usize = 20;
+
+/// Public parameters of an RSA accumulator: a modulus of unknown factorization
+/// and the generator `3`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RsaAccumulator {
+    #[serde(with = "biguint_hex")]
+    pub modulus: BigUint,
+}
+
+/// Value of an accumulator over a set of primes, `g^(product of primes) mod N`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AccumulatorValue(#[serde(with = "biguint_hex")] pub BigUint);
+
+/// Witness that a prime is accumulated: the value of the accumulator over
+/// the other primes.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MembershipWitness(#[serde(with = "biguint_hex")] pub BigUint);
+
+/// Witness that none of a set of primes is accumulated, with `a * u + b * y = 1`
+/// for the product `u` of the accumulated primes and `y` of the excluded ones.
+///
+/// Holds `a` and `g^b mod N`, whatever the number of excluded primes.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NonMembershipWitness {
+    #[serde(with = "biguint_hex")]
+    pub a: BigUint,
+    #[serde(with = "biguint_hex")]
+    pub b: BigUint,
+}
+
+impl RsaAccumulator {
+    /// Generates a modulus of `bits` bits, forgetting its factorization.
+    pub fn setup<R: CryptoRng + RngCore>(rng: &mut R, bits: usize) -> Result<Self> {
+        let key = RsaPrivateKey::new(rng, bits).context("failed to generate accumulator modulus")?;
+        Ok(Self { modulus: key.n().clone() })
+    }
+
+    /// Takes the big-endian hex `modulus` of an external setup, whose
+    /// factorization nobody is trusted to have forgotten here.
+    pub fn from_hex(modulus: &str) -> Result<Self> {
+        let bytes = hex::decode(modulus.trim()).context("accumulator modulus is not hex")?;
+        let modulus = BigUint::from_bytes_be(&bytes);
+        if modulus.is_even() || modulus <= BigUint::from(3u32) {
+            bail!("accumulator modulus has to be odd and above the generator 3");
+        }
+        Ok(Self { modulus })
+    }
+
+    pub fn generator(&self) -> BigUint {
+        BigUint::from(3u32)
+    }
+
+    pub fn accumulate(&self, primes: &[BigUint]) -> AccumulatorValue {
+        AccumulatorValue(self.generator().modpow(&product(primes), &self.modulus))
+    }
+
+    /// Witness that `element` is one of `primes`, `None` if it is not.
+    pub fn prove_membership(&self, primes: &[BigUint], element: &BigUint) -> Option<MembershipWitness> {
+        let position = primes.iter().position(|prime| prime == element)?;
+        let others: Vec<BigUint> = primes.iter()
+            .enumerate()
+            .filter(|(i, _)| *i != position)
+            .map(|(_, prime)| prime.clone())
+            .collect();
+        Some(MembershipWitness(self.accumulate(&others).0))
+    }
+
+    /// Witness that none of `excluded` is one of `primes`, `None` if one is.
+    pub fn prove_non_membership(&self, primes: &[BigUint], excluded: &[BigUint]) -> Option<NonMembershipWitness> {
+        let u = BigInt::from_biguint(Sign::Plus, product(primes));
+        let y = BigInt::from_biguint(Sign::Plus, product(excluded));
+        let (gcd, a, _) = u.clone().extended_gcd(&y);
+        if !gcd.is_one() {
+            return None;
+        }
+        // keep `a` below `y`, then `b = (1 - a * u) / y` exactly
+        let a = a.mod_floor(&y);
+        let b = (BigInt::one() - &a * &u) / &y;
+        let modulus = BigInt::from_biguint(Sign::Plus, self.modulus.clone());
+        let generator = BigInt::from_biguint(Sign::Plus, self.generator());
+        let b = if b.is_negative() {
+            let inverse = generator.mod_inverse(&modulus)?;
+            inverse.modpow(&-b, &modulus)
+        } else {
+            generator.modpow(&b, &modulus)
+        };
+        Some(NonMembershipWitness { a: a.to_biguint()?, b: b.to_biguint()? })
+    }
+}
+
+impl MembershipWitness {
+    pub fn verify(&self, acc: &RsaAccumulator, value: &AccumulatorValue, element: &BigUint) -> bool {
+        !acc.modulus.is_zero() && self.0.modpow(element, &acc.modulus) == value.0
+    }
+}
+
+impl NonMembershipWitness {
+    /// Checks `value^a * (g^b)^y = g mod N`, `y` being the product of `excluded`.
+    pub fn verify(&self, acc: &RsaAccumulator, value: &AccumulatorValue, excluded: &[BigUint]) -> bool {
+        if acc.modulus.is_zero() {
+            return false;
+        }
+        let lhs = value.0.modpow(&self.a, &acc.modulus) * self.b.modpow(&product(excluded), &acc.modulus);
+        lhs % &acc.modulus == acc.generator() % &acc.modulus
+    }
+}
+
+impl Digestible for AccumulatorValue {
+    fn to_digest_with(&self, hasher: &dyn Hasher) -> Digest {
+        self.0.to_bytes_be().to_digest_with(hasher)
+    }
+}
+
+/// Maps `data` to a 256 bits prime, the first one among the digests of `data`
+/// followed by an increasing counter.
+pub fn hash_to_prime(data: &[u8], hasher: &dyn Hasher) -> BigUint {
+    let mut counter: u32 = 0;
+    loop {
+        let mut state = hasher.context();
+        state.update(data);
+        state.update(&counter.to_le_bytes());
+        let mut bytes = state.finish().0;
+        // full size and odd
+        bytes[0] |= 0x80;
+        bytes[bytes.len() - 1] |= 0x01;
+        let candidate = BigUint::from_bytes_be(&bytes);
+        if probably_prime(&candidate, PRIME_ROUNDS) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+fn product(primes: &[BigUint]) -> BigUint {
+    primes.iter().fold(BigUint::one(), |acc, prime| acc * prime)
+}
+
+/// Big-endian hex encoding of big integers, as the digests are.
+mod biguint_hex {
+    use num_bigint::BigUint;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &BigUint, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(value.to_bytes_be()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigUint, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        let bytes = hex::decode(data).map_err(D::Error::custom)?;
+        Ok(BigUint::from_bytes_be(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::BLAKE2B;
+
+    fn primes(items: &[&str]) -> Vec<BigUint> {
+        items.iter().map(|item| hash_to_prime(item.as_bytes(), &BLAKE2B)).collect()
+    }
+
+    #[test]
+    fn test_hash_to_prime() {
+        let prime = hash_to_prime(b"addr_a", &BLAKE2B);
+        assert_eq!(prime, hash_to_prime(b"addr_a", &BLAKE2B));
+        assert_ne!(prime, hash_to_prime(b"addr_b", &BLAKE2B));
+        assert_eq!(prime.bits(), 256);
+        assert!(probably_prime(&prime, PRIME_ROUNDS));
+    }
+
+    #[test]
+    fn test_modulus_from_hex() {
+        let acc = RsaAccumulator::setup(&mut rand_core::OsRng, 512).unwrap();
+        let modulus = hex::encode(acc.modulus.to_bytes_be());
+        assert_eq!(RsaAccumulator::from_hex(&format!("{}\n", modulus)).unwrap(), acc);
+        assert!(RsaAccumulator::from_hex("not hex").is_err());
+        assert!(RsaAccumulator::from_hex("0100").is_err());
+        assert!(RsaAccumulator::from_hex("03").is_err());
+    }
+
+    #[test]
+    fn test_membership() {
+        let acc = RsaAccumulator::setup(&mut rand_core::OsRng, 512).unwrap();
+        let set = primes(&["addr_a", "addr_b", "addr_c"]);
+        let value = acc.accumulate(&set);
+        for element in set.iter() {
+            let witness = acc.prove_membership(&set, element).unwrap();
+            assert!(witness.verify(&acc, &value, element));
+        }
+        let outsider = hash_to_prime(b"addr_d", &BLAKE2B);
+        assert!(acc.prove_membership(&set, &outsider).is_none());
+        let witness = acc.prove_membership(&set, &set[0]).unwrap();
+        assert!(!witness.verify(&acc, &value, &outsider));
+    }
+
+    #[test]
+    fn test_non_membership() {
+        let acc = RsaAccumulator::setup(&mut rand_core::OsRng, 512).unwrap();
+        let set = primes(&["addr_a", "addr_b", "addr_c"]);
+        let value = acc.accumulate(&set);
+        let excluded = primes(&["addr_d", "addr_e"]);
+        let witness = acc.prove_non_membership(&set, &excluded).unwrap();
+        assert!(witness.verify(&acc, &value, &excluded));
+        assert!(!witness.verify(&acc, &value, &excluded[..1]));
+        // an accumulated element can not be excluded
+        let mut forged = excluded.clone();
+        forged.push(set[1].clone());
+        assert!(acc.prove_non_membership(&set, &forged).is_none());
+        assert!(!witness.verify(&acc, &value, &forged));
+        // nor the value of another set
+        let other = acc.accumulate(&set[1..]);
+        assert!(!witness.verify(&acc, &other, &excluded));
+
+        let empty = acc.accumulate(&[]);
+        let witness = acc.prove_non_membership(&[], &excluded).unwrap();
+        assert!(witness.verify(&acc, &empty, &excluded));
+    }
+}
@@ -6,6 +6,8 @@ use ahash::AHasher;
 use serde::{Serialize, Deserialize};
 use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
+use crate::digest::{Digest, Digestible};
+use crate::hasher::Hasher as DigestHasher;
 use super::*;
 
 /// A bloom filter that uses a single Hasher that can be seeded to simulate an arbitrary number
@@ -162,6 +164,42 @@ impl SeededBloomFilter {
     pub fn to_bytes(&self) -> &Vec<u8>{
         self.bitset.bytes.as_ref()
     }
+
+    pub fn number_of_hashers(&self) -> usize {
+        self.number_of_hashers
+    }
+
+    pub fn bits_per_hasher(&self) -> usize {
+        self.bits_per_hasher
+    }
+
+    pub fn bit_count(&self) -> usize {
+        self.bitset.len()
+    }
+
+    /// Rebuild a bloom filter from its configuration and the bytes of `to_bytes`,
+    /// `None` if they do not fit together.
+    pub fn from_parts(number_of_hashers: usize, bits_per_hasher: usize, bit_count: usize, bytes: Vec<u8>) -> Option<Self> {
+        if number_of_hashers == 0
+        || number_of_hashers.checked_mul(bits_per_hasher) != Some(bit_count)
+        || bytes.len() != bit_count.div_ceil(8) {
+            return None;
+        }
+        let mut bitset = Bitset::new(bit_count);
+        bitset.bytes = bytes;
+        Some(Self { number_of_hashers, bitset, bits_per_hasher })
+    }
+}
+
+impl Digestible for SeededBloomFilter {
+    fn to_digest_with(&self, hasher: &dyn DigestHasher) -> Digest {
+        let mut state = hasher.context();
+        state.update(&(self.number_of_hashers as u64).to_le_bytes());
+        state.update(&(self.bits_per_hasher as u64).to_le_bytes());
+        state.update(&(self.bitset.len() as u64).to_le_bytes());
+        state.update(self.to_bytes());
+        state.finish()
+    }
 }
 
 impl Debug for SeededBloomFilter {
@@ -1,8 +1,8 @@
 use std::cmp::Ordering;
-use std::hash::{Hash, Hasher};
+use std::hash::{self, Hash};
 
 use bloom_filter::BloomFilter;
-use ring::digest::Algorithm;
+use crate::hasher::Hasher;
 
 use crate::tree::{Tree, LeavesIterator, LeavesIntoIterator};
 use crate::{Positioned, bloom_filter};
@@ -12,7 +12,7 @@ use super::utils::*;
 #[derive(Clone, Debug)]
 pub struct BloomMerkleTree<T> {
     /// The hashing algorithm used by this Merkle tree
-    pub algorithm: &'static Algorithm,
+    pub algorithm: &'static dyn Hasher,
 
     /// The root of the inner binary tree
     root: Tree<T>,
@@ -25,12 +25,11 @@ pub struct BloomMerkleTree<T> {
 }
 
 impl<T: PartialEq> PartialEq for BloomMerkleTree<T> {
-    #[allow(trivial_casts)]
     fn eq(&self, other: &BloomMerkleTree<T>) -> bool {
         self.root == other.root
             && self.height == other.height
             && self.count == other.count
-            && (self.algorithm as *const Algorithm) == (other.algorithm as *const Algorithm)
+            && self.algorithm.algorithm() == other.algorithm.algorithm()
     }
 }
 
@@ -43,23 +42,21 @@ impl<T: Ord> PartialOrd for BloomMerkleTree<T> {
 }
 
 impl<T: Ord> Ord for BloomMerkleTree<T> {
-    #[allow(trivial_casts)]
     fn cmp(&self, other: &BloomMerkleTree<T>) -> Ordering {
         self.height
             .cmp(&other.height)
             .then(self.count.cmp(&other.count))
-            .then((self.algorithm as *const Algorithm).cmp(&(other.algorithm as *const Algorithm)))
+            .then(self.algorithm.algorithm().cmp(&other.algorithm.algorithm()))
             .then_with(|| self.root.cmp(&other.root))
     }
 }
 
 impl<T: Hash> Hash for BloomMerkleTree<T> {
-    #[allow(trivial_casts)]
-    fn hash<H: Hasher>(&self, state: &mut H) {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
         <Tree<T> as Hash>::hash(&self.root, state);
         self.height.hash(state);
         self.count.hash(state);
-        (self.algorithm as *const Algorithm).hash(state);
+        self.algorithm.algorithm().hash(state);
     }
 }
 
@@ -68,7 +65,7 @@ impl<T: Hash> Hash for BloomMerkleTree<T> {
 impl<T> BloomMerkleTree<T> {
     /// Constructs a Merkle Tree from a vector of data blocks.
     /// Returns `None` if `values` is empty.
-    pub fn from_vec(algorithm: &'static Algorithm, values: Vec<T>) -> Self
+    pub fn from_vec(algorithm: &'static dyn Hasher, values: Vec<T>) -> Self
     where
         T: Hashable,
     {
@@ -167,6 +164,25 @@ impl<T> BloomMerkleTree<T> {
             .map(|lemma|Proof::new(self.algorithm, root_hash, lemma, value))
     }
 
+    /// Returns the lemma of the root node, holding its bloom filter and the
+    /// hashes of its children. Returns `None` if the tree has less than two leaves.
+    pub fn root_lemma(&self) -> Option<Lemma> {
+        Lemma::new_bloom_node(&self.root)
+    }
+
+    /// Generate a non-membership proof for the given value, backed by the bloom
+    /// filter of the root. Returns `None` if the bloom filter does not rule it out.
+    pub fn gen_non_membership_proof(&self, value: T) -> Option<Proof<T>>
+    where
+        T: Hashable,
+    {
+        let lemma = self.root_lemma()?;
+        if !lemma.rejects(self.algorithm, &value) {
+            return None;
+        }
+        Some(Proof::new(self.algorithm, self.root_hash().clone(), lemma, value))
+    }
+
     /// Creates an `Iterator` over the values contained in this Merkle tree.
     pub fn iter(&self) -> LeavesIterator<T> {
         self.root.iter()
@@ -11,4 +11,10 @@ pub use tree::*;
 pub mod bloom_merkle_tree;
 pub use bloom_merkle_tree::*;
 
+#[cfg(feature = "serialization-protobuf")]
+#[allow(unused_qualifications)]
+mod proto;
+#[cfg(feature = "serialization-protobuf")]
+pub use proto::{BloomLemmaProto, BloomProofProto, PositionedLemmaProto, SeededBloomFilterProto};
+
 mod tests;
\ No newline at end of file
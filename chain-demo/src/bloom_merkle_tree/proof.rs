@@ -1,10 +1,11 @@
 use std::cmp::Ordering;
-use std::hash::{Hash, Hasher};
+use std::hash::{self, Hash};
 
 use crate::bloom_filter::{SeededBloomFilter, BloomFilter};
-use ring::digest::Algorithm;
+use crate::hasher::Hasher;
+use serde::{Deserialize, Serialize};
 
-use super::utils::HashUtils;
+use super::utils::{HashUtils, Hashable};
 use crate::tree::Tree;
 // TODO: ADD to support multiple values exsit proof, value = Vec<T>
 /// An inclusion proof represent the fact that a `value` is a member
@@ -14,7 +15,7 @@ use crate::tree::Tree;
 pub struct Proof<T> {
     /// The hashing algorithm used in the original `MerkleTree`
     #[cfg_attr(feature = "serialization-serde", serde(with = "algorithm_serde"))]
-    pub algorithm: &'static Algorithm,
+    pub algorithm: &'static dyn Hasher,
 
     /// The hash of the root of the original `MerkleTree`
     pub root_hash: Vec<u8>,
@@ -51,7 +52,7 @@ impl<T: Ord> Ord for Proof<T> {
 }
 
 impl<T: Hash> Hash for Proof<T> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
         self.root_hash.hash(state);
         self.lemma.hash(state);
         self.value.hash(state);
@@ -60,7 +61,7 @@ impl<T: Hash> Hash for Proof<T> {
 
 impl<T> Proof<T> {
     /// Constructs a new `Proof`
-    pub fn new(algorithm: &'static Algorithm, root_hash: Vec<u8>, lemma: Lemma, value: T) -> Self {
+    pub fn new(algorithm: &'static dyn Hasher, root_hash: Vec<u8>, lemma: Lemma, value: T) -> Self {
         Proof {
             algorithm,
             root_hash,
@@ -79,6 +80,18 @@ impl<T> Proof<T> {
         self.lemma.validate(self.algorithm)
     }
 
+    /// Checks whether this proof shows that `value` is not a member of the tree
+    /// with root hash `root_hash`, i.e. the bloom filter of the root rejects it.
+    pub fn validate_non_membership(&self, root_hash: &[u8]) -> bool
+    where
+        T: Hashable,
+    {
+        self.root_hash == root_hash
+            && self.lemma.node_hash == root_hash
+            && self.lemma.validate_bloom_node(self.algorithm)
+            && self.lemma.rejects(self.algorithm, &self.value)
+    }
+
     // /// Returns the index of this proof's value, given the total number of items in the tree.
     // ///
     // /// # Panics
@@ -92,8 +105,7 @@ impl<T> Proof<T> {
 /// A `Lemma` holds the hash of a node, a left_lemma of node, and a right_lemma
 /// `node_hash ==  left_lemma.node_hash|right_lemma.node_hash|hash(bloom_filter)` 
 /// 
-#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Lemma {
     pub node_hash: Vec<u8>,
     // pub sub_lemmas: Option<Vec<Box<Lemma>>>,
@@ -167,7 +179,54 @@ impl Lemma {
 
     }
 
-    fn validate(&self, algorithm: &'static Algorithm) -> bool {
+    /// Lemma of the inner node `tree` with its bloom filter, opened one level
+    /// down: the children are only given by their hash.
+    ///
+    /// Returns `None` if `tree` is not an inner node.
+    pub fn new_bloom_node<T>(tree: &Tree<T>) -> Option<Lemma> {
+        match *tree {
+            Tree::Node {
+                ref hash,
+                ref left,
+                ref right,
+                ref bloom_filter,
+            } => Some(Lemma {
+                node_hash: hash.clone(),
+                left_lemma: Some(Box::new(Positioned::Leaf(Lemma::hash_only(left.hash())))),
+                right_lemma: Some(Box::new(Positioned::Leaf(Lemma::hash_only(right.hash())))),
+                bloom_filter: Some(bloom_filter.clone()),
+            }),
+            _ => None,
+        }
+    }
+
+    fn hash_only(hash: &[u8]) -> Lemma {
+        Lemma {
+            node_hash: hash.into(),
+            left_lemma: None,
+            right_lemma: None,
+            bloom_filter: None,
+        }
+    }
+
+    /// Checks that both children and the bloom filter are given,
+    /// and that they hash to `node_hash`.
+    pub fn validate_bloom_node(&self, algorithm: &'static dyn Hasher) -> bool {
+        self.left_lemma.is_some()
+            && self.right_lemma.is_some()
+            && self.bloom_filter.is_some()
+            && self.validate(algorithm)
+    }
+
+    /// Whether the bloom filter of this node rules out a leaf holding `value`.
+    pub fn rejects<T: Hashable>(&self, algorithm: &'static dyn Hasher, value: &T) -> bool {
+        let leaf_hash: Vec<u8> = algorithm.hash_leaf(value).as_ref().into();
+        self.bloom_filter
+            .as_ref()
+            .is_some_and(|bloom_filter| !bloom_filter.contains(&leaf_hash))
+    }
+
+    fn validate(&self, algorithm: &'static dyn Hasher) -> bool {
         let mut result = true;
         let left_result = match self.left_lemma {
             None => {return true},
@@ -202,8 +261,7 @@ impl Lemma {
 
 
 /// Tags a value so that we know from which Types of a `Node` (if any) it was found.
-#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Positioned<T> {
     /// leaf node
     Leaf(T),
@@ -0,0 +1,176 @@
+// declares `bloom_proof`, generated by build.rs from protobuf/bloom_proof.proto
+include!(concat!(env!("OUT_DIR"), "/bloom_merkle_tree/mod.rs"));
+
+use crate::hasher::Hasher;
+
+use protobuf::error::ProtobufResult;
+use protobuf::Message;
+
+use crate::bloom_filter::SeededBloomFilter;
+use crate::bloom_merkle_tree::proof::{Lemma, Positioned, Proof};
+
+pub use self::bloom_proof::{BloomLemmaProto, BloomProofProto, PositionedLemmaProto, SeededBloomFilterProto};
+
+impl<T> Proof<T> {
+    /// Constructs a `Proof` struct from its Protobuf representation.
+    pub fn from_protobuf(algorithm: &'static dyn Hasher, proto: BloomProofProto) -> Option<Self>
+    where
+        T: From<Vec<u8>>,
+    {
+        proto.into_proof(algorithm)
+    }
+
+    /// Encode this `Proof` to its Protobuf representation.
+    pub fn into_protobuf(self) -> BloomProofProto
+    where
+        T: Into<Vec<u8>>,
+    {
+        BloomProofProto::from_proof(self)
+    }
+
+    /// Parse a `Proof` from its Protobuf binary representation.
+    pub fn parse_from_bytes(
+        bytes: &[u8],
+        algorithm: &'static dyn Hasher,
+    ) -> ProtobufResult<Option<Self>>
+    where
+        T: From<Vec<u8>>,
+    {
+        BloomProofProto::parse_from_bytes(bytes).map(|proto| proto.into_proof(algorithm))
+    }
+
+    /// Serialize this `Proof` with Protobuf.
+    pub fn write_to_bytes(self) -> ProtobufResult<Vec<u8>>
+    where
+        T: Into<Vec<u8>>,
+    {
+        self.into_protobuf().write_to_bytes()
+    }
+}
+
+impl BloomProofProto {
+    /// Encode `proof`, its value being stored as bytes.
+    pub fn from_proof<T>(proof: Proof<T>) -> Self
+    where
+        T: Into<Vec<u8>>,
+    {
+        let mut proto = Self::new();
+        proto.set_root_hash(proof.root_hash);
+        proto.set_lemma(BloomLemmaProto::from_lemma(proof.lemma));
+        proto.set_value(proof.value.into());
+        proto
+    }
+
+    /// Decode the `Proof`, `None` if the root hash or the lemma are missing or malformed.
+    pub fn into_proof<T>(mut self, algorithm: &'static dyn Hasher) -> Option<Proof<T>>
+    where
+        T: From<Vec<u8>>,
+    {
+        if self.root_hash.is_empty() || !self.has_lemma() {
+            return None;
+        }
+
+        self.take_lemma().into_lemma().map(|lemma| {
+            Proof::new(
+                algorithm,
+                self.take_root_hash(),
+                lemma,
+                self.take_value().into(),
+            )
+        })
+    }
+}
+
+impl BloomLemmaProto {
+    /// Encode `lemma` and its sub lemmas.
+    pub fn from_lemma(lemma: Lemma) -> Self {
+        let mut proto = Self::new();
+        proto.set_node_hash(lemma.node_hash);
+
+        if let Some(left_lemma) = lemma.left_lemma {
+            proto.set_left_lemma(PositionedLemmaProto::from_positioned(*left_lemma));
+        }
+        if let Some(right_lemma) = lemma.right_lemma {
+            proto.set_right_lemma(PositionedLemmaProto::from_positioned(*right_lemma));
+        }
+        if let Some(bloom_filter) = lemma.bloom_filter {
+            proto.set_bloom_filter(SeededBloomFilterProto::from_bloom_filter(&bloom_filter));
+        }
+
+        proto
+    }
+
+    /// Decode the `Lemma`, `None` if it or one of its sub lemmas is malformed.
+    pub fn into_lemma(mut self) -> Option<Lemma> {
+        if self.node_hash.is_empty() {
+            return None;
+        }
+
+        // present children and bloom filters have to unserialize to valid ones,
+        // otherwise we return `None`
+        let left_lemma = match self.has_left_lemma() {
+            true => Some(Box::new(self.take_left_lemma().into_positioned()?)),
+            false => None,
+        };
+        let right_lemma = match self.has_right_lemma() {
+            true => Some(Box::new(self.take_right_lemma().into_positioned()?)),
+            false => None,
+        };
+        let bloom_filter = match self.has_bloom_filter() {
+            true => Some(self.take_bloom_filter().into_bloom_filter()?),
+            false => None,
+        };
+
+        Some(Lemma {
+            node_hash: self.take_node_hash(),
+            left_lemma,
+            right_lemma,
+            bloom_filter,
+        })
+    }
+}
+
+impl PositionedLemmaProto {
+    /// Encode a child lemma, tagged as leaf or inner node.
+    pub fn from_positioned(positioned: Positioned<Lemma>) -> Self {
+        let mut proto = Self::new();
+        match positioned {
+            Positioned::Leaf(lemma) => proto.set_leaf(BloomLemmaProto::from_lemma(lemma)),
+            Positioned::Node(lemma) => proto.set_node(BloomLemmaProto::from_lemma(lemma)),
+        }
+        proto
+    }
+
+    /// Decode the child lemma, `None` if it is untagged or malformed.
+    pub fn into_positioned(mut self) -> Option<Positioned<Lemma>> {
+        if self.has_leaf() {
+            self.take_leaf().into_lemma().map(Positioned::Leaf)
+        } else if self.has_node() {
+            self.take_node().into_lemma().map(Positioned::Node)
+        } else {
+            None
+        }
+    }
+}
+
+impl SeededBloomFilterProto {
+    /// Encode the configuration and the bits of `bloom_filter`.
+    pub fn from_bloom_filter(bloom_filter: &SeededBloomFilter) -> Self {
+        let mut proto = Self::new();
+        proto.set_number_of_hashers(bloom_filter.number_of_hashers() as u64);
+        proto.set_bits_per_hasher(bloom_filter.bits_per_hasher() as u64);
+        proto.set_bit_count(bloom_filter.bit_count() as u64);
+        proto.set_bitset(bloom_filter.to_bytes().clone());
+        proto
+    }
+
+    /// Decode the bloom filter, `None` if its configuration does not fit its bits.
+    pub fn into_bloom_filter(mut self) -> Option<SeededBloomFilter> {
+        SeededBloomFilter::from_parts(
+            self.number_of_hashers as usize,
+            self.bits_per_hasher as usize,
+            self.bit_count as usize,
+            self.take_bitset(),
+        )
+    }
+}
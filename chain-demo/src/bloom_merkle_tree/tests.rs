@@ -1,11 +1,11 @@
 #![cfg(test)]
 
 use crate::bloom_filter::{SeededBloomFilter, BloomFilter};
-use ring::digest::{Algorithm, SHA512};
+use crate::hasher::{Hasher, SHA3_256};
 
 use super::utils::*;
 use crate::bloom_merkle_tree::BloomMerkleTree;
-static DIGEST: &Algorithm = &SHA512;
+static DIGEST: &dyn Hasher = &SHA3_256;
 
 
 #[test]
@@ -98,4 +98,72 @@ fn test_tree_iter() {
     let iter = tree.iter().cloned().collect::<Vec<_>>();
 
     assert_eq!(values, iter);
-}
\ No newline at end of file
+}
+#[test]
+fn test_non_membership_proof() {
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = BloomMerkleTree::from_vec(DIGEST, values.clone());
+    let root_hash = tree.root_hash();
+
+    // bloom filter of the root contains every leaf
+    for value in values {
+        assert!(tree.gen_non_membership_proof(value).is_none());
+    }
+
+    let proof = tree.gen_non_membership_proof(vec![42]).unwrap();
+    assert!(proof.validate_non_membership(root_hash));
+
+    // the proof does not hold for another root
+    let other = BloomMerkleTree::from_vec(DIGEST, vec![vec![4], vec![5]]);
+    assert!(!proof.validate_non_membership(other.root_hash()));
+}
+
+#[test]
+fn test_wrong_non_membership_proof() {
+    let values = vec![vec![1], vec![2], vec![3], vec![4]];
+    let tree = BloomMerkleTree::from_vec(DIGEST, values);
+    let root_hash = tree.root_hash();
+
+    // claim an empty bloom filter at the root
+    let mut proof = tree.gen_non_membership_proof(vec![42]).unwrap();
+    proof.value = vec![1];
+    proof.lemma.bloom_filter = Some(SeededBloomFilter::new(BLOOMFILTER_CAPASITY, BLOOMFILTER_FP));
+    assert!(!proof.validate_non_membership(root_hash));
+
+    // hide the children of the root
+    let mut proof = tree.gen_non_membership_proof(vec![42]).unwrap();
+    proof.lemma.left_lemma = None;
+    assert!(!proof.validate_non_membership(root_hash));
+}
+
+#[cfg(feature = "serialization-protobuf")]
+#[test]
+fn test_serialize_proof_with_protobuf() {
+    use crate::bloom_merkle_tree::Proof;
+
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = BloomMerkleTree::from_vec(DIGEST, values);
+    let root_hash = tree.root_hash();
+
+    let proof = tree.gen_proof(vec![5]).unwrap();
+    let bytes = proof.clone().write_to_bytes().expect("serialize proof");
+    let decoded = Proof::<Vec<u8>>::parse_from_bytes(&bytes, DIGEST)
+        .expect("parse proof")
+        .expect("valid proof");
+    assert_eq!(proof, decoded);
+    assert!(decoded.validate(root_hash));
+
+    // the bloom filters of the nodes travel along
+    let proof = tree.gen_non_membership_proof(vec![42]).unwrap();
+    let bytes = proof.clone().write_to_bytes().expect("serialize proof");
+    let decoded = Proof::<Vec<u8>>::parse_from_bytes(&bytes, DIGEST)
+        .expect("parse proof")
+        .expect("valid proof");
+    assert_eq!(proof, decoded);
+    assert!(decoded.validate_non_membership(root_hash));
+
+    // a bloom filter whose bits do not fit its configuration is rejected
+    let mut proto = proof.into_protobuf();
+    proto.mut_lemma().mut_bloom_filter().mut_bitset().pop();
+    assert!(proto.into_proof::<Vec<u8>>(DIGEST).is_none());
+}
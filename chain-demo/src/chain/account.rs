@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use super::*;
+
+/// A transaction of an address, with the balance of the address after it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountEntry {
+    pub block_id: IdType,
+    pub tx_id: IdType,
+    pub time_stamp: TsType,
+    pub trans_in: bool,
+    pub trans_value: TxType,
+    pub balance: i128,
+}
+
+/// Transactions of an address within a timestamp window, in chain order.
+///
+/// Balances count every transaction of the address from the start of the
+/// chain, those before the window making up `opening_balance`. `first_seen`,
+/// `last_seen` and the totals are taken over the whole chain as well, only
+/// `net_flow` is that of the window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountView {
+    pub address: String,
+    pub window: Option<(TsType, TsType)>,
+    pub opening_balance: i128,
+    pub history: Vec<AccountEntry>,
+    pub first_seen: Option<IdType>,
+    pub last_seen: Option<IdType>,
+    pub total_in: u128,
+    pub total_out: u128,
+    pub net_flow: i128,
+}
+
+impl AccountView {
+    /// Query of every transaction of `address`, whatever the window of the
+    /// view, skipping blocks by their bloom filter and looking the address up
+    /// in the intra index.
+    pub fn query_param(address: &str) -> QueryParam {
+        let condition = Condition::Request(QueryRequest {
+            key: "address".to_string(),
+            predicate: Predicate::Eq { value: Value::Text(address.to_string()) },
+        });
+        QueryParam {
+            bloom_filter: true,
+            intra_index: true,
+            condition: Some(condition),
+            ..QueryParam::default()
+        }
+    }
+
+    /// View of `address` over the transactions of `result`, the one of `query_param`.
+    pub fn from_result(address: &str, window: Option<(TsType, TsType)>, result: &OverallResult) -> Self {
+        let mut txs: Vec<&Transaction> = result.res_txs.txs()
+            .filter(|tx| tx.value.address == address)
+            .collect();
+        // tx ids grow along the chain
+        txs.sort_by_key(|tx| (tx.block_id, tx.id));
+        let mut view = AccountView {
+            address: address.to_string(),
+            window,
+            opening_balance: 0,
+            history: Vec::new(),
+            first_seen: txs.first().map(|tx| tx.block_id),
+            last_seen: txs.last().map(|tx| tx.block_id),
+            total_in: 0,
+            total_out: 0,
+            net_flow: 0,
+        };
+        let mut balance: i128 = 0;
+        for tx in txs {
+            let value = tx.value.trans_value;
+            let flow = if tx.value.trans_in {
+                view.total_in += value as u128;
+                value as i128
+            } else {
+                view.total_out += value as u128;
+                -(value as i128)
+            };
+            balance += flow;
+            match window {
+                Some((left, _)) if tx.value.time_stamp < left => view.opening_balance = balance,
+                Some((_, right)) if tx.value.time_stamp > right => continue,
+                _ => {
+                    view.net_flow += flow;
+                    view.history.push(AccountEntry {
+                        block_id: tx.block_id,
+                        tx_id: tx.id,
+                        time_stamp: tx.value.time_stamp,
+                        trans_in: tx.value.trans_in,
+                        trans_value: value,
+                        balance,
+                    });
+                }
+            }
+        }
+        view
+    }
+}
+
+/// View of `address` within `window`, with the query result it is built
+/// from, which a light node can verify.
+pub fn account_view(
+    address: &str,
+    window: Option<(TsType, TsType)>,
+    chain: &mut (impl ReadInterface + WriteInterface + Sync),
+) -> Result<(AccountView, OverallResult)> {
+    let result = historical_query(&AccountView::query_param(address), chain)?;
+    Ok((AccountView::from_result(address, window, &result), result))
+}
@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use super::*;
+
+/// Function of the `trans_value` of the matching transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateFunction {
+    Sum,
+    Count,
+    Min,
+    Max,
+    Avg,
+}
+
+/// How the matching transactions are grouped before being aggregated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    Address,
+    Block,
+    /// `trans_in`, in or out
+    Direction,
+    /// timestamps cut in buckets of the given width
+    TimeBucket(TsType),
+}
+
+/// Aggregates asked for by a query, e.g. `{"functions":["sum","count"],"group_by":"address"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub functions: Vec<AggregateFunction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<GroupBy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// Group of transactions an `AggregateRow` is computed over, a time bucket
+/// being known by its first timestamp.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupKey {
+    Address(String),
+    Block(IdType),
+    Direction(Direction),
+    TimeBucket(TsType),
+}
+
+/// Aggregates of a group, `group` being `None` when the query is not grouped.
+/// Only the requested functions are set, `min`, `max` and `avg` being left
+/// out of an empty result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AggregateRow {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<GroupKey>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sum: Option<u128>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct Accumulated {
+    sum: u128,
+    count: u64,
+    min: Option<u64>,
+    max: Option<u64>,
+}
+
+impl Aggregate {
+    pub fn check(&self) -> Result<()> {
+        if self.functions.is_empty() {
+            bail!("no aggregate function");
+        }
+        if self.group_by == Some(GroupBy::TimeBucket(0)) {
+            bail!("time buckets have to be wider than 0");
+        }
+        Ok(())
+    }
+
+    /// Aggregates of `txs`, one row per group in the order of their keys. An
+    /// ungrouped aggregate always has a row, a grouped one none for no `txs`.
+    pub fn compute<'a>(&self, txs: impl IntoIterator<Item = &'a Transaction>) -> Vec<AggregateRow> {
+        let mut groups: BTreeMap<Option<GroupKey>, Accumulated> = BTreeMap::new();
+        if self.group_by.is_none() {
+            groups.insert(None, Accumulated::default());
+        }
+        for tx in txs {
+            let group = self.group_by.map(|group_by| group_key(group_by, tx));
+            let accumulated = groups.entry(group).or_default();
+            let value = tx.value.trans_value;
+            accumulated.sum += value as u128;
+            accumulated.count += 1;
+            accumulated.min = Some(accumulated.min.map_or(value, |min| min.min(value)));
+            accumulated.max = Some(accumulated.max.map_or(value, |max| max.max(value)));
+        }
+        groups.into_iter().map(|(group, accumulated)| self.row(group, &accumulated)).collect()
+    }
+
+    fn row(&self, group: Option<GroupKey>, accumulated: &Accumulated) -> AggregateRow {
+        let has = |function| self.functions.contains(&function);
+        let avg = if accumulated.count == 0 { None } else { Some(accumulated.sum as f64 / accumulated.count as f64) };
+        AggregateRow {
+            group,
+            sum: if has(AggregateFunction::Sum) { Some(accumulated.sum) } else { None },
+            count: if has(AggregateFunction::Count) { Some(accumulated.count) } else { None },
+            min: if has(AggregateFunction::Min) { accumulated.min } else { None },
+            max: if has(AggregateFunction::Max) { accumulated.max } else { None },
+            avg: if has(AggregateFunction::Avg) { avg } else { None },
+        }
+    }
+}
+
+fn group_key(group_by: GroupBy, tx: &Transaction) -> GroupKey {
+    match group_by {
+        GroupBy::Address => GroupKey::Address(tx.value.address.clone()),
+        GroupBy::Block => GroupKey::Block(tx.block_id),
+        GroupBy::Direction => {
+            GroupKey::Direction(if tx.value.trans_in { Direction::In } else { Direction::Out })
+        }
+        GroupBy::TimeBucket(width) => GroupKey::TimeBucket(tx.value.time_stamp - tx.value.time_stamp % width),
+    }
+}
@@ -177,13 +177,14 @@
 
 
 
-    pub fn build_tree<K>(key: &Vec<K>, value: &Vec<Transaction>)-> BTreeMap<K,Transaction>
+    /// Transactions of a block by key, several of them sharing a key.
+    pub fn build_tree<K>(key: &[K], value: &[Transaction])-> BTreeMap<K,Vec<Transaction>>
     where 
         K:Ord+Clone,
     {
-        let mut btree= BTreeMap::new();
+        let mut btree: BTreeMap<K, Vec<Transaction>> = BTreeMap::new();
         for i in 0..value.len(){
-            btree.entry(key[i].clone()).or_insert(value[i].clone());
+            btree.entry(key[i].clone()).or_default().push(value[i].clone());
         }
         btree
     }
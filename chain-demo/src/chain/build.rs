@@ -1,10 +1,9 @@
 use std::collections::{BTreeMap};
+use anyhow::{bail, Context};
 use log::info;
 use crate::{Digest, btree::index_build,btree::index_build_block};
 use super::*;
 
-use crate::SeededBloomFilter;
-
 ///
 /// 
 /// For BlockData
@@ -15,11 +14,19 @@ use crate::SeededBloomFilter;
 /// 
 /// For BlockHeader
 /// --
+/// signed by the `producer`, whose public key is the producer_key of the parameter.
+/// 
+/// commits the merkle mountain range over the previous headers, which `pre_hash`
+/// is appended to, and the accumulator over the addresses of the block.
 /// 
+/// Every transaction has to be signed by its sender, an address being bound
+/// to the key of its first transaction, and later ones signed with another
+/// key rejected.
 pub fn build_block<'a>(
     block_id: IdType,
     pre_hash: Digest,
     raw_txs: impl Iterator<Item = &'a RawTransaction>,
+    producer: &Keypair,
     chain: &mut (impl ReadInterface + WriteInterface),
     configs_map:&mut IndexConfigs_map,
 ) -> Result<(BlockHeader)> {    
@@ -32,39 +39,176 @@ pub fn build_block<'a>(
     String::from("address"),
     String::from("value"),
 ];
+    // timestamps do not decrease along the chain, which bounds timestamp windows
+    if pre_hash != Digest::default()
+    && chain.read_block_header(block_id - 1)?.time_stamp > _time_stamp {
+        return Err(InvalidReason::NonMonotonicTimestamp { block_id }.into());
+    }
+    let param = chain.get_parameter()?;
+    let mut mmr_peaks = chain.read_mmr_peaks()?;
+    let mut mmr_nodes = Vec::new();
+    if pre_hash != Digest::default() {
+        mmr_nodes = mmr_peaks.append_with_nodes(&pre_hash, param.hasher());
+    }
     let mut height=[block_id];
-    let mut bloom_filter: SeededBloomFilter = SeededBloomFilter::new(BLOOM_CAPACITY,BLOOM_FP);
+    let mut bound_keys: HashMap<KeyType, PkType> = HashMap::new();
     for tx in txs.iter(){
+      if !tx.verify_signature() {
+        return Err(InvalidReason::InvalidSignature { block_id, tx_id: Some(tx.id) }.into());
+      }
+      let account_key = match bound_keys.get(&tx.value.address) {
+        Some(key) => Some(*key),
+        None => chain.read_account_key(&tx.value.address)?,
+      };
+      match account_key {
+        Some(key) if key != tx.public_key => {
+          return Err(InvalidReason::InvalidSignature { block_id, tx_id: Some(tx.id) }.into());
+        }
+        Some(_) => {}
+        None => {
+          bound_keys.insert(tx.value.address.clone(), tx.public_key);
+        }
+      }
       tx_ids.push(tx.id);
-      bloom_filter.insert(&tx.id);
-      bloom_filter.insert(&tx.value.address);
-      bloom_filter.insert(&tx.value.trans_value);
     }
-    let block_header = BlockHeader{
-        block_id,
-        pre_hash,
-        time_stamp: _time_stamp,
-        BMT_root: bloom_filter,
-        rmt_root: pre_hash,
-    };
-
     let block_data = BlockData {
         block_id,
         tx_ids,
         txs,
     };
 
+    let bloom_merkle_tree = block_data.bloom_merkle_tree(param.hasher());
+    let mut bmt_root = Digest::default();
+    bmt_root.0.copy_from_slice(bloom_merkle_tree.root_hash());
+
+    let mut block_header = BlockHeader{
+        block_id,
+        pre_hash,
+        time_stamp: _time_stamp,
+        BMT_root: bmt_root,
+        rmt_root: block_data.merkle_root(param.hasher()),
+        intra_index_root: intra_index_root(&block_data.sorted_roots(param.hasher()), param.hasher()),
+        inter_index_root: Digest::default(),
+        mmr_root: mmr_peaks.root(param.hasher()),
+        address_acc: param.accumulator.accumulate(&block_data.address_primes(param.hasher())).to_digest_with(param.hasher()),
+        max_value: block_data.txs.iter().map(|tx| tx.value.trans_value).max().unwrap_or_default(),
+        signature: Signature::default(),
+    };
+    block_header.signature = producer.sign(&mut block_header.signing_transcript(param.hasher()));
+
     chain.write_block_header(block_header.clone())?;
     chain.write_block_data(block_data.clone())?;
+    chain.write_mmr_peaks(mmr_peaks)?;
+    for node in mmr_nodes {
+        chain.write_mmr_node(node)?;
+    }
+    for (address, key) in bound_keys {
+        chain.write_account_key(&address, key)?;
+    }
+    if let Some(block_bloom) = BlockBloom::new(block_id, &bloom_merkle_tree) {
+        chain.write_block_bloom(block_bloom)?;
+    }
     let (block_index)=index_build_block(&attributes,block_id,chain,configs_map)?;
-    //todo : BMT build
-    //todo : RMT build
     chain.write_intra_index(block_index)?;
     Ok((block_header))
 }
 
+/// Append a block of `raw_txs` after the latest one of `chain`, then fit it
+/// into the inter index, committing the index in the new latest header.
+///
+/// The greedy fit of `build_inter_index` never goes back to the segments of
+/// the blocks it had already seen, so only the last segment is refitted over
+/// its own headers, or a new one started after it.
+pub fn append_block(
+    raw_txs: &[RawTransaction],
+    producer: &Keypair,
+    chain: &mut (impl ReadInterface + WriteInterface),
+) -> Result<BlockHeader> {
+    let mut param = chain.get_parameter()?;
+    if raw_txs.is_empty() {
+        bail!("no transaction in the block");
+    }
+    let block_id = param.start_block_id + param.block_count;
+    if let Some(raw_tx) = raw_txs.iter().find(|raw_tx| raw_tx.block_id != block_id) {
+        bail!("transaction of block {} appended as block {}", raw_tx.block_id, block_id);
+    }
+    let pre_hash = match param.block_count {
+        0 => Digest::default(),
+        _ => {
+            // tx ids go on from the ones of the chain
+            let latest_data = chain.read_block_data(block_id - 1)?;
+            if let Some(max_id) = latest_data.tx_ids.iter().max() {
+                Transaction::reserve_ids_below(max_id + 1);
+            }
+            chain.read_block_header(block_id - 1)?.to_digest_with(param.hasher())
+        }
+    };
+    let mut configs_map = IndexConfigs_map::new();
+    build_block(block_id, pre_hash, raw_txs.iter(), producer, chain, &mut configs_map)?;
+    for configs in Vec::from(configs_map) {
+        let mut stored = chain.read_index_config(configs.attribute.clone())
+            .unwrap_or(IndexConfigs { attribute: configs.attribute.clone(), config: Vec::new() });
+        stored.config.extend(configs.config);
+        chain.write_index_config(stored)?;
+    }
+    param.block_count += 1;
+    chain.set_parameter(param.clone())?;
+    extend_inter_index(block_id, producer, chain)?;
+    chain.read_block_header(block_id)
+}
+
+/// Fit the latest block `block_id` into the inter index as `build_inter_index`
+/// does with each header, reading the headers of the last segment only.
+fn extend_inter_index(
+    block_id: IdType,
+    producer: &Keypair,
+    chain: &mut (impl ReadInterface + WriteInterface),
+) -> Result<()> {
+    let mut param = chain.get_parameter()?;
+    let err_bounds = param.error_bounds as FloatType;
+    let header = chain.read_block_header(block_id)?;
+    let mut segments = chain.read_inter_indexs()?;
+    if segments.is_empty() {
+        segments.push(InterIndex { start_timestamp: header.time_stamp, regression_a: 1.0, regression_b: 1.0 });
+        param.inter_index_timestamps.push(header.time_stamp);
+    }
+    let point_x = header.time_stamp as FloatType;
+    let point_y = header.block_id as FloatType;
+    let last = segments.last_mut().context("no inter index segment")?;
+    if !is_within_boundary(last.regression_a, last.regression_b, point_x, point_y, err_bounds) {
+        let mut timestamps = vec![header.time_stamp];
+        let mut heights = vec![block_id];
+        let mut id = block_id;
+        while id > param.start_block_id {
+            let pre_header = chain.read_block_header(id - 1)?;
+            if pre_header.time_stamp < last.start_timestamp {
+                break;
+            }
+            timestamps.push(pre_header.time_stamp);
+            heights.push(pre_header.block_id);
+            id -= 1;
+        }
+        timestamps.reverse();
+        heights.reverse();
+        let (regression_a, regression_b) = linear_regression(&timestamps, &heights);
+        if is_within_boundary(regression_a, regression_b, point_x, point_y, err_bounds) {
+            last.regression_a = regression_a;
+            last.regression_b = regression_b;
+        } else if header.time_stamp != last.start_timestamp {
+            // start new piecewise linear function
+            segments.push(InterIndex { start_timestamp: header.time_stamp, regression_a: 1.0, regression_b: 1.0 });
+            param.inter_index_timestamps.push(header.time_stamp);
+        }
+    }
+    chain.write_inter_index(segments.last().context("no inter index segment")?.clone())?;
+    commit_inter_index(&segments, header, producer, &param, chain)
+}
+
+/// Fit the inter index over `block_headers` and commit it, with the error
+/// bounds, in the latest header, signed again by the `producer`.
 pub fn build_inter_index(
     block_headers: Vec<BlockHeader>,
+    producer: &Keypair,
     chain: &mut (impl ReadInterface + WriteInterface)
 ) -> Result<IdType>{
     info!("build inter index");
@@ -120,6 +264,25 @@ pub fn build_inter_index(
         chain.write_inter_index(inter_index.to_owned())?;
         param.inter_index_timestamps.push(inter_index.start_timestamp);
     }
-    chain.set_parameter(param.clone())?;
+    let segments: Vec<InterIndex> = inter_indexs.into_values().collect();
+    let latest_header = block_headers.last().unwrap().to_owned();
+    commit_inter_index(&segments, latest_header, producer, &param, chain)?;
     Ok(inter_index_size)
 }
+
+/// Commit the inter index `segments`, with the error bounds, in
+/// `latest_header`, signed again by the `producer`, and save `param`.
+fn commit_inter_index(
+    segments: &[InterIndex],
+    mut latest_header: BlockHeader,
+    producer: &Keypair,
+    param: &Parameter,
+    chain: &mut impl WriteInterface,
+) -> Result<()> {
+    let mut tree_root = Digest::default();
+    tree_root.0.copy_from_slice(inter_index_tree(segments, param.hasher()).root_hash());
+    latest_header.inter_index_root = inter_index_root(&tree_root, param.error_bounds as FloatType, param.hasher());
+    latest_header.signature = producer.sign(&mut latest_header.signing_transcript(param.hasher()));
+    chain.write_block_header(latest_header)?;
+    chain.set_parameter(param.clone())
+}
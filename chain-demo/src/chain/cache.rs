@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use super::*;
+
+/// entries a cache keeps unless given another capacity
+pub const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+/// How a cache answered the queries it was asked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// results reused without evaluating any block
+    pub hits: u64,
+    /// results of open ended queries, extended with the blocks appended since
+    pub refreshes: u64,
+    /// results computed from scratch
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// Result of a query as of the chain height it was computed at.
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    height: u64,
+    /// timestamp of the latest block at that height
+    tip_time: TsType,
+    result: OverallResult,
+    last_used: u64,
+}
+
+/// Results of recent queries, keyed by their normalized `QueryParam`.
+///
+/// An entry outlives the height it was computed at: a timestamp window ending
+/// before the tip of that height can not match an appended block, while an
+/// open ended query only has to evaluate the blocks appended since. Either
+/// way, the result is proven against the latest header again.
+#[derive(Debug)]
+pub struct QueryCache {
+    capacity: usize,
+    entries: HashMap<String, CacheEntry>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+impl QueryCache {
+    /// A cache of at most `capacity` results, the least recently used being
+    /// evicted first; a capacity of 0 caches nothing.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { entries: self.entries.len(), ..self.stats }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Result of `q_param`, as `historical_query` returns it. Pages and
+    /// explained queries are always evaluated. A cached answer is counted
+    /// towards the index management as an evaluated one, and timed anew.
+    pub fn query(
+        &mut self,
+        q_param: &QueryParam,
+        chain: &mut (impl ReadInterface + WriteInterface + Sync),
+    ) -> Result<OverallResult> {
+        let timer = howlong::HighResolutionTimer::new();
+        let selection = q_param.selection()?;
+        if self.capacity == 0 || q_param.explain || q_param.limit.is_some() || q_param.cursor.is_some() {
+            return historical_query(q_param, chain);
+        }
+        let key = serde_json::to_string(&q_param.normalized())?;
+        let height = chain.get_parameter()?.block_count;
+        self.clock += 1;
+        let cached = match self.entries.get(&key) {
+            Some(entry) if entry.height == height => {
+                self.stats.hits += 1;
+                Some(entry.result.clone())
+            }
+            Some(entry) if entry.height < height && matches!(selection.window, Some((_, right)) if right < entry.tip_time) => {
+                let result = reuse(entry, &selection, chain)?;
+                if result.is_some() {
+                    self.stats.hits += 1;
+                }
+                result
+            }
+            Some(entry) if entry.height < height && q_param.top_k.is_none() => {
+                let result = refresh(entry, &selection, q_param, chain)?;
+                if result.is_some() {
+                    self.stats.refreshes += 1;
+                }
+                result
+            }
+            _ => None,
+        };
+        let answered = cached.is_some();
+        let mut result = match cached {
+            Some(result) => result,
+            None => {
+                self.stats.misses += 1;
+                historical_query(q_param, chain)?
+            }
+        };
+        result.query_param = q_param.clone();
+        result.vo_size = vo_size(&result);
+        result.query_time_ms = timer.elapsed().as_millis() as u64;
+        // historical_query counted the misses
+        if answered {
+            record_query(q_param, chain)?;
+        }
+        let tip_time = match height {
+            0 => TsType::MIN,
+            _ => {
+                let param = chain.get_parameter()?;
+                chain.read_block_header(param.start_block_id + height - 1)?.time_stamp
+            }
+        };
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let oldest = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, CacheEntry { height, tip_time, result: result.clone(), last_used: self.clock });
+        Ok(result)
+    }
+}
+
+/// The result of `entry`, whose window ends before the appended blocks,
+/// these being proven out of range as far as the inter index plans it.
+fn reuse(entry: &CacheEntry, selection: &Selection, chain: &impl ReadInterface) -> Result<Option<OverallResult>> {
+    let param = chain.get_parameter()?;
+    let latest_id = param.start_block_id + param.block_count - 1;
+    let mut result = entry.result.clone();
+    let mut end_id = latest_id;
+    if result.res_inter_vo.is_some() {
+        end_id = match prove_window(&mut result, selection, &param, chain)? {
+            Some(end_id) => end_id.min(latest_id),
+            None => return Ok(None),
+        };
+    }
+    let appended: Vec<BlockVo> = (param.start_block_id + entry.height..=end_id).rev()
+        .map(|block_id| BlockVo::OutOfRange { block_id })
+        .collect();
+    result.res_vos.0.splice(0..0, appended);
+    Ok(Some(result))
+}
+
+/// The result of `entry` with the matches of the blocks appended since, `None`
+/// when the planner no longer reads the window the same way.
+fn refresh(
+    entry: &CacheEntry,
+    selection: &Selection,
+    q_param: &QueryParam,
+    chain: &(impl ReadInterface + Sync),
+) -> Result<Option<OverallResult>> {
+    let (plan, _) = plan_query(q_param, selection, chain)?;
+    let mut result = entry.result.clone();
+    if plan.inter_index != result.use_inter_index {
+        return Ok(None);
+    }
+    let mut param = chain.get_parameter()?;
+    param.intra_index = !plan.intra_index.is_empty();
+    param.inter_index = plan.inter_index;
+    let latest_id = param.start_block_id + param.block_count - 1;
+    let block_ids: Vec<IdType> = (param.start_block_id + entry.height..=latest_id).rev().collect();
+    let walk = walk_blocks(&block_ids, selection, q_param, &plan, param.start_block_id, &param, chain)?;
+    let mut res_sigs = Vec::new();
+    for block in walk.res_txs.0.iter() {
+        let block_header = chain.read_block_header(block.block_id)?;
+        res_sigs.push(BlockSig::new(block_header, &param, &block.Txs));
+    }
+    // the appended blocks come first, as the latest ones do in a result
+    result.res_txs.0.splice(0..0, walk.res_txs.0);
+    result.res_vos.0.splice(0..0, walk.res_vos.0);
+    result.res_sigs.0.splice(0..0, res_sigs);
+    if let Some(boundary) = result.res_boundary.as_mut() {
+        if walk.boundary.right.is_some() {
+            boundary.right = walk.boundary.right;
+        }
+        if walk.boundary.left.is_some() {
+            boundary.left = walk.boundary.left;
+        }
+    }
+    if result.res_inter_vo.is_some() && prove_window(&mut result, selection, &param, chain)?.is_none() {
+        return Ok(None);
+    }
+    result.aggregates = q_param.aggregate.as_ref().map(|aggregate| aggregate.compute(result.res_txs.txs()));
+    Ok(Some(result))
+}
+
+/// Proves the window of `result` with the inter index committed by the
+/// latest header, returning the last block of the range it plans; `None` if
+/// that range starts below the blocks of the result.
+fn prove_window(
+    result: &mut OverallResult,
+    selection: &Selection,
+    param: &Parameter,
+    chain: &impl ReadInterface,
+) -> Result<Option<IdType>> {
+    let window = selection.window.context("no timestamp range in query")?;
+    let inter_indexs = chain.read_inter_indexs()?;
+    let (start_id, end_id) = match window_range(&inter_indexs, window, param) {
+        Some(range) => range,
+        None => return Ok(None),
+    };
+    let first = result.res_vos.0.iter().map(|block_vo| block_vo.block_id()).min();
+    if start_id <= end_id && first.is_none_or(|first| first > start_id) {
+        return Ok(None);
+    }
+    let latest_id = param.start_block_id + param.block_count - 1;
+    result.res_inter_vo = Some(InterIndexVo::new(latest_id, param.error_bounds, &inter_indexs, window, param.hasher()));
+    Ok(Some(end_id))
+}
+
+fn vo_size(result: &OverallResult) -> usize {
+    result.res_vos.size() + result.res_sigs.size()
+        + result.res_inter_vo.as_ref().map_or(0, |vo| vo.size())
+        + result.res_boundary.as_ref().map_or(0, |vo| vo.size())
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeSet;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use super::*;
+
+/// Boolean combination of requests a transaction has to satisfy, e.g.
+/// `{"and":[{"request":{..}},{"not":{"request":{..}}}]}` in json.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// every condition, true when empty
+    And(Vec<Condition>),
+    /// any condition, false when empty
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+    Request(QueryRequest),
+}
+
+/// Normalized `conditions`, ordered by their json.
+fn normalized_terms(conditions: &[Condition]) -> Vec<Condition> {
+    let mut terms: Vec<(String, Condition)> = conditions.iter()
+        .map(|condition| {
+            let condition = condition.normalized();
+            (serde_json::to_string(&condition).unwrap_or_default(), condition)
+        })
+        .collect();
+    terms.sort_by(|a, b| a.0.cmp(&b.0));
+    terms.dedup_by(|a, b| a.0 == b.0);
+    terms.into_iter().map(|(_, condition)| condition).collect()
+}
+
+/// What a query selects: the blocks within `window`, inclusive, if any, and
+/// in them the transactions matching `condition`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub window: Option<(TsType, TsType)>,
+    pub condition: Condition,
+}
+
+impl Condition {
+    /// Checks the values of every request against the type of its attribute.
+    pub fn check(&self) -> Result<()> {
+        self.requests().into_iter().try_for_each(|request| request.check())
+    }
+
+    /// The requests of the leaves, from left to right.
+    pub fn requests(&self) -> Vec<&QueryRequest> {
+        match self {
+            Condition::And(conditions) | Condition::Or(conditions) => {
+                conditions.iter().flat_map(|condition| condition.requests()).collect()
+            }
+            Condition::Not(condition) => condition.requests(),
+            Condition::Request(request) => vec![request],
+        }
+    }
+
+    /// The condition with the terms of every `and` and `or` sorted and deduplicated.
+    pub fn normalized(&self) -> Condition {
+        match self {
+            Condition::And(conditions) => Condition::And(normalized_terms(conditions)),
+            Condition::Or(conditions) => Condition::Or(normalized_terms(conditions)),
+            Condition::Not(condition) => Condition::Not(Box::new(condition.normalized())),
+            Condition::Request(request) => Condition::Request(request.clone()),
+        }
+    }
+
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        match self {
+            Condition::And(conditions) => conditions.iter().all(|condition| condition.matches(tx)),
+            Condition::Or(conditions) => conditions.iter().any(|condition| condition.matches(tx)),
+            Condition::Not(condition) => !condition.matches(tx),
+            Condition::Request(request) => request.matches(tx),
+        }
+    }
+
+    /// Whether a block may hold a match, given which keys of the bloom merkle
+    /// tree its bloom filter `contains`. False only when no transaction can match.
+    pub fn may_match(&self, contains: &dyn Fn(&[u8]) -> bool) -> bool {
+        match self {
+            Condition::And(conditions) => conditions.iter().all(|condition| condition.may_match(contains)),
+            Condition::Or(conditions) => conditions.iter().any(|condition| condition.may_match(contains)),
+            // a bloom filter tells absent keys apart, never the ones every tx holds
+            Condition::Not(_) => true,
+            Condition::Request(request) => {
+                // timestamps are not in the bloom filter, and only a few listed values can be looked up
+                if request.key == "timestamp" {
+                    return true;
+                }
+                match request.predicate.enumerate(MAX_ENUMERATED_VALUES) {
+                    Some(values) => values.iter().any(|value| contains(&bloom_key(&request.key, &value.to_string()))),
+                    None => true,
+                }
+            }
+        }
+    }
+
+    /// Addresses one of which a block must hold to contain a match, `None` if
+    /// the condition does not restrict addresses to a list.
+    pub fn required_addresses(&self) -> Option<BTreeSet<String>> {
+        match self {
+            // any restricted term will do, the shortest makes the smallest witness
+            Condition::And(conditions) => conditions.iter()
+                .filter_map(|condition| condition.required_addresses())
+                .min_by_key(|addresses| addresses.len()),
+            Condition::Or(conditions) => {
+                let mut addresses = BTreeSet::new();
+                for condition in conditions {
+                    addresses.extend(condition.required_addresses()?);
+                }
+                Some(addresses)
+            }
+            Condition::Not(_) => None,
+            Condition::Request(request) => {
+                if request.key != "address" {
+                    return None;
+                }
+                let values = request.predicate.enumerate(MAX_ENUMERATED_VALUES)?;
+                Some(values.into_iter().map(|value| value.to_string()).collect())
+            }
+        }
+    }
+
+    /// Lookups of `SORTED_ATTRIBUTES` whose matches hold every match of the
+    /// condition, each matching a single interval of the values of its
+    /// attribute. `None` if the condition can not be narrowed down that way.
+    pub fn sorted_lookups(&self) -> Option<Vec<QueryRequest>> {
+        match self {
+            // any narrowed term will do, the fewest lookups make the smallest proof
+            Condition::And(conditions) => conditions.iter()
+                .filter_map(|condition| condition.sorted_lookups())
+                .min_by_key(|lookups| lookups.len()),
+            Condition::Or(conditions) => {
+                let mut lookups = Vec::new();
+                for condition in conditions {
+                    lookups.extend(condition.sorted_lookups()?);
+                }
+                Some(lookups)
+            }
+            Condition::Not(_) => None,
+            Condition::Request(request) => {
+                if !SORTED_ATTRIBUTES.contains(&request.key.as_str()) {
+                    return None;
+                }
+                match &request.predicate {
+                    Predicate::In { values } => {
+                        let values: BTreeSet<&Value> = values.iter().collect();
+                        Some(values.into_iter().map(|value| QueryRequest {
+                            key: request.key.clone(),
+                            predicate: Predicate::Eq { value: value.clone() },
+                        }).collect())
+                    }
+                    Predicate::NotEq { .. } => None,
+                    _ => Some(vec![request.clone()]),
+                }
+            }
+        }
+    }
+
+    /// `self` and `other`, flattening nested conjunctions.
+    pub fn and(self, other: Condition) -> Condition {
+        let mut conditions = match self {
+            Condition::And(conditions) => conditions,
+            condition => vec![condition],
+        };
+        match other {
+            Condition::And(others) => conditions.extend(others),
+            other => conditions.push(other),
+        }
+        Condition::And(conditions)
+    }
+}
+
+impl Selection {
+    /// Takes the top level timestamp ranges of `condition` out into the window,
+    /// the blocks being selected by their timestamp instead of their transactions.
+    pub fn new(window: Option<(TsType, TsType)>, condition: Condition) -> Result<Self> {
+        let mut window = window;
+        let conditions = match condition {
+            Condition::And(conditions) => conditions,
+            condition => vec![condition],
+        };
+        let mut rest = Vec::new();
+        for condition in conditions {
+            let range = match &condition {
+                Condition::Request(request) if request.key == "timestamp" => request.predicate.number_range(),
+                _ => None,
+            };
+            match range {
+                Some((left, right)) => {
+                    window = Some(match window {
+                        Some((start, end)) => (start.max(left), end.min(right)),
+                        None => (left, right),
+                    });
+                }
+                None => rest.push(condition),
+            }
+        }
+        if matches!(window, Some((left, right)) if left > right) {
+            bail!("the timestamp window is empty");
+        }
+        let condition = if rest.len() == 1 { rest.pop().unwrap() } else { Condition::And(rest) };
+        Ok(Selection { window, condition })
+    }
+}
@@ -1,7 +1,14 @@
 use super::{IdType, TsType, PkType, BTreeEnum};
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, BTreeSet};
 use serde::{Deserialize, Serialize};
-use crate::{digest::*, KeyType, FloatType, TransactionValue, TxType, Transaction, SeededBloomFilter};
+use crate::{digest::*, FloatType, TxType, Transaction, MerkleTree};
+use crate::hasher::Hasher;
+use crate::bloom_merkle_tree::{BloomMerkleTree, Lemma};
+use crate::signature::Signature;
+use crate::accumulator::hash_to_prime;
+use super::predicate::attribute_value;
+use num_bigint::BigUint;
+use merlin::Transcript;
 
 // static INDEX_ID_CNT: AtomicU64 = AtomicU64::new(0);
 
@@ -13,6 +20,107 @@ pub struct BlockData {
     pub txs: Vec<Transaction>,
 }
 
+impl BlockData {
+    /// Merkle tree whose leaves are the digests of `txs`, in block order.
+    pub fn merkle_tree(&self, hasher: &'static dyn Hasher) -> MerkleTree<Digest> {
+        let leaves = self.txs.iter().map(|tx| tx.to_digest_with(hasher)).collect();
+        MerkleTree::from_vec(hasher, leaves)
+    }
+
+    pub fn merkle_root(&self, hasher: &'static dyn Hasher) -> Digest {
+        let mut root = Digest::default();
+        root.0.copy_from_slice(self.merkle_tree(hasher).root_hash());
+        root
+    }
+
+    /// Positions of `txs` sorted by their `attribute`, equal values in block order.
+    pub fn sorted_positions(&self, attribute: &str) -> Vec<usize> {
+        let mut positions: Vec<usize> = (0..self.txs.len()).collect();
+        positions.sort_by_cached_key(|position| attribute_value(attribute, &self.txs[*position]));
+        positions
+    }
+
+    /// Merkle tree whose leaves are the digests of the `txs` at `positions`.
+    pub fn sorted_tree(&self, positions: &[usize], hasher: &'static dyn Hasher) -> MerkleTree<Digest> {
+        let leaves = positions.iter().map(|position| self.txs[*position].to_digest_with(hasher)).collect();
+        MerkleTree::from_vec(hasher, leaves)
+    }
+
+    /// Roots of the trees of `txs` sorted by each of `SORTED_ATTRIBUTES`.
+    pub fn sorted_roots(&self, hasher: &'static dyn Hasher) -> Vec<Digest> {
+        SORTED_ATTRIBUTES.iter().map(|attribute| {
+            let mut root = Digest::default();
+            root.0.copy_from_slice(self.sorted_tree(&self.sorted_positions(attribute), hasher).root_hash());
+            root
+        }).collect()
+    }
+
+    /// Bloom merkle tree whose leaves are the `bloom_key`s of every tx, so that
+    /// id, address and value lookups do not collide.
+    pub fn bloom_merkle_tree(&self, hasher: &'static dyn Hasher) -> BloomMerkleTree<Vec<u8>> {
+        let mut leaves = Vec::with_capacity(self.txs.len() * 3);
+        for tx in self.txs.iter() {
+            leaves.push(bloom_key("id", &tx.id.to_string()));
+            leaves.push(bloom_key("address", &tx.value.address));
+            leaves.push(bloom_key("value", &tx.value.trans_value.to_string()));
+        }
+        BloomMerkleTree::from_vec(hasher, leaves)
+    }
+
+    /// Primes of the distinct addresses of `txs`, accumulated in the header.
+    pub fn address_primes(&self, hasher: &dyn Hasher) -> Vec<BigUint> {
+        let addresses: BTreeSet<&str> = self.txs.iter().map(|tx| tx.value.address.as_str()).collect();
+        addresses.into_iter().map(|address| address_prime(address, hasher)).collect()
+    }
+}
+
+/// Attributes the transactions of a block are committed sorted by, so that a
+/// lookup of an interval of values is proven by the contiguous leaves it hits.
+pub const SORTED_ATTRIBUTES: [&str; 4] = ["id", "address", "value", "timestamp"];
+
+/// Commitment to the `roots` of the sorted trees of a block, stored in its header.
+pub fn intra_index_root(roots: &[Digest], hasher: &dyn Hasher) -> Digest {
+    let mut state = hasher.context();
+    for root in roots {
+        state.update(&root.0);
+    }
+    state.finish()
+}
+
+/// Leaf of the bloom merkle tree for `attribute` holding `value`.
+pub fn bloom_key(attribute: &str, value: &str) -> Vec<u8> {
+    format!("{}:{}", attribute, value).into_bytes()
+}
+
+/// Prime `address` is accumulated as, hashed from its bloom key.
+pub fn address_prime(address: &str, hasher: &dyn Hasher) -> BigUint {
+    hash_to_prime(&bloom_key("address", address), hasher)
+}
+
+/// Root node of the bloom merkle tree of a block: its bloom filter and the
+/// hashes of its children, enough to prove that a key is absent.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BlockBloom {
+    pub block_id: IdType,
+    pub lemma: Lemma,
+}
+
+impl BlockBloom {
+    pub fn new(block_id: IdType, tree: &BloomMerkleTree<Vec<u8>>) -> Option<Self> {
+        tree.root_lemma().map(|lemma| Self { block_id, lemma })
+    }
+
+    /// Checks that the lemma is the root node committed by `bmt_root`.
+    pub fn validate(&self, bmt_root: &Digest, hasher: &'static dyn Hasher) -> bool {
+        self.lemma.node_hash == bmt_root.0
+            && self.lemma.validate_bloom_node(hasher)
+    }
+
+    pub fn contains(&self, key: &[u8], hasher: &'static dyn Hasher) -> bool {
+        !self.lemma.rejects(hasher, &key)
+    }
+}
+
 //block_id == block_height, data_root = data.hash()
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct BlockHeader {
@@ -20,18 +128,47 @@ pub struct BlockHeader {
     pub pre_hash: Digest,
     // pub data_root: Digest,
     pub time_stamp: TsType,
-    pub BMT_root: SeededBloomFilter,
+    // root of the bloom merkle tree over the keys of the block
+    pub BMT_root: Digest,
     pub rmt_root: Digest,
+    // commitment to the trees of the transactions sorted by each of SORTED_ATTRIBUTES
+    pub intra_index_root: Digest,
+    // commitment to the inter index and its error bounds, only set in the latest header
+    pub inter_index_root: Digest,
+    // root of the merkle mountain range over the digests of the previous headers
+    pub mmr_root: Digest,
+    // digest of the rsa accumulator over the addresses of the block
+    pub address_acc: Digest,
+    // largest trans_value of the block, which top-k queries stop at
+    pub max_value: TxType,
+    // signature of the block producer over the header digest
+    pub signature: Signature,
+}
+
+impl BlockHeader {
+    /// Transcript the producer signs the header under, binding its digest.
+    pub fn signing_transcript(&self, hasher: &dyn Hasher) -> Transcript {
+        let mut transcript = Transcript::new(b"chain-demo block header");
+        transcript.append_message(b"digest", &self.to_digest_with(hasher).0);
+        transcript
+    }
 }
 
 
 impl Digestible for BlockHeader {
-    fn to_digest(&self) -> Digest{
-        let mut state = blake2().to_state();
+    fn to_digest_with(&self, hasher: &dyn Hasher) -> Digest{
+        let mut state = hasher.context();
         state.update(&self.block_id.to_le_bytes());
         state.update(&self.pre_hash.0);
         state.update(&self.time_stamp.to_le_bytes());
-        Digest::from(state.finalize())
+        state.update(&self.BMT_root.0);
+        state.update(&self.rmt_root.0);
+        state.update(&self.intra_index_root.0);
+        state.update(&self.inter_index_root.0);
+        state.update(&self.mmr_root.0);
+        state.update(&self.address_acc.0);
+        state.update(&self.max_value.to_le_bytes());
+        state.finish()
     }
 }
 
@@ -55,4 +192,56 @@ pub struct InterIndex {
     pub start_timestamp: TsType,
     pub regression_a: FloatType,
     pub regression_b: FloatType,
-}
\ No newline at end of file
+}
+impl InterIndex {
+    /// Block id the segment predicts for `timestamp`.
+    pub fn predict(&self, timestamp: TsType) -> FloatType {
+        self.regression_a * timestamp as FloatType + self.regression_b
+    }
+}
+
+impl Digestible for InterIndex {
+    fn to_digest_with(&self, hasher: &dyn Hasher) -> Digest {
+        let mut state = hasher.context();
+        state.update(&self.start_timestamp.to_le_bytes());
+        state.update(&self.regression_a.to_le_bytes());
+        state.update(&self.regression_b.to_le_bytes());
+        state.finish()
+    }
+}
+
+/// Merkle tree whose leaves are the digests of the inter index `segments`,
+/// ordered by `start_timestamp`.
+pub fn inter_index_tree(segments: &[InterIndex], hasher: &'static dyn Hasher) -> MerkleTree<Digest> {
+    let leaves = segments.iter().map(|segment| segment.to_digest_with(hasher)).collect();
+    MerkleTree::from_vec(hasher, leaves)
+}
+
+/// Commitment to the root of `inter_index_tree` and the error bounds the
+/// segments were fitted with, stored in the latest header.
+pub fn inter_index_root(tree_root: &Digest, error_bounds: FloatType, hasher: &dyn Hasher) -> Digest {
+    let mut state = hasher.context();
+    state.update(&tree_root.0);
+    state.update(&error_bounds.to_le_bytes());
+    state.finish()
+}
+
+/// Position of the segment covering `timestamp` among segments starting at
+/// `start_timestamps`: the last one starting at or before it, or the first one.
+pub fn segment_position(start_timestamps: &[TsType], timestamp: TsType) -> usize {
+    start_timestamps.partition_point(|start| *start <= timestamp).saturating_sub(1)
+}
+
+/// Block ids predicted for the window `[left, right]` by the segments covering
+/// its ends, widened by `error_bounds` and kept within `[first_id, last_id]`.
+pub fn predicted_range(
+    start_segment: &InterIndex,
+    end_segment: &InterIndex,
+    (left, right): (TsType, TsType),
+    error_bounds: FloatType,
+    (first_id, last_id): (IdType, IdType),
+) -> (IdType, IdType) {
+    let start_id = (start_segment.predict(left) - error_bounds) as IdType;
+    let end_id = (end_segment.predict(right) + error_bounds) as IdType;
+    (start_id.clamp(first_id, last_id), end_id.clamp(first_id, last_id))
+}
@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+use anyhow::{bail, Context};
+use crate::digest::{Digest, Digestible};
+use crate::hasher::Hasher;
+use super::*;
+
+/// Peaks of a Merkle Mountain Range over the digests of the headers of the
+/// chain, enough to append the next header and to compute its root.
+///
+/// Every peak is the root of a perfect binary tree; their heights are the set
+/// bits of `leaf_count`, the highest mountain first.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MmrPeaks {
+    pub leaf_count: u64,
+    pub peaks: Vec<Digest>,
+}
+
+/// Node of an MMR, the root of the perfect subtree over the leaves
+/// `index << height..(index + 1) << height`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MmrNode {
+    pub height: u32,
+    pub index: u64,
+    pub digest: Digest,
+}
+
+impl MmrPeaks {
+    /// Appends the header digest `leaf`, merging the mountains of equal height.
+    pub fn append(&mut self, leaf: &Digest, hasher: &dyn Hasher) {
+        self.append_subtree(hash_leaf(leaf, hasher), 0, hasher);
+    }
+
+    /// Appends the header digest `leaf` as `append` does, returning the nodes
+    /// it adds, bottom up, for proofs to be built from.
+    pub fn append_with_nodes(&mut self, leaf: &Digest, hasher: &dyn Hasher) -> Vec<MmrNode> {
+        self.append_subtree(hash_leaf(leaf, hasher), 0, hasher)
+    }
+
+    /// Appends the root of a perfect subtree holding `1 << height` leaves,
+    /// returning it and the nodes merged above it. `leaf_count` has to be a
+    /// multiple of its size.
+    fn append_subtree(&mut self, mut node: Digest, mut height: u32, hasher: &dyn Hasher) -> Vec<MmrNode> {
+        debug_assert_eq!(self.leaf_count % (1 << height), 0);
+        self.leaf_count += 1 << height;
+        let mut count = (self.leaf_count >> height) - 1;
+        let mut nodes = vec![MmrNode { height, index: count, digest: node }];
+        while count & 1 == 1 {
+            // the lowest mountain has the height of the subtree
+            let left = self.peaks.pop().unwrap();
+            node = hash_nodes(&left, &node, hasher);
+            count >>= 1;
+            height += 1;
+            nodes.push(MmrNode { height, index: count, digest: node });
+        }
+        self.peaks.push(node);
+        nodes
+    }
+
+    pub fn root(&self, hasher: &dyn Hasher) -> Digest {
+        mmr_root(&self.peaks, self.leaf_count, hasher)
+    }
+}
+
+/// Proof that a header digest is the leaf at `leaf_index` of the MMR holding
+/// `leaf_count` leaves.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MmrProof {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    // siblings on the path from the leaf to the peak of its mountain, bottom up
+    pub siblings: Vec<Digest>,
+    pub peaks: Vec<Digest>,
+}
+
+impl MmrProof {
+    /// Generates the proof of the leaf at `leaf_index` among `leaves`.
+    pub fn new(leaves: &[Digest], leaf_index: u64, hasher: &dyn Hasher) -> Option<Self> {
+        let leaf_count = leaves.len() as u64;
+        let (mountain, offset, height) = mountain_of(leaf_count, leaf_index)?;
+        let start = offset as usize;
+        let siblings = path(&leaves[start..start + (1 << height)], (leaf_index - offset) as usize, hasher);
+        let peaks = mountains(leaf_count)
+            .map(|(offset, height)| subtree_root(&leaves[offset as usize..(offset + (1 << height)) as usize], hasher))
+            .collect::<Vec<_>>();
+        debug_assert!(mountain < peaks.len());
+        Some(Self { leaf_index, leaf_count, siblings, peaks })
+    }
+
+    /// Checks that `leaf` is at `leaf_index` of the MMR with root `root`.
+    pub fn verify(&self, leaf: &Digest, root: &Digest, hasher: &dyn Hasher) -> bool {
+        let (mountain, offset, height) = match mountain_of(self.leaf_count, self.leaf_index) {
+            Some(position) => position,
+            None => return false,
+        };
+        if self.siblings.len() != height as usize || self.peaks.len() != self.leaf_count.count_ones() as usize {
+            return false;
+        }
+        let mut index = self.leaf_index - offset;
+        let mut node = hash_leaf(leaf, hasher);
+        for sibling in self.siblings.iter() {
+            node = if index & 1 == 0 { hash_nodes(&node, sibling, hasher) } else { hash_nodes(sibling, &node, hasher) };
+            index >>= 1;
+        }
+        self.peaks[mountain] == node && mmr_root(&self.peaks, self.leaf_count, hasher) == *root
+    }
+
+    /// Checks that `header` is committed by the MMR of the `tip` header of the
+    /// chain with parameter `param`.
+    pub fn verify_header(&self, header: &BlockHeader, tip: &BlockHeader, param: &Parameter) -> bool {
+        let start_block_id = param.start_block_id;
+        header.block_id >= start_block_id
+            && self.leaf_index == header.block_id - start_block_id
+            && tip.block_id >= start_block_id
+            && self.leaf_count == tip.block_id - start_block_id
+            && self.verify(&header.to_digest_with(param.hasher()), &tip.mmr_root, param.hasher())
+    }
+}
+
+/// Proof that the MMR holding `old_count` leaves is a prefix of the one holding
+/// `new_count` leaves.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ConsistencyProof {
+    pub old_count: u64,
+    pub new_count: u64,
+    pub old_peaks: Vec<Digest>,
+    // roots of the perfect subtrees covering the leaves appended since, left to right
+    pub subtrees: Vec<Digest>,
+}
+
+impl ConsistencyProof {
+    /// Generates the proof that the first `old_count` of `leaves` are a prefix of them.
+    pub fn new(leaves: &[Digest], old_count: u64, hasher: &dyn Hasher) -> Option<Self> {
+        let new_count = leaves.len() as u64;
+        if old_count > new_count {
+            return None;
+        }
+        let old_peaks = mountains(old_count)
+            .map(|(offset, height)| subtree_root(&leaves[offset as usize..(offset + (1 << height)) as usize], hasher))
+            .collect();
+        let subtrees = appended_subtrees(old_count, new_count)
+            .into_iter()
+            .map(|(offset, height)| subtree_root(&leaves[offset as usize..(offset + (1 << height)) as usize], hasher))
+            .collect();
+        Some(Self { old_count, new_count, old_peaks, subtrees })
+    }
+
+    /// Checks that the MMR with root `old_root` is a prefix of the one with root `new_root`.
+    pub fn verify(&self, old_root: &Digest, new_root: &Digest, hasher: &dyn Hasher) -> bool {
+        if self.old_count > self.new_count
+            || self.old_peaks.len() != self.old_count.count_ones() as usize
+            || mmr_root(&self.old_peaks, self.old_count, hasher) != *old_root
+        {
+            return false;
+        }
+        let heights = appended_subtrees(self.old_count, self.new_count);
+        if heights.len() != self.subtrees.len() {
+            return false;
+        }
+        let mut peaks = MmrPeaks { leaf_count: self.old_count, peaks: self.old_peaks.clone() };
+        for ((_, height), subtree) in heights.into_iter().zip(self.subtrees.iter()) {
+            peaks.append_subtree(*subtree, height, hasher);
+        }
+        peaks.root(hasher) == *new_root
+    }
+
+    /// Checks that the MMR of the `old_tip` header is a prefix of the one of
+    /// the `new_tip` header, in the chain with parameter `param`.
+    pub fn verify_headers(&self, old_tip: &BlockHeader, new_tip: &BlockHeader, param: &Parameter) -> bool {
+        let start_block_id = param.start_block_id;
+        old_tip.block_id >= start_block_id
+            && self.old_count == old_tip.block_id - start_block_id
+            && new_tip.block_id >= start_block_id
+            && self.new_count == new_tip.block_id - start_block_id
+            && self.verify(&old_tip.mmr_root, &new_tip.mmr_root, param.hasher())
+    }
+}
+
+/// Root of the MMR with `peaks` holding `leaf_count` leaves: the peaks are
+/// bagged with the leaf count, so that MMRs of different sizes differ.
+pub fn mmr_root(peaks: &[Digest], leaf_count: u64, hasher: &dyn Hasher) -> Digest {
+    let mut state = hasher.context();
+    state.update(&leaf_count.to_le_bytes());
+    for peak in peaks.iter() {
+        state.update(&peak.0);
+    }
+    state.finish()
+}
+
+/// Proof that the header `block_id` is committed by the latest header, from
+/// the stored peaks, which are the ones of its MMR, and nodes.
+pub fn mmr_proof(block_id: IdType, chain: &impl ReadInterface) -> Result<MmrProof> {
+    let param = chain.get_parameter()?;
+    let tip_id = param.start_block_id + param.block_count.saturating_sub(1);
+    let peaks = chain.read_mmr_peaks()?;
+    let leaf_index = block_id.wrapping_sub(param.start_block_id);
+    let (_, _, height) = mountain_of(peaks.leaf_count, leaf_index)
+        .with_context(|| format!("block {} is not committed by the latest block {}", block_id, tip_id))?;
+    let siblings = (0..height)
+        .map(|level| chain.read_mmr_node(level, (leaf_index >> level) ^ 1))
+        .collect::<Result<Vec<Digest>>>()?;
+    Ok(MmrProof { leaf_index, leaf_count: peaks.leaf_count, siblings, peaks: peaks.peaks })
+}
+
+/// Proof that the MMR of the header `old_tip_id` is a prefix of the one of
+/// the header `new_tip_id`.
+pub fn mmr_consistency_proof(old_tip_id: IdType, new_tip_id: IdType, chain: &impl ReadInterface) -> Result<ConsistencyProof> {
+    let param = chain.get_parameter()?;
+    let last_id = param.start_block_id + param.block_count.saturating_sub(1);
+    if old_tip_id < param.start_block_id || new_tip_id < old_tip_id || new_tip_id > last_id {
+        bail!("no consistency proof from block {} to block {}", old_tip_id, new_tip_id);
+    }
+    let old_count = old_tip_id - param.start_block_id;
+    let new_count = new_tip_id - param.start_block_id;
+    // the mountains and appended subtrees are aligned, and stored once complete
+    let read_subtree = |(offset, height): (u64, u32)| chain.read_mmr_node(height, offset >> height);
+    let old_peaks = mountains(old_count).map(read_subtree).collect::<Result<Vec<Digest>>>()?;
+    let subtrees = appended_subtrees(old_count, new_count).into_iter().map(read_subtree).collect::<Result<Vec<Digest>>>()?;
+    Ok(ConsistencyProof { old_count, new_count, old_peaks, subtrees })
+}
+
+fn hash_leaf(leaf: &Digest, hasher: &dyn Hasher) -> Digest {
+    let mut state = hasher.context();
+    state.update(&[0x00]);
+    state.update(&leaf.0);
+    state.finish()
+}
+
+fn hash_nodes(left: &Digest, right: &Digest, hasher: &dyn Hasher) -> Digest {
+    let mut state = hasher.context();
+    state.update(&[0x01]);
+    state.update(&left.0);
+    state.update(&right.0);
+    state.finish()
+}
+
+/// The mountains of an MMR holding `leaf_count` leaves, as the offset of
+/// their first leaf and their height, the highest first.
+fn mountains(leaf_count: u64) -> impl Iterator<Item = (u64, u32)> {
+    let mut offset = 0;
+    (0..u64::BITS).rev()
+        .filter(move |height| leaf_count & (1 << height) != 0)
+        .map(move |height| {
+            let mountain = (offset, height);
+            offset += 1 << height;
+            mountain
+        })
+}
+
+/// Position, offset and height of the mountain holding the leaf at `leaf_index`.
+fn mountain_of(leaf_count: u64, leaf_index: u64) -> Option<(usize, u64, u32)> {
+    mountains(leaf_count)
+        .enumerate()
+        .find(|(_, (offset, height))| leaf_index >= *offset && leaf_index - offset < 1 << height)
+        .map(|(position, (offset, height))| (position, offset, height))
+}
+
+/// The largest aligned perfect subtrees covering the leaves `old_count..new_count`.
+fn appended_subtrees(old_count: u64, new_count: u64) -> Vec<(u64, u32)> {
+    let mut subtrees = Vec::new();
+    let mut offset = old_count;
+    while offset < new_count {
+        let mut height = if offset == 0 { u64::BITS - 1 } else { offset.trailing_zeros() };
+        while offset + (1 << height) > new_count {
+            height -= 1;
+        }
+        subtrees.push((offset, height));
+        offset += 1 << height;
+    }
+    subtrees
+}
+
+/// Root of the perfect tree over `leaves`, whose length is a power of two.
+fn subtree_root(leaves: &[Digest], hasher: &dyn Hasher) -> Digest {
+    if leaves.len() == 1 {
+        return hash_leaf(&leaves[0], hasher);
+    }
+    let (left, right) = leaves.split_at(leaves.len() / 2);
+    hash_nodes(&subtree_root(left, hasher), &subtree_root(right, hasher), hasher)
+}
+
+/// Siblings on the path from the leaf at `index` to the root of the perfect
+/// tree over `leaves`, bottom up.
+fn path(leaves: &[Digest], index: usize, hasher: &dyn Hasher) -> Vec<Digest> {
+    if leaves.len() == 1 {
+        return Vec::new();
+    }
+    let half = leaves.len() / 2;
+    let (left, right) = leaves.split_at(half);
+    let (mut siblings, sibling) = if index < half {
+        (path(left, index, hasher), subtree_root(right, hasher))
+    } else {
+        (path(right, index - half, hasher), subtree_root(left, hasher))
+    };
+    siblings.push(sibling);
+    siblings
+}
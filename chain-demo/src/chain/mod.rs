@@ -22,9 +22,39 @@ pub use build::*;
 pub mod query;
 pub use query::*;
 
+pub mod predicate;
+pub use predicate::*;
+
+pub mod condition;
+pub use condition::*;
+
+pub mod aggregate;
+pub use aggregate::*;
+
+pub mod account;
+pub use account::*;
+
+pub mod planner;
+pub use planner::*;
+
+pub mod subscription;
+pub use subscription::*;
+
+pub mod cache;
+pub use cache::*;
+
+pub mod parser;
+pub use parser::*;
+
 pub mod verify;
 pub use verify::*;
 
+pub mod vo;
+pub use vo::*;
+
+pub mod mmr;
+pub use mmr::*;
+
 pub type IdType = u64;
 // Timestamp size 4 bytes
 pub type TsType = u64; 
@@ -37,9 +67,6 @@ pub type TxType = u64;
 // FloatType especially for linear regression
 pub type FloatType = f64;
 
-pub static BLOOM_CAPACITY: usize = 50000;
-pub static BLOOM_FP: f64 = 0.01;
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Parameter {
     pub error_bounds: FloatType,
@@ -48,11 +75,23 @@ pub struct Parameter {
     pub start_block_id: u64,
     pub block_count: u64,
     pub inter_index_timestamps: Vec<TsType>,
+    // public key the block producer signs headers with
+    pub producer_key: PkType,
+    // modulus of the accumulators over the addresses of each block
+    pub accumulator: RsaAccumulator,
+    // hash function of every digest and merkle tree of the chain
+    pub hash_algorithm: HashAlgorithm,
+}
+
+impl Parameter {
+    pub fn hasher(&self) -> &'static dyn Hasher {
+        self.hash_algorithm.hasher()
+    }
 }
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum BTreeEnum {
-    U64(BTreeMap<u64, Transaction>),
-    String(BTreeMap<String, Transaction>),
+    U64(BTreeMap<u64, Vec<Transaction>>),
+    String(BTreeMap<String, Vec<Transaction>>),
 }
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IndexConfigs {
@@ -102,6 +141,7 @@ pub trait ReadInterface {
     fn get_parameter(&self) -> Result<Parameter>;
     fn read_block_header(&self, id: IdType) -> Result<BlockHeader>;
     fn read_block_data(&self, id: IdType) -> Result<BlockData>;
+    fn read_block_bloom(&self, id: IdType) -> Result<BlockBloom>;
     fn read_intra_index(&self, id: IdType) -> Result<IntraIndex>;
     fn read_intra_indexs_size(&self) -> usize;
     // fn read_intra_indexs(&self) -> Result<Vec<IntraIndex>>;
@@ -109,17 +149,24 @@ pub trait ReadInterface {
     fn read_inter_index(&self, timestamp: TsType) -> Result<InterIndex>;
     fn read_inter_indexs(&self) -> Result<Vec<InterIndex>>;
     fn read_index_config(&self,attribute:KeyType) -> Result<IndexConfigs>;
+    fn read_mmr_peaks(&self) -> Result<MmrPeaks>;
+    fn read_mmr_node(&self, height: u32, index: u64) -> Result<Digest>;
+    fn read_account_key(&self, address: &KeyType) -> Result<Option<PkType>>;
 }
 
 pub trait WriteInterface {
     fn set_parameter(&mut self, param: Parameter) -> Result<()>;
     fn write_block_header(&mut self, header: BlockHeader) -> Result<()>;
     fn write_block_data(&mut self, data: BlockData) -> Result<()>;
+    fn write_block_bloom(&mut self, bloom: BlockBloom) -> Result<()>;
     fn write_intra_index(&mut self, index: IntraIndex) -> Result<()>;
     fn write_transaction(&mut self, tx: Transaction) -> Result<()>;
     fn write_inter_index(&mut self, index: InterIndex) -> Result<()>;
     fn write_index_config(&mut self,config:IndexConfigs) -> Result<()>;
     fn update_intra_index(&mut self, indexs: Vec<IntraIndex>) -> Result<()>;
+    fn write_mmr_peaks(&mut self, peaks: MmrPeaks) -> Result<()>;
+    fn write_mmr_node(&mut self, node: MmrNode) -> Result<()>;
+    fn write_account_key(&mut self, address: &KeyType, key: PkType) -> Result<()>;
 }
 
 #[cfg(test)]
@@ -0,0 +1,400 @@
+use std::fmt;
+use std::ops::Bound;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use super::*;
+
+/// Error of a textual query, `position` being the offset in characters of the
+/// token it was found at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(position: usize, message: impl Into<String>) -> Self {
+        Self { position, message: message.into() }
+    }
+
+    /// `query` followed by a caret under the position of the error.
+    pub fn pointer(&self, query: &str) -> String {
+        format!("{}\n{}^", query, " ".repeat(self.position))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse a textual query into the predicates of a `QueryParam`.
+///
+/// ```text
+/// query     := [EXPLAIN] (or | SELECT function (',' function)* [WHERE or] [GROUP BY group])
+///              [TOP number] [USING option (',' option)*]
+/// or        := and (OR and)*
+/// and       := not (AND not)*
+/// not       := NOT not | '(' or ')' | condition
+/// condition := attribute ('=' | '!=' | '<>' | '>=' | '>' | '<=' | '<') literal
+///            | attribute BETWEEN literal AND literal
+///            | attribute IN '[' literal ',' literal ']'
+///            | attribute IN '(' literal (',' literal)* ')'
+///            | attribute STARTS WITH string
+/// function  := (SUM | COUNT | MIN | MAX | AVG) ['(' VALUE ')']
+/// group     := ADDRESS | BLOCK | DIRECTION | TIME_BUCKET '(' number ')'
+/// option    := BLOOM_FILTER | INTRA_INDEX | PLANNER
+/// ```
+///
+/// `NOT` binds tighter than `AND`, itself tighter than `OR`, e.g.
+/// `(address = 'addr_a' OR value BETWEEN 10 AND 50) AND NOT id = 3 AND timestamp IN [100, 300]`,
+/// a timestamp range joined to the rest with `AND` selecting the blocks.
+/// `SELECT` aggregates the values of the matching transactions, `TOP k` keeps
+/// the k of largest value. `USING planner` lets the planner choose the indexes,
+/// `EXPLAIN` returns the plan along with the result. `BETWEEN` and `IN [..]` include both ends, `IN (..)` lists values. Keywords
+/// are case insensitive and strings single quoted.
+pub fn parse_query(query: &str) -> std::result::Result<QueryParam, ParseError> {
+    let tokens = tokenize(query)?;
+    Parser { tokens, next: 0, end: query.chars().count() }.query()
+}
+
+impl FromStr for QueryParam {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        parse_query(s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Number(String),
+    Str(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Number(number) => write!(f, "number {}", number),
+            Token::Str(string) => write!(f, "string '{}'", string),
+            Token::Symbol(symbol) => write!(f, "`{}`", symbol),
+        }
+    }
+}
+
+// longest first, so that `>=` is not read as `>`
+const SYMBOLS: [&str; 11] = [">=", "<=", "!=", "<>", "=", ">", "<", "(", ")", "[", "]"];
+
+fn tokenize(query: &str) -> std::result::Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == ',' {
+            tokens.push((start, Token::Symbol(",")));
+            i += 1;
+        } else if c == '\'' {
+            // a quote inside a string is doubled
+            let mut string = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(ParseError::new(start, "unterminated string")),
+                    Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                        string.push('\'');
+                        i += 2;
+                    }
+                    Some('\'') => {
+                        i += 1;
+                        break;
+                    }
+                    Some(c) => {
+                        string.push(*c);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((start, Token::Str(string)));
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i].is_alphabetic() || chars[i] == '_') {
+                return Err(ParseError::new(start, "invalid number"));
+            }
+            tokens.push((start, Token::Number(chars[start..i].iter().collect())));
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((start, Token::Word(chars[start..i].iter().collect())));
+        } else {
+            let symbol = SYMBOLS.iter().find(|symbol| {
+                symbol.chars().enumerate().all(|(offset, s)| chars.get(i + offset) == Some(&s))
+            });
+            match symbol {
+                Some(symbol) => {
+                    tokens.push((start, Token::Symbol(symbol)));
+                    i += symbol.len();
+                }
+                None => return Err(ParseError::new(start, format!("unexpected character `{}`", c))),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    // length of the query, where running out of tokens is reported
+    end: usize,
+}
+
+impl Parser {
+    fn query(mut self) -> std::result::Result<QueryParam, ParseError> {
+        let mut q_param = QueryParam { explain: self.eat_keyword("EXPLAIN"), ..QueryParam::default() };
+        if self.eat_keyword("SELECT") {
+            let mut functions = vec![self.function()?];
+            while self.eat_symbol(",") {
+                functions.push(self.function()?);
+            }
+            // every transaction without WHERE
+            q_param.condition = Some(if self.eat_keyword("WHERE") { self.disjunction()? } else { Condition::And(Vec::new()) });
+            let group_by = if self.eat_keyword("GROUP") {
+                self.expect_keyword("BY")?;
+                Some(self.group_by()?)
+            } else {
+                None
+            };
+            q_param.aggregate = Some(Aggregate { functions, group_by });
+        } else {
+            q_param.condition = Some(self.disjunction()?);
+        }
+        if self.eat_keyword("TOP") {
+            let position = self.position();
+            match self.literal(ValueType::Number)? {
+                Value::Number(k) if k > 0 && k <= usize::MAX as u64 => q_param.top_k = Some(k as usize),
+                _ => return Err(ParseError::new(position, "top-k of no transaction")),
+            }
+        }
+        if self.eat_keyword("USING") {
+            loop {
+                let position = self.position();
+                match self.word("an option")?.to_ascii_lowercase().as_str() {
+                    "bloom_filter" => q_param.bloom_filter = true,
+                    "intra_index" => q_param.intra_index = true,
+                    "planner" => q_param.planner = true,
+                    other => return Err(ParseError::new(position, format!("unknown option `{}`", other))),
+                }
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        match self.peek() {
+            None => Ok(q_param),
+            Some((position, token)) => Err(ParseError::new(*position, format!("unexpected {}", token))),
+        }
+    }
+
+    fn function(&mut self) -> std::result::Result<AggregateFunction, ParseError> {
+        let position = self.position();
+        let function = match self.word("an aggregate function")?.to_ascii_lowercase().as_str() {
+            "sum" => AggregateFunction::Sum,
+            "count" => AggregateFunction::Count,
+            "min" => AggregateFunction::Min,
+            "max" => AggregateFunction::Max,
+            "avg" => AggregateFunction::Avg,
+            other => return Err(ParseError::new(position, format!("unknown aggregate `{}`", other))),
+        };
+        // only values are aggregated, `SUM(value)` reads better though
+        if self.eat_symbol("(") {
+            self.expect_keyword("value")?;
+            self.expect_symbol(")")?;
+        }
+        Ok(function)
+    }
+
+    fn group_by(&mut self) -> std::result::Result<GroupBy, ParseError> {
+        let position = self.position();
+        match self.word("a grouping")?.to_ascii_lowercase().as_str() {
+            "address" => Ok(GroupBy::Address),
+            "block" => Ok(GroupBy::Block),
+            "direction" => Ok(GroupBy::Direction),
+            "time_bucket" => {
+                self.expect_symbol("(")?;
+                let position = self.position();
+                let width = match self.literal(ValueType::Number)? {
+                    Value::Number(width) if width > 0 => width,
+                    _ => return Err(ParseError::new(position, "time buckets have to be wider than 0")),
+                };
+                self.expect_symbol(")")?;
+                Ok(GroupBy::TimeBucket(width))
+            }
+            other => Err(ParseError::new(position, format!("can not group by `{}`", other))),
+        }
+    }
+
+    fn disjunction(&mut self) -> std::result::Result<Condition, ParseError> {
+        let mut conditions = vec![self.conjunction()?];
+        while self.eat_keyword("OR") {
+            conditions.push(self.conjunction()?);
+        }
+        Ok(if conditions.len() == 1 { conditions.pop().unwrap() } else { Condition::Or(conditions) })
+    }
+
+    fn conjunction(&mut self) -> std::result::Result<Condition, ParseError> {
+        let mut conditions = vec![self.factor()?];
+        while self.eat_keyword("AND") {
+            conditions.push(self.factor()?);
+        }
+        Ok(if conditions.len() == 1 { conditions.pop().unwrap() } else { Condition::And(conditions) })
+    }
+
+    fn factor(&mut self) -> std::result::Result<Condition, ParseError> {
+        if self.eat_keyword("NOT") {
+            Ok(Condition::Not(Box::new(self.factor()?)))
+        } else if self.eat_symbol("(") {
+            let condition = self.disjunction()?;
+            self.expect_symbol(")")?;
+            Ok(condition)
+        } else {
+            Ok(Condition::Request(self.condition()?))
+        }
+    }
+
+    fn condition(&mut self) -> std::result::Result<QueryRequest, ParseError> {
+        let position = self.position();
+        let key = self.word("an attribute")?.to_ascii_lowercase();
+        let value_type = match attribute_type(&key) {
+            Some(value_type) => value_type,
+            None => return Err(ParseError::new(position, format!("unknown attribute `{}`", key))),
+        };
+        let predicate = if self.eat_symbol("=") {
+            Predicate::Eq { value: self.literal(value_type)? }
+        } else if self.eat_symbol("!=") || self.eat_symbol("<>") {
+            Predicate::NotEq { value: self.literal(value_type)? }
+        } else if self.eat_symbol(">=") {
+            Predicate::inclusive(Some(self.literal(value_type)?), None)
+        } else if self.eat_symbol(">") {
+            Predicate::Range { start: Bound::Excluded(self.literal(value_type)?), end: Bound::Unbounded }
+        } else if self.eat_symbol("<=") {
+            Predicate::inclusive(None, Some(self.literal(value_type)?))
+        } else if self.eat_symbol("<") {
+            Predicate::Range { start: Bound::Unbounded, end: Bound::Excluded(self.literal(value_type)?) }
+        } else if self.eat_keyword("BETWEEN") {
+            let left = self.literal(value_type)?;
+            self.expect_keyword("AND")?;
+            Predicate::inclusive(Some(left), Some(self.literal(value_type)?))
+        } else if self.eat_keyword("IN") {
+            if self.eat_symbol("(") {
+                let mut values = vec![self.literal(value_type)?];
+                while self.eat_symbol(",") {
+                    values.push(self.literal(value_type)?);
+                }
+                self.expect_symbol(")")?;
+                Predicate::In { values }
+            } else {
+                self.expect_symbol("[")?;
+                let left = self.literal(value_type)?;
+                self.expect_symbol(",")?;
+                let right = self.literal(value_type)?;
+                self.expect_symbol("]")?;
+                Predicate::inclusive(Some(left), Some(right))
+            }
+        } else if self.eat_keyword("STARTS") {
+            self.expect_keyword("WITH")?;
+            if value_type != ValueType::Text {
+                return Err(ParseError::new(position, format!("`{}` is not a text attribute", key)));
+            }
+            match self.literal(value_type)? {
+                Value::Text(prefix) => Predicate::Prefix { prefix },
+                Value::Number(_) => unreachable!("text literal"),
+            }
+        } else {
+            return Err(self.expected("a comparison, BETWEEN, IN or STARTS WITH"));
+        };
+        Ok(QueryRequest { key, predicate })
+    }
+
+    fn literal(&mut self, value_type: ValueType) -> std::result::Result<Value, ParseError> {
+        match (self.peek().cloned(), value_type) {
+            (Some((position, Token::Number(number))), ValueType::Number) => {
+                let number = number.parse::<u64>().map_err(|_| ParseError::new(position, "number out of range"))?;
+                self.next += 1;
+                Ok(Value::Number(number))
+            }
+            (Some((_, Token::Str(string))), ValueType::Text) => {
+                self.next += 1;
+                Ok(Value::Text(string))
+            }
+            (_, ValueType::Number) => Err(self.expected("a number")),
+            (_, ValueType::Text) => Err(self.expected("a quoted string")),
+        }
+    }
+
+    /// Next token as a word, `what` naming the word expected there.
+    fn word(&mut self, what: &str) -> std::result::Result<String, ParseError> {
+        match self.peek().cloned() {
+            Some((_, Token::Word(word))) => {
+                self.next += 1;
+                Ok(word)
+            }
+            _ => Err(self.expected(what)),
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some((_, Token::Word(word))) if word.eq_ignore_ascii_case(keyword) => {
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> std::result::Result<(), ParseError> {
+        if self.eat_keyword(keyword) { Ok(()) } else { Err(self.expected(keyword)) }
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some((_, Token::Symbol(s))) if *s == symbol => {
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> std::result::Result<(), ParseError> {
+        if self.eat_symbol(symbol) { Ok(()) } else { Err(self.expected(&format!("`{}`", symbol))) }
+    }
+
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.next)
+    }
+
+    fn position(&self) -> usize {
+        self.peek().map_or(self.end, |(position, _)| *position)
+    }
+
+    fn expected(&self, what: &str) -> ParseError {
+        match self.peek() {
+            Some((position, token)) => ParseError::new(*position, format!("expected {}, found {}", what, token)),
+            None => ParseError::new(self.end, format!("expected {}, found end of query", what)),
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::BTreeSet;
+use serde::{Deserialize, Serialize};
+use super::*;
+
+/// cost of reading a page: block data or inter index segment
+const C_PAGE: f64 = 1.0;
+/// cost of matching a transaction against a request
+const C_TUPLE: f64 = 0.01;
+/// share of a page a block header or bloom filter is, both being small
+const HEADER_PAGES: f64 = 0.1;
+/// size of a page in bytes, the `storage_cost` of an index config being in bytes
+const PAGE_SIZE: f64 = 4096.0;
+/// selectivities the predicates are estimated with
+const EQ_SELECTIVITY: f64 = 0.01;
+const RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+const PREFIX_SELECTIVITY: f64 = 0.1;
+
+/// How a query reads the chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryPlan {
+    /// whether the blocks of the timestamp window are located with the inter
+    /// index, instead of reading the header of every block
+    pub inter_index: bool,
+    /// whether blocks are skipped by their bloom filter and accumulator
+    pub bloom_filter: bool,
+    /// attributes looked up in the intra index of a block, the others being scanned
+    pub intra_index: Vec<KeyType>,
+    /// blocks expected to be evaluated
+    pub estimated_blocks: u64,
+    pub estimated_cost: f64,
+}
+
+/// Plan a query ran with, the plans it was chosen among, and the blocks it
+/// actually evaluated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Explain {
+    pub plan: QueryPlan,
+    pub alternatives: Vec<QueryPlan>,
+    pub actual_blocks: u64,
+}
+
+/// What the chain tells about the cost of a query.
+#[derive(Debug, Clone, PartialEq)]
+struct Statistics {
+    blocks: f64,
+    txs_per_block: f64,
+    /// blocks in the timestamp window, and the ones read to find them with the
+    /// inter index, `None` without an inter index
+    window_blocks: f64,
+    inter_blocks: Option<f64>,
+    inter_segments: f64,
+    /// pages of the intra index of an attribute in a block, from the stored index configs
+    index_pages: HashMap<KeyType, f64>,
+}
+
+impl Statistics {
+    fn new(selection: &Selection, chain: &impl ReadInterface) -> Result<Self> {
+        let param = chain.get_parameter()?;
+        let blocks = param.block_count as f64;
+        if param.block_count == 0 {
+            return Ok(Statistics {
+                blocks,
+                txs_per_block: 0.0,
+                window_blocks: 0.0,
+                inter_blocks: None,
+                inter_segments: 0.0,
+                index_pages: HashMap::new(),
+            });
+        }
+        let first_id = param.start_block_id;
+        let latest_id = first_id + param.block_count - 1;
+        // the latest block stands for the others
+        let txs_per_block = chain.read_block_data(latest_id)?.txs.len() as f64;
+        let inter_indexs = chain.read_inter_indexs()?;
+        let (window_blocks, inter_blocks) = match selection.window {
+            Some((left, right)) => {
+                // timestamps taken as evenly spread over the chain
+                let first = chain.read_block_header(first_id)?.time_stamp as f64;
+                let latest = chain.read_block_header(latest_id)?.time_stamp as f64;
+                let span = (latest - first).max(1.0);
+                let covered = (right as f64).min(latest) - (left as f64).max(first);
+                let window_blocks = if covered < 0.0 { 0.0 } else { (blocks * (covered + 1.0) / (span + 1.0)).clamp(1.0, blocks) };
+                let inter_blocks = window_range(&inter_indexs, (left, right), &param)
+                    .map(|(start_id, end_id)| (end_id.saturating_sub(start_id) + 1) as f64);
+                (window_blocks, inter_blocks)
+            }
+            None => (blocks, None),
+        };
+        let mut index_pages = HashMap::new();
+        let attributes: BTreeSet<&KeyType> = selection.condition.requests().into_iter().map(|request| &request.key).collect();
+        for attribute in attributes {
+            // without stats an index is taken to fill a page
+            let pages = chain.read_index_config(attribute.clone()).ok()
+                .filter(|configs| !configs.config.is_empty())
+                .map(|configs| {
+                    let bytes: f64 = configs.config.iter().map(|config| config.storage_cost).sum();
+                    (bytes / configs.config.len() as f64 / PAGE_SIZE).ceil().max(1.0)
+                })
+                .unwrap_or(1.0);
+            index_pages.insert(attribute.clone(), pages);
+        }
+        Ok(Statistics {
+            blocks,
+            txs_per_block,
+            window_blocks,
+            inter_blocks,
+            inter_segments: inter_indexs.len() as f64,
+            index_pages,
+        })
+    }
+
+    /// Cost of evaluating `condition` in a block, looking up the attributes of `indexed`.
+    fn evaluation_cost(&self, condition: &Condition, indexed: &[KeyType]) -> f64 {
+        condition.requests().into_iter().map(|request| {
+            let cost = QueryCost { n_pages: 0.0, c_page: C_PAGE, c_tuple: C_TUPLE, n_total_tuple: self.txs_per_block };
+            if indexed.contains(&request.key) {
+                let pages = self.index_pages.get(&request.key).copied().unwrap_or(1.0);
+                QueryCost { n_pages: pages, ..cost }.cost(1.0, selectivity(request))
+            } else {
+                cost.cost(1.0, 1.0)
+            }
+        }).sum()
+    }
+
+    /// Cheapest attributes of `condition` to look up, an intra index paying
+    /// off when its pages cost less than the transactions it spares.
+    fn indexed(&self, condition: &Condition) -> Vec<KeyType> {
+        let mut indexed: Vec<KeyType> = Vec::new();
+        for request in condition.requests() {
+            if indexed.contains(&request.key) {
+                continue;
+            }
+            let key = [request.key.clone()];
+            let lookup = self.evaluation_cost(&Condition::Request(request.clone()), &key);
+            let scan = self.evaluation_cost(&Condition::Request(request.clone()), &[]);
+            if lookup < scan {
+                indexed.push(request.key.clone());
+            }
+        }
+        indexed
+    }
+
+    /// Estimated cost of running `selection` with the given choices.
+    fn plan(
+        &self,
+        selection: &Selection,
+        top_k: Option<usize>,
+        inter_index: bool,
+        bloom_filter: bool,
+        intra_index: Vec<KeyType>,
+    ) -> QueryPlan {
+        let condition = &selection.condition;
+        let mut cost = 0.0;
+        // headers read to find the blocks of the window
+        cost += match (selection.window, inter_index, self.inter_blocks) {
+            (Some(_), true, Some(inter_blocks)) => {
+                self.inter_segments.log2().max(1.0) * C_PAGE + (inter_blocks + 2.0) * HEADER_PAGES * C_PAGE
+            }
+            (Some(_), _, _) => self.blocks * HEADER_PAGES * C_PAGE,
+            (None, _, _) => 0.0,
+        };
+        let mut blocks = self.window_blocks;
+        if bloom_filter {
+            cost += blocks * HEADER_PAGES * C_PAGE;
+            // a block is skipped when none of its transactions can match
+            if !condition.may_match(&|_| false) {
+                blocks *= 1.0 - (1.0 - condition_selectivity(condition)).powf(self.txs_per_block);
+            }
+        }
+        if let Some(k) = top_k {
+            // blocks visited by decreasing max value until k matches are found
+            let matches_per_block = condition_selectivity(condition) * self.txs_per_block;
+            if matches_per_block > 0.0 {
+                blocks = blocks.min((k as f64 / matches_per_block).ceil());
+            }
+        }
+        cost += blocks * (C_PAGE + self.evaluation_cost(condition, &intra_index));
+        QueryPlan {
+            inter_index,
+            bloom_filter,
+            intra_index,
+            estimated_blocks: blocks.ceil() as u64,
+            estimated_cost: cost,
+        }
+    }
+}
+
+/// Plans of `q_param` the planner chooses among, cheapest first; the choices
+/// of the query itself when it does not ask for the planner.
+///
+/// The statistics are only gathered for the planner or an explained query,
+/// the plan of any other query is the one it asks for, without estimates.
+pub fn plan_query(
+    q_param: &QueryParam,
+    selection: &Selection,
+    chain: &impl ReadInterface,
+) -> Result<(QueryPlan, Vec<QueryPlan>)> {
+    if !q_param.planner && !q_param.explain {
+        let param = chain.get_parameter()?;
+        // as `window_range` finds it, without reading the segments
+        let inter_available = selection.window.is_some() && param.block_count > 0 && !param.inter_index_timestamps.is_empty();
+        let plan = QueryPlan {
+            inter_index: inter_available,
+            bloom_filter: q_param.bloom_filter,
+            intra_index: requested_intra_index(q_param, selection),
+            estimated_blocks: 0,
+            estimated_cost: 0.0,
+        };
+        return Ok((plan, Vec::new()));
+    }
+    let stats = Statistics::new(selection, chain)?;
+    let inter_available = selection.window.is_some() && stats.inter_blocks.is_some();
+    let indexed = stats.indexed(&selection.condition);
+    let mut plans = Vec::new();
+    for inter_index in [true, false] {
+        if inter_index && !inter_available {
+            continue;
+        }
+        for bloom_filter in [false, true] {
+            plans.push(stats.plan(selection, q_param.top_k, inter_index, bloom_filter, indexed.clone()));
+        }
+    }
+    plans.sort_by(|a, b| a.estimated_cost.total_cmp(&b.estimated_cost));
+    let plan = if q_param.planner {
+        plans[0].clone()
+    } else {
+        stats.plan(selection, q_param.top_k, inter_available, q_param.bloom_filter, requested_intra_index(q_param, selection))
+    };
+    Ok((plan, plans))
+}
+
+/// Attributes of `selection` a query without the planner looks up in the
+/// intra index, all of them if it asks for one.
+fn requested_intra_index(q_param: &QueryParam, selection: &Selection) -> Vec<KeyType> {
+    let mut keys: Vec<KeyType> = Vec::new();
+    if q_param.intra_index {
+        for request in selection.condition.requests() {
+            if !keys.contains(&request.key) {
+                keys.push(request.key.clone());
+            }
+        }
+    }
+    keys
+}
+
+/// Blocks of the window `(left, right)` as predicted by the inter index,
+/// `None` on a chain without one.
+pub(crate) fn window_range(
+    inter_indexs: &[InterIndex],
+    (left, right): (TsType, TsType),
+    param: &Parameter,
+) -> Option<(IdType, IdType)> {
+    if inter_indexs.is_empty() || param.block_count == 0 {
+        return None;
+    }
+    let index_timestamps: Vec<TsType> = inter_indexs.iter().map(|index| index.start_timestamp).collect();
+    let start_segment = &inter_indexs[segment_position(&index_timestamps, left)];
+    let end_segment = &inter_indexs[segment_position(&index_timestamps, right)];
+    let latest_id = param.start_block_id + param.block_count - 1;
+    Some(predicted_range(start_segment, end_segment, (left, right), param.error_bounds, (param.start_block_id, latest_id)))
+}
+
+/// Share of the transactions a request is expected to match.
+fn selectivity(request: &QueryRequest) -> f64 {
+    match &request.predicate {
+        Predicate::Eq { .. } => EQ_SELECTIVITY,
+        Predicate::NotEq { .. } => 1.0 - EQ_SELECTIVITY,
+        Predicate::In { values } => (values.len() as f64 * EQ_SELECTIVITY).min(1.0),
+        Predicate::Range { .. } => match request.predicate.enumerate(MAX_ENUMERATED_VALUES) {
+            Some(values) => (values.len() as f64 * EQ_SELECTIVITY).min(RANGE_SELECTIVITY),
+            None => RANGE_SELECTIVITY,
+        },
+        Predicate::Prefix { .. } => PREFIX_SELECTIVITY,
+    }
+}
+
+/// Share of the transactions `condition` is expected to match, its terms
+/// taken as independent.
+fn condition_selectivity(condition: &Condition) -> f64 {
+    match condition {
+        Condition::And(conditions) => conditions.iter().map(condition_selectivity).product(),
+        Condition::Or(conditions) => 1.0 - conditions.iter().map(|condition| 1.0 - condition_selectivity(condition)).product::<f64>(),
+        Condition::Not(condition) => 1.0 - condition_selectivity(condition),
+        Condition::Request(request) => selectivity(request),
+    }
+}
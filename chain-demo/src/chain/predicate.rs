@@ -0,0 +1,259 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Bound;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use super::*;
+
+// at most this many values of a range are looked up in the bloom filters
+pub const MAX_ENUMERATED_VALUES: u64 = 4096;
+
+/// Value of an attribute of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Number(u64),
+    Text(String),
+}
+
+/// Type of the values of an attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Number,
+    Text,
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::Number(_) => ValueType::Number,
+            Value::Text(_) => ValueType::Text,
+        }
+    }
+
+    /// Parses `s` as a value of type `value_type`.
+    pub fn parse(value_type: ValueType, s: &str) -> Result<Self> {
+        match value_type {
+            ValueType::Number => match s.trim().parse::<u64>() {
+                Ok(number) => Ok(Value::Number(number)),
+                Err(_) => bail!("`{}` is not a number", s),
+            },
+            ValueType::Text => Ok(Value::Text(s.to_string())),
+        }
+    }
+
+    pub fn as_number(&self) -> Option<u64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            Value::Text(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Number => write!(f, "number"),
+            ValueType::Text => write!(f, "text"),
+        }
+    }
+}
+
+/// Type of the values of `attribute`, `None` if transactions do not have it.
+pub fn attribute_type(attribute: &str) -> Option<ValueType> {
+    match attribute {
+        "id" | "value" | "timestamp" => Some(ValueType::Number),
+        "address" => Some(ValueType::Text),
+        _ => None,
+    }
+}
+
+/// Value of `attribute` in `tx`, `None` if transactions do not have it.
+pub fn attribute_value(attribute: &str, tx: &Transaction) -> Option<Value> {
+    match attribute {
+        "id" => Some(Value::Number(tx.id)),
+        "value" => Some(Value::Number(tx.value.trans_value)),
+        "timestamp" => Some(Value::Number(tx.value.time_stamp)),
+        "address" => Some(Value::Text(tx.value.address.clone())),
+        _ => None,
+    }
+}
+
+/// Condition on the value of an attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Predicate {
+    Eq { value: Value },
+    NotEq { value: Value },
+    Range { start: Bound<Value>, end: Bound<Value> },
+    In { values: Vec<Value> },
+    /// text values starting with `prefix`
+    Prefix { prefix: String },
+}
+
+impl Predicate {
+    /// Range with both ends included, unbounded where `None`.
+    pub fn inclusive(start: Option<Value>, end: Option<Value>) -> Self {
+        let bound = |value: Option<Value>| value.map_or(Bound::Unbounded, Bound::Included);
+        Predicate::Range { start: bound(start), end: bound(end) }
+    }
+
+    /// Range of a `QueryParam`, both ends included and parsed as `value_type`.
+    pub fn from_range(value_type: ValueType, range: &[Option<KeyType>; 2]) -> Result<Self> {
+        let parse = |bound: &Option<KeyType>| bound.as_deref().map(|s| Value::parse(value_type, s)).transpose();
+        Ok(Predicate::inclusive(parse(&range[0])?, parse(&range[1])?))
+    }
+
+    /// Checks that every value of the predicate has type `value_type`.
+    pub fn check(&self, value_type: ValueType) -> Result<()> {
+        let values: Vec<&Value> = match self {
+            Predicate::Eq { value } | Predicate::NotEq { value } => vec![value],
+            Predicate::Range { start, end } => [start, end].iter().filter_map(|bound| bound_value(bound)).collect(),
+            Predicate::In { values } => values.iter().collect(),
+            Predicate::Prefix { .. } => {
+                if value_type != ValueType::Text {
+                    bail!("prefix of a {} attribute", value_type);
+                }
+                Vec::new()
+            }
+        };
+        match values.into_iter().find(|value| value.value_type() != value_type) {
+            Some(value) => bail!("`{}` is not a {}", value, value_type),
+            None => Ok(()),
+        }
+    }
+
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            Predicate::Eq { value: expected } => value == expected,
+            Predicate::NotEq { value: excluded } => value != excluded,
+            Predicate::Range { .. } => self.position(value) == Some(Ordering::Equal),
+            Predicate::In { values } => values.contains(value),
+            Predicate::Prefix { prefix } => match value {
+                Value::Text(text) => text.starts_with(prefix.as_str()),
+                Value::Number(_) => false,
+            },
+        }
+    }
+
+    /// Where `value` lies from the values matching the predicate, when they
+    /// form a single interval of the value order: `Less` below them, `Equal`
+    /// among them and `Greater` above them. `None` for `NotEq` and `In`.
+    pub fn position(&self, value: &Value) -> Option<Ordering> {
+        match self {
+            Predicate::Eq { value: expected } => Some(value.cmp(expected)),
+            Predicate::Range { start, end } => {
+                let above = match start {
+                    Bound::Included(start) => value >= start,
+                    Bound::Excluded(start) => value > start,
+                    Bound::Unbounded => true,
+                };
+                let below = match end {
+                    Bound::Included(end) => value <= end,
+                    Bound::Excluded(end) => value < end,
+                    Bound::Unbounded => true,
+                };
+                Some(if !above { Ordering::Less } else if !below { Ordering::Greater } else { Ordering::Equal })
+            }
+            // the texts starting with `prefix` follow it, before any other text above it
+            Predicate::Prefix { prefix } => match value {
+                Value::Text(text) if text.starts_with(prefix.as_str()) => Some(Ordering::Equal),
+                Value::Text(text) => Some(text.as_str().cmp(prefix.as_str())),
+                Value::Number(_) => None,
+            },
+            Predicate::NotEq { .. } | Predicate::In { .. } => None,
+        }
+    }
+
+    /// Smallest range of values holding every match, `None` if nothing matches.
+    ///
+    /// It can always be passed to `BTreeMap::range`, which panics on an
+    /// inverted range.
+    pub fn bounds(&self) -> Option<(Bound<Value>, Bound<Value>)> {
+        let (start, end) = match self {
+            Predicate::Eq { value } => (Bound::Included(value.clone()), Bound::Included(value.clone())),
+            Predicate::NotEq { .. } => (Bound::Unbounded, Bound::Unbounded),
+            Predicate::Range { start, end } => (start.clone(), end.clone()),
+            Predicate::In { values } => {
+                let min = values.iter().min()?;
+                let max = values.iter().max()?;
+                (Bound::Included(min.clone()), Bound::Included(max.clone()))
+            }
+            // a text starting with `prefix` is not below it
+            Predicate::Prefix { prefix } => (Bound::Included(Value::Text(prefix.clone())), Bound::Unbounded),
+        };
+        let empty = match (bound_value(&start), bound_value(&end)) {
+            (Some(left), Some(right)) => {
+                left > right
+                    || (left == right && (matches!(start, Bound::Excluded(_)) || matches!(end, Bound::Excluded(_))))
+            }
+            _ => false,
+        };
+        if empty { None } else { Some((start, end)) }
+    }
+
+    /// Every value matching the predicate, `None` if there are more than
+    /// `limit` of them or they can not be listed.
+    pub fn enumerate(&self, limit: u64) -> Option<Vec<Value>> {
+        let values = match self {
+            Predicate::Eq { value } => vec![value.clone()],
+            Predicate::In { values } => {
+                let mut values = values.clone();
+                values.sort();
+                values.dedup();
+                values
+            }
+            Predicate::Range { .. } => match self.bounds() {
+                None => Vec::new(),
+                Some((Bound::Included(Value::Text(left)), Bound::Included(Value::Text(right)))) if left == right => {
+                    vec![Value::Text(left)]
+                }
+                Some(_) => {
+                    let (left, right) = self.number_range()?;
+                    if right - left >= limit {
+                        return None;
+                    }
+                    (left..=right).map(Value::Number).collect()
+                }
+            },
+            Predicate::NotEq { .. } | Predicate::Prefix { .. } => return None,
+        };
+        if values.len() as u64 > limit { None } else { Some(values) }
+    }
+
+    /// Inclusive ends of a numeric range, `None` if it is empty or the
+    /// predicate is not a range of numbers.
+    pub fn number_range(&self) -> Option<(u64, u64)> {
+        let (start, end) = match self {
+            Predicate::Eq { .. } | Predicate::Range { .. } => self.bounds()?,
+            _ => return None,
+        };
+        let left = match &start {
+            Bound::Included(value) => value.as_number()?,
+            Bound::Excluded(value) => value.as_number()?.checked_add(1)?,
+            Bound::Unbounded => u64::MIN,
+        };
+        let right = match &end {
+            Bound::Included(value) => value.as_number()?,
+            Bound::Excluded(value) => value.as_number()?.checked_sub(1)?,
+            Bound::Unbounded => u64::MAX,
+        };
+        if left <= right { Some((left, right)) } else { None }
+    }
+}
+
+fn bound_value<T>(bound: &Bound<T>) -> Option<&T> {
+    match bound {
+        Bound::Included(value) | Bound::Excluded(value) => Some(value),
+        Bound::Unbounded => None,
+    }
+}
@@ -1,13 +1,13 @@
 use super::*;
-use anyhow::Ok;
+use anyhow::{bail, Context, Ok};
 use howlong::Duration;
 use log::info;
-use rand_core::block;
 use serde::{Serialize, Deserialize};
-use std::{collections::HashMap, ops::Bound};
-use std::sync::Mutex;
+use std::{collections::{BinaryHeap, HashMap, HashSet}, cmp::Reverse, ops::Bound};
+use std::sync::{atomic::{AtomicUsize, Ordering}, Mutex};
 use lazy_static::lazy_static;
 use std::thread;
+use num_bigint::BigUint;
 
 const QUERY_THRESHOLD: usize = 100; // 设置阈值
 lazy_static! {
@@ -16,6 +16,17 @@ lazy_static! {
     pub static ref BLOCK_ACCESS_COUNTER: Mutex<Vec<Vec<usize>>> = Mutex::new(Vec::new());
 }
 
+/// Workers a query splits its blocks among, 0 for one per available core.
+pub static QUERY_WORKERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of workers blocks are read and evaluated by, see `QUERY_WORKERS`.
+pub fn query_workers() -> usize {
+    match QUERY_WORKERS.load(Ordering::Relaxed) {
+        0 => thread::available_parallelism().map_or(1, |workers| workers.get()),
+        workers => workers,
+    }
+}
+
 struct DeferredExecution<F: FnOnce()>(Option<F>);
 
 impl<F: FnOnce()> Drop for DeferredExecution<F> {
@@ -33,6 +44,7 @@ impl<F: FnOnce()> DeferredExecution<F> {
 }
 
 #[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct QueryParam{
     #[serde(rename = "query_attribute")]
     pub key: Vec<KeyType>,
@@ -40,15 +52,55 @@ pub struct QueryParam{
     pub value: Vec<[Option<KeyType>; 2]>,
     pub bloom_filter: bool,
     pub intra_index: bool,
+    /// typed conditions, joined to the ranges above
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub predicates: Vec<QueryRequest>,
+    /// boolean condition, joined to the ranges and predicates with AND
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<Condition>,
+    /// aggregates of the matching transactions, returned next to them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggregate: Option<Aggregate>,
+    /// only the `top_k` matching transactions of largest value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+    /// the indexes chosen by their estimated cost instead of the flags above
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub planner: bool,
+    /// the plan returned along with the result
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub explain: bool,
+    /// only the blocks below this one, the `next_cursor` of the previous page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<IdType>,
+    /// matches a page stops at, once the block reaching them is done
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
 }
 
 /// res_txs for block query transactions, and boundary check.
+/// res_vos for verification objects of every block touched by the query
 /// res_sigs for aggregate_sinatures of each block
-#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// res_inter_vo for the inter index segments a timestamp window is planned with
+/// res_boundary for the headers bounding a timestamp window
+/// aggregates for the rows asked for by the query, computed over res_txs,
+/// which are returned as well: the verifier recomputes the rows from them
+/// explain for the plan of the query, when asked for
+/// next_cursor for the block a page stopped at, the cursor of the next page
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OverallResult{
     #[serde(rename = "result")]
     pub res_txs: ResultTxs,
-    // pub res_vos: ResultVos,
+    pub res_vos: ResultVos,
+    pub res_sigs: ResultSigs,
+    pub res_inter_vo: Option<InterIndexVo>,
+    pub res_boundary: Option<WindowBoundary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregates: Option<Vec<AggregateRow>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explain: Option<Explain>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<IdType>,
     pub query_param: QueryParam,
     pub query_time_ms: u64,
     pub use_inter_index: bool,
@@ -57,100 +109,158 @@ pub struct OverallResult{
 }
 
 impl OverallResult {
-    // pub async fn verify(
-    //     &self,
-    //     chain: &impl LightNodeInterface
-    // )
-    // -> Result<(VerifyResult, Duration)>{
-    //     let cpu_timer = howlong::ProcessCPUTimer::new();
-    //     let timer = howlong::HighResolutionTimer::new();
-    //     // let res = self.inner_verify(chain).await?;
-    //     let res = self.aggre_verify(chain).await?;
-    //     let time = timer.elapsed();
-    //     info!("verify used time {}",cpu_timer.elapsed());
-        
-    //     Ok((res, time))
-    // }
-
-    // async fn inner_verify(&self, chain: &impl LightNodeInterface) -> Result<VerifyResult>{
-    //     let mut result = VerifyResult::default();
-    //     let mut signature: Option<Signature>;
-    //     let mut block_header: BlockHeader;
-    //     let ctx = signing_context(b"");
-    //     for (id, txs) in self.res_txs.0.iter(){
-    //         signature = self.res_sigs.0.get(id).unwrap().to_owned();
-    //         block_header = chain.lightnode_read_block_header(id.to_owned()).await?;
-    //         if signature.eq(&Option::None){
-    //             //this means no satisfying txs in block(id)
-    //             //and the Vec stores boundary conditions 
-    //             continue;
-    //         }
-    //         let mut aggre_string_txs: String = String::from("");
-    //         let public_key = PublicKey::recover(block_header.public_key);
-    //         for tx in txs {
-    //             aggre_string_txs += &serde_json::to_string(&tx).unwrap();
-    //         }
-    //         //verify failed, malicious actions exist
-    //         if public_key.verify(ctx.bytes(aggre_string_txs.as_bytes()), &signature.unwrap()).is_err() {
-    //             result.add(InvalidReason::InvalidSignature);
-    //         }
-    //     }
-
-    //     Ok(result)
-    // }
-
-    // async fn aggre_verify(&self, chain: &impl LightNodeInterface) -> Result<VerifyResult>{
-    //     let mut result = VerifyResult::default();
-        
-
-    //     let mut sign_ctx: Vec<String> = Vec::new(); 
-    //     let mut aggre_string_txs: String = String::from("");
-    //     let mut public_keys: Vec<PublicKey> = Vec::new();
-    //     let mut signature:Option<Signature>;
-    //     let mut block_indexs:Vec<IdType> = Vec::from_iter(self.res_sigs.0.iter().map(|(x,_y)|x.to_owned()));
-    //     block_indexs.sort();
-    //     for index in block_indexs.iter(){
-    //         signature = self.res_sigs.0.get(index).unwrap().to_owned();
-    //         if signature.ne(&None) {
-    //             for tx in self.res_txs.0.get(index).unwrap().iter() {
-    //                 aggre_string_txs += &serde_json::to_string(tx).unwrap();
-    //             }
-    //             sign_ctx.push(aggre_string_txs.clone());
-    //             public_keys.push(
-    //                 PublicKey::recover(
-    //                     chain.lightnode_read_block_header(*index)
-    //                     .await
-    //                     .unwrap()
-    //                     .public_key
-    //                 )
-    //             );
-    //             aggre_string_txs.clear();
-    //         } else {
-    //             // boundary txs add to signature
-    //             for tx in self.res_txs.0.get(index).unwrap().iter() {
-    //                 aggre_string_txs += &(String::from(tx.block_id.to_string())
-    //                 + &String::from(tx.key.clone())
-    //                 + &String::from(tx.value.to_string()));
-    //                 sign_ctx.push(aggre_string_txs.clone());
-    //                 public_keys.push(
-    //                     PublicKey::recover(
-    //                         chain.lightnode_read_block_header(*index)
-    //                         .await
-    //                         .unwrap()
-    //                         .public_key
-    //                     )
-    //                 );
-    //                 aggre_string_txs.clear();
-    //             }
-    //         }
-    //     }
-    //     let ctx = signing_context(b"");
-    //     let transcripts = sign_ctx.iter().map(|m| ctx.bytes(m.as_bytes()));
-    //     if self.aggre_sign.as_ref().unwrap().verify(transcripts, &sign_ctx[..], &public_keys[..], false).is_err() {
-    //         result.add(InvalidReason::InvalidSignature);
-    //     }
-    //     Ok(result)
-    // }
+    pub async fn verify(
+        &self,
+        chain: &impl LightNodeInterface
+    )
+    -> Result<(VerifyResult, Duration)>{
+        let cpu_timer = howlong::ProcessCPUTimer::new();
+        let timer = howlong::HighResolutionTimer::new();
+        let res = self.inner_verify(chain).await?;
+        let time = timer.elapsed();
+        info!("verify used time {}",cpu_timer.elapsed());
+
+        Ok((res, time))
+    }
+
+    async fn inner_verify(&self, chain: &impl LightNodeInterface) -> Result<VerifyResult>{
+        let mut result = VerifyResult::default();
+        let selection = self.query_param.selection()?;
+        let window = selection.window;
+        let param = chain.lightnode_get_parameter().await?;
+        let block_txs: HashMap<IdType, &BlockTxs> = self.res_txs.0.iter()
+            .map(|block| (block.block_id, block))
+            .collect();
+        // the k-th value of a full top-k result, which the other matches can not exceed
+        let mut threshold = None;
+        if let Some(k) = self.query_param.top_k {
+            let mut values: Vec<TxType> = self.res_txs.txs().map(|tx| tx.value.trans_value).collect();
+            values.sort_unstable_by(|a, b| b.cmp(a));
+            if values.len() > k {
+                result.add(InvalidReason::InvalidTopK { block_id: None });
+            } else if values.len() == k {
+                threshold = Some(values[k - 1]);
+            }
+        }
+        let mut block_headers: BTreeMap<IdType, BlockHeader> = BTreeMap::new();
+        for block_vo in self.res_vos.0.iter() {
+            let block_header = chain.lightnode_read_block_header(block_vo.block_id()).await?;
+            let txs = block_txs.get(&block_vo.block_id()).copied();
+            result.append(verify_block_vo(block_vo, &block_header, txs, &selection, threshold, &param));
+            block_headers.insert(block_header.block_id, block_header);
+        }
+        // every returned block has to be backed by the vo
+        for block in self.res_txs.0.iter().filter(|block| !block_headers.contains_key(&block.block_id)) {
+            let mut tx_ids: Vec<IdType> = block.Txs.keys().copied().collect();
+            tx_ids.sort_unstable();
+            for tx_id in tx_ids {
+                result.add(InvalidReason::UnprovenResult { block_id: block.block_id, tx_id });
+            }
+        }
+        // consecutive headers have to be linked, their timestamps not decreasing
+        for (pre_header, block_header) in block_headers.values().zip(block_headers.values().skip(1)) {
+            if block_header.block_id != pre_header.block_id + 1 {
+                continue;
+            }
+            if block_header.pre_hash != pre_header.to_digest_with(param.hasher()) {
+                result.add(InvalidReason::InvalidHeaderDigest { block_id: block_header.block_id });
+            }
+            if block_header.time_stamp < pre_header.time_stamp {
+                result.add(InvalidReason::NonMonotonicTimestamp { block_id: block_header.block_id });
+            }
+        }
+        // the vo has to cover the whole block range of the query
+        let chain_end = param.start_block_id + param.block_count;
+        // a timestamp window is planned with the inter index committed in the latest
+        // header, or else spans the whole chain, its blocks outside being out of range
+        let mut planned_range = None;
+        if let (Some(window), Some(inter_vo)) = (window, self.res_inter_vo.as_ref()) {
+            if param.block_count > 0 {
+                let latest_header = chain.lightnode_read_block_header(chain_end - 1).await?;
+                planned_range = inter_vo.predicted_range(&latest_header, window, (param.start_block_id, chain_end - 1), param.hasher());
+                if planned_range.is_none() {
+                    result.add(InvalidReason::InvalidInterIndex { block_id: latest_header.block_id });
+                }
+            }
+        }
+        let first = block_headers.keys().next().copied();
+        let last = block_headers.keys().next_back().copied();
+        // a page spans the blocks from its next cursor up to below its cursor
+        let paged_below = self.query_param.cursor;
+        let paged_above = self.next_cursor;
+        let page_start = paged_above.unwrap_or(param.start_block_id).max(param.start_block_id);
+        let page_end = paged_below.unwrap_or(chain_end).min(chain_end);
+        let expected_ids = match window {
+            Some((left, right)) => {
+                // the vo has to span at least the planned range, within the page
+                if let Some((start_id, end_id)) = planned_range {
+                    let (start_id, end_id) = (start_id.max(page_start), end_id.min(page_end.saturating_sub(1)));
+                    if start_id <= end_id && page_start < page_end
+                    && (first.is_none_or(|first| first > start_id) || last.is_none_or(|last| last < end_id)) {
+                        result.add(InvalidReason::InvalidInterIndex { block_id: chain_end - 1 });
+                    }
+                }
+                // the boundary headers have to be the local ones, and lie outside of the window
+                let boundary = self.res_boundary.clone().unwrap_or_default();
+                for (header, below) in [(&boundary.left, true), (&boundary.right, false)] {
+                    if let Some(header) = header {
+                        let local_header = chain.lightnode_read_block_header(header.block_id).await.ok();
+                        let outside = if below { header.time_stamp < left } else { header.time_stamp > right };
+                        if local_header.as_ref() != Some(header) {
+                            result.add(InvalidReason::InvalidHeaderDigest { block_id: header.block_id });
+                        } else if !outside {
+                            result.add(InvalidReason::InvalidTimeRange { block_id: header.block_id });
+                        }
+                    }
+                }
+                // the blocks between the boundaries are the ones in the window, a missing
+                // boundary has to be the end of the chain or of the page
+                let lower = match (&boundary.left, first) {
+                    (Some(header), _) => header.block_id + 1,
+                    (None, _) if paged_above.is_some() => page_start,
+                    (None, Some(first)) if first > param.start_block_id => {
+                        result.add(InvalidReason::MissingBoundaryBlock { block_id: first - 1 });
+                        first
+                    }
+                    _ => param.start_block_id,
+                };
+                let upper = match (&boundary.right, last) {
+                    (Some(header), _) => header.block_id,
+                    (None, _) if paged_below.is_some() => page_end,
+                    (None, Some(last)) if last + 1 < chain_end => {
+                        result.add(InvalidReason::MissingBoundaryBlock { block_id: last + 1 });
+                        last + 1
+                    }
+                    _ => chain_end,
+                };
+                lower.max(page_start)..upper.min(page_end)
+            }
+            None => page_start..page_end,
+        };
+        for block_id in expected_ids.filter(|id| !block_headers.contains_key(id)) {
+            result.add(InvalidReason::MissingBlock { block_id });
+        }
+        // every returned block has to be signed by the producer, and its txs by their senders
+        let block_sigs: HashMap<IdType, &BlockSig> = self.res_sigs.0.iter()
+            .map(|block_sig| (block_sig.block_id(), block_sig))
+            .collect();
+        for block in self.res_txs.0.iter() {
+            let signed = match (block_sigs.get(&block.block_id), block_headers.get(&block.block_id)) {
+                (Some(block_sig), Some(block_header)) => {
+                    block_sig.header == *block_header && block_sig.verify(&param, &block.Txs)
+                }
+                _ => false,
+            };
+            if !signed {
+                result.add(InvalidReason::InvalidSignature { block_id: block.block_id, tx_id: None });
+            }
+        }
+        // the aggregates have to be the ones of the proven result
+        if self.aggregates != self.query_param.aggregate.as_ref().map(|aggregate| aggregate.compute(self.res_txs.txs())) {
+            result.add(InvalidReason::InvalidAggregate);
+        }
+        Ok(result)
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -158,73 +268,204 @@ pub struct ResultTxs(pub Vec<BlockTxs>);
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BlockTxs {
-    block_id: IdType,
-    Txs:HashMap<IdType, Transaction>
+    pub block_id: IdType,
+    pub Txs:HashMap<IdType, Transaction>
 }
 
-// #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
-// pub struct ResultVos(pub HashMap<IdType, Option<Signature>>);
-
 impl ResultTxs{
     pub fn new() -> Self{
         Self(Vec::new())
     }
+
+    /// Every returned transaction, block by block.
+    pub fn txs(&self) -> impl Iterator<Item = &Transaction> {
+        self.0.iter().flat_map(|block| block.Txs.values())
+    }
 }
 
-// impl ResultVos{
-//     pub fn new() -> Self{
-//         Self(HashMap::new())
-//     }
-// }
 // #[derive(Debug, Default, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 // pub struct TimeRange([Option<TsType>; 2]);
-#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct QueryRequest{
+    #[serde(rename = "attribute")]
     pub key:KeyType,
-    pub value: [Option<KeyType>; 2],
+    pub predicate: Predicate,
 }
 
-pub fn extract_request(q_param: &QueryParam) -> Result<Vec<QueryRequest>> {
-    let mut requests = Vec::new();
-    
-    // 获取 KEY_USAGE_COUNTER 的互斥锁
-    let mut key_usage = KEY_USAGE_COUNTER.lock().unwrap();
+impl QueryRequest {
+    /// Request of `predicate` on `key`, whose values have to be of the type of the attribute.
+    pub fn new(key: impl Into<KeyType>, predicate: Predicate) -> Result<Self> {
+        let request = QueryRequest { key: key.into(), predicate };
+        request.check()?;
+        Ok(request)
+    }
+
+    pub(crate) fn check(&self) -> Result<()> {
+        let value_type = attribute_type(&self.key).with_context(|| format!("unknown attribute `{}`", self.key))?;
+        self.predicate.check(value_type).with_context(|| format!("invalid condition on `{}`", self.key))
+    }
+
+    /// Whether the attribute of `tx` satisfies the predicate of this request.
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        attribute_value(&self.key, tx).is_some_and(|value| self.predicate.matches(&value))
+    }
+}
 
-    for x in 0..q_param.key.len() {
-        let request = QueryRequest {
-            key: q_param.key[x].clone(),
-            value: q_param.value[x].clone(),
+impl QueryParam {
+    /// The query as a timestamp window and a condition on transactions, every
+    /// value checked against the type of its attribute.
+    ///
+    /// The ranges and predicates match a transaction matching any of them, the
+    /// timestamp one being the window, which has to be a single non empty range.
+    /// `condition` is joined to them with AND, its top level timestamp ranges
+    /// narrowing the window.
+    pub fn selection(&self) -> Result<Selection> {
+        if self.key.len() != self.value.len() {
+            bail!("query has {} attributes but {} ranges", self.key.len(), self.value.len());
+        }
+        let mut requests = Vec::new();
+        for (key, range) in self.key.iter().zip(self.value.iter()) {
+            let value_type = attribute_type(key).with_context(|| format!("unknown attribute `{}`", key))?;
+            let predicate = Predicate::from_range(value_type, range)
+                .with_context(|| format!("invalid range of `{}`", key))?;
+            requests.push(QueryRequest { key: key.clone(), predicate });
+        }
+        for request in self.predicates.iter() {
+            request.check()?;
+            requests.push(request.clone());
+        }
+        let (windows, others): (Vec<QueryRequest>, Vec<QueryRequest>) = requests.into_iter()
+            .partition(|request| request.key == "timestamp");
+        if windows.len() > 1 {
+            bail!("query has {} timestamp windows", windows.len());
+        }
+        let window = match windows.first() {
+            Some(request) => Some(request.predicate.number_range().context("the timestamp window has to be a non empty range")?),
+            None => None,
+        };
+        let any = if others.is_empty() {
+            None
+        } else {
+            Some(Condition::Or(others.into_iter().map(Condition::Request).collect()))
+        };
+        let condition = match (any, &self.condition) {
+            (any, Some(condition)) => {
+                condition.check()?;
+                match any {
+                    Some(any) => any.and(condition.clone()),
+                    None => condition.clone(),
+                }
+            }
+            (Some(any), None) => any,
+            // a query of the timestamp window only matches every transaction in it
+            (None, None) => Condition::And(Vec::new()),
         };
+        if let Some(aggregate) = &self.aggregate {
+            aggregate.check()?;
+        }
+        if self.top_k == Some(0) {
+            bail!("top-k of no transaction");
+        }
+        if self.limit == Some(0) {
+            bail!("page of no transaction");
+        }
+        if self.limit.is_some() || self.cursor.is_some() {
+            if self.top_k.is_some() {
+                bail!("top-k results can not be paged");
+            }
+            if self.aggregate.is_some() {
+                bail!("aggregates can not be paged");
+            }
+        }
+        Selection::new(window, condition)
+    }
+
+    /// The query with its ranges, predicates and condition sorted and
+    /// deduplicated, the same for queries differing only in their order.
+    pub fn normalized(&self) -> QueryParam {
+        if self.key.len() != self.value.len() {
+            return self.clone();
+        }
+        let mut ranges: Vec<(KeyType, [Option<KeyType>; 2])> = self.key.iter().cloned().zip(self.value.iter().cloned()).collect();
+        ranges.sort();
+        ranges.dedup();
+        let mut predicates: Vec<(String, QueryRequest)> = self.predicates.iter()
+            .map(|request| (serde_json::to_string(request).unwrap_or_default(), request.clone()))
+            .collect();
+        predicates.sort_by(|a, b| a.0.cmp(&b.0));
+        predicates.dedup_by(|a, b| a.0 == b.0);
+        let (key, value) = ranges.into_iter().unzip();
+        QueryParam {
+            key,
+            value,
+            predicates: predicates.into_iter().map(|(_, request)| request).collect(),
+            condition: self.condition.as_ref().map(Condition::normalized),
+            ..self.clone()
+        }
+    }
+}
+
+pub fn extract_request(q_param: &QueryParam) -> Result<Selection> {
+    let selection = q_param.selection()?;
 
+    // 获取 KEY_USAGE_COUNTER 的互斥锁
+    let mut key_usage = KEY_USAGE_COUNTER.lock().unwrap();
+    for request in selection.condition.requests() {
         // 更新 key 使用计数
-        *key_usage.entry(q_param.key[x].clone()).or_insert(0) += 1;
+        *key_usage.entry(request.key.clone()).or_insert(0) += 1;
+    }
+    if selection.window.is_some() {
+        *key_usage.entry("timestamp".to_string()).or_insert(0) += 1;
+    }
 
-        requests.push(request);
+    Ok(selection)
+}
+
+/// Runs the index management once `query_counter` reaches `QUERY_THRESHOLD`.
+fn manage_indexes(query_counter: &mut usize, chain: &mut (impl ReadInterface + WriteInterface)) {
+    if *query_counter >= QUERY_THRESHOLD {
+        // 如果达到阈值，重置计数器并触发额外的函数
+        *query_counter = 0;
+        let _ = index_management(chain);
     }
+}
 
-    Ok(requests)
+/// Counts a query answered without evaluating it, e.g. from a cache, towards
+/// the query and key usage counters as `historical_query` counts its own.
+pub fn record_query(q_param: &QueryParam, chain: &mut (impl ReadInterface + WriteInterface)) -> Result<()> {
+    let mut query_counter = QUERY_COUNTER.lock().unwrap();
+    *query_counter += 1;
+    extract_request(q_param)?;
+    manage_indexes(&mut query_counter, chain);
+    Ok(())
 }
 
-pub fn historical_query(q_param: &QueryParam, chain: &mut (impl ReadInterface + WriteInterface)) 
+pub fn historical_query(q_param: &QueryParam, chain: &mut (impl ReadInterface + WriteInterface + Sync)) 
  -> Result<OverallResult>{
     let mut query_counter = QUERY_COUNTER.lock().unwrap();
     *query_counter+=1;
     info!("process query {:?}; query_counter:{:?}", q_param,query_counter);
-    if q_param.key.len()!=q_param.value.len(){
-        panic!("key's len is not equal to value's len");
-    }
+    let selection = extract_request(q_param)?;
+    let (plan, alternatives) = plan_query(q_param, &selection, chain)?;
+    info!("plan {:?}", plan);
     let mut param = chain.get_parameter()?;
-    param.intra_index = q_param.intra_index;
-    param.inter_index = q_param.bloom_filter;
+    param.intra_index = !plan.intra_index.is_empty();
+    param.inter_index = plan.inter_index;
 
     let cpu_timer = howlong::ProcessCPUTimer::new();
     let timer = howlong::HighResolutionTimer::new();
-    let mut res_txs = ResultTxs::new();
-    // let mut res_vos = ResultVos::new();
+    let mut res_inter_vo = None;
+    let mut res_boundary = None;
 
     let mut result = OverallResult {
-        res_txs: res_txs.clone(),
-        // res_vos: res_vos.clone(),
+        res_txs: ResultTxs::new(),
+        res_vos: ResultVos::new(),
+        res_sigs: ResultSigs::new(),
+        res_inter_vo: None,
+        res_boundary: None,
+        aggregates: None,
+        explain: None,
+        next_cursor: None,
         query_param: q_param.clone(),
         query_time_ms: 0,
         use_inter_index: param.inter_index,
@@ -232,269 +473,487 @@ pub fn historical_query(q_param: &QueryParam, chain: &mut (impl ReadInterface +
         vo_size:0
     };
     //query block_header & block_data within the query range of timestamp
-    if q_param.key.contains(&"timestamp".to_string()) {
+    let walk = if plan.inter_index {
         info!("query_inter_index");
-        res_txs=query_chain_inter_index(&q_param, chain)?;
+        let (mut walk, inter_vo) = query_chain_inter_index(q_param, &selection, &plan, chain)?;
+        res_inter_vo = Some(inter_vo);
+        res_boundary = Some(std::mem::take(&mut walk.boundary));
+        walk
     } else {
         info!("query_no_inter_index");
-        res_txs=query_chain_no_inter_index(&q_param, chain)?;
+        query_chain_no_inter_index(q_param, &selection, &plan, chain)?
+    };
+    let res_txs = walk.res_txs;
+    let res_vos = walk.res_vos;
+    result.next_cursor = walk.next_cursor;
+    if q_param.explain {
+        let actual_blocks = res_vos.0.iter().filter(|block_vo| matches!(block_vo, BlockVo::Evaluated { .. } | BlockVo::Indexed { .. })).count() as u64;
+        result.explain = Some(Explain { plan, alternatives, actual_blocks });
+    }
+    let mut res_sigs = ResultSigs::new();
+    for block in res_txs.0.iter() {
+        let block_header = chain.read_block_header(block.block_id)?;
+        res_sigs.0.push(BlockSig::new(block_header, &param, &block.Txs));
     }
     //query inside block to check if consist key
-    let mut vo_size=0;
+    let vo_size=res_vos.size() + res_sigs.size()
+        + res_inter_vo.as_ref().map_or(0, |vo| vo.size())
+        + res_boundary.as_ref().map_or(0, |vo| vo.size());
     result.vo_size=vo_size;
     result.res_txs=res_txs;
+    result.res_vos=res_vos;
+    result.res_sigs=res_sigs;
+    result.res_inter_vo=res_inter_vo;
+    result.res_boundary=res_boundary;
+    result.aggregates = q_param.aggregate.as_ref().map(|aggregate| aggregate.compute(result.res_txs.txs()));
     info!("used time: {:?}", cpu_timer.elapsed());
     info!("vo_size: {:?}", vo_size);
+    result.query_time_ms = timer.elapsed().as_millis() as u64;
     let _deferred_execution = DeferredExecution::new(|| {
         // let mut query_counter = QUERY_COUNTER.lock().unwrap();
-        if *query_counter >= QUERY_THRESHOLD {
-            // 如果达到阈值，重置计数器并触发额外的函数
-            *query_counter = 0;
-            let _ = index_management(chain);
-        }
+        manage_indexes(&mut query_counter, chain);
     });
     Ok(result)
 }
 
-/// return BlockData & BlockHeader falls in the timestamp range
+/// Pages of the result of a query with a `limit`, from the latest block down,
+/// each resuming below the block the previous one stopped at.
+pub struct Pages<'a, C> {
+    q_param: Option<QueryParam>,
+    chain: &'a mut C,
+}
+
+pub fn paged_query<'a, C: ReadInterface + WriteInterface + Sync>(q_param: &QueryParam, chain: &'a mut C) -> Pages<'a, C> {
+    Pages { q_param: Some(q_param.clone()), chain }
+}
+
+impl<'a, C: ReadInterface + WriteInterface + Sync> Iterator for Pages<'a, C> {
+    type Item = Result<OverallResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut q_param = self.q_param.take()?;
+        let result = historical_query(&q_param, self.chain);
+        if let Some(cursor) = result.as_ref().ok().and_then(|result| result.next_cursor) {
+            q_param.cursor = Some(cursor);
+            self.q_param = Some(q_param);
+        }
+        Some(result)
+    }
+}
+
+/// return BlockData & BlockHeader falls in the timestamp range, the proof of
+/// the inter index segments the range is planned with, and the headers of the
+/// blocks bounding the window
 fn query_chain_inter_index(
     q_param: &QueryParam,
-    chain: &impl ReadInterface,
-) -> Result<ResultTxs>{
+    selection: &Selection,
+    plan: &QueryPlan,
+    chain: &(impl ReadInterface + Sync),
+) -> Result<(Walk, InterIndexVo)>{
     info!("query using inter_index");
-    let mut res_txs = ResultTxs::new();
     let param = chain.get_parameter()?;
     let inter_indexs = chain.read_inter_indexs()?;
-    let index_timestamps = inter_indexs.iter().map(|x| x.start_timestamp.to_owned() as TsType).collect::<Vec<TsType>>();
-    let requests=extract_request(q_param)?;
-    let mut timestamp_range: [Option<String>; 2] = [
-        Some("20212332".to_string()), 
-        Some("20222332".to_string())
-    ];
-    for request in &requests{
-        if request.key=="timestamp".to_string(){
-            timestamp_range=request.value.clone();
-        }
-    }
-    let left_timestamp = timestamp_range[0].as_ref().and_then(|s| s.parse::<u64>().ok()).unwrap();
-    let right_timestamp = timestamp_range[1].as_ref().and_then(|s| s.parse::<u64>().ok()).unwrap();
-    // use learned index with err
-    let start_inter_index = chain.read_inter_index(variant_binary_search(&index_timestamps[..], left_timestamp))?;
-    let end_inter_index = chain.read_inter_index(variant_binary_search(&index_timestamps[..], right_timestamp))?;
-    let mut start_id = (start_inter_index.regression_a * left_timestamp as FloatType + start_inter_index.regression_b - param.error_bounds as FloatType) as IdType;
-    let mut end_id = (end_inter_index.regression_a * right_timestamp as FloatType + end_inter_index.regression_b + param.error_bounds as FloatType) as IdType;
-    // do not exceed block_index boundary
-    start_id = start_id.max(param.start_block_id);
-    end_id = end_id.min(param.start_block_id + param.block_count - 1);
+    let (left_timestamp, right_timestamp) = selection.window.context("no timestamp range in query")?;
+    // use learned index with err, not exceeding block_index boundary
+    let latest_id = param.start_block_id + param.block_count - 1;
+    let (mut start_id, mut end_id) = window_range(&inter_indexs, (left_timestamp, right_timestamp), &param)
+        .context("no inter index on the chain")?;
+    let inter_vo = InterIndexVo::new(latest_id, param.error_bounds, &inter_indexs, (left_timestamp, right_timestamp), param.hasher());
+    // reach the blocks just outside the window where the learned index falls short,
+    // timestamps being non-decreasing along the chain
+    while start_id > param.start_block_id && chain.read_block_header(start_id)?.time_stamp >= left_timestamp {
+        start_id -= 1;
+    }
+    while end_id < latest_id && chain.read_block_header(end_id)?.time_stamp <= right_timestamp {
+        end_id += 1;
+    }
     info!("start_id {}, end_id {}",start_id, end_id);
     // eliminate err_bounds
-    let mut index = end_id;
-    while index >= start_id{
-        let block_header = chain.read_block_header(index)?;
-        if block_header.time_stamp >= left_timestamp
-        && block_header.time_stamp <= right_timestamp{
-            if q_param.bloom_filter
-            && !judge_contain_key(&requests, block_header.BMT_root) {
-                index-=1;
-                continue;
+    let block_ids: Vec<IdType> = (start_id..=end_id).rev()
+        .filter(|block_id| q_param.cursor.is_none_or(|cursor| *block_id < cursor))
+        .collect();
+    let walk = walk_blocks(&block_ids, selection, q_param, plan, start_id, &param, chain)?;
+    Ok((walk, inter_vo))
+}
+/// Whether the bloom filter of a block lets through a match of `condition`.
+pub(crate) fn judge_contain_key(condition: &Condition, block_bloom: &BlockBloom, hasher: &'static dyn Hasher) -> bool {
+    condition.may_match(&|key| block_bloom.contains(key, hasher))
+}
+
+/// Primes of the addresses one of which a block must hold to contain a
+/// result, `None` if the condition does not restrict addresses to a list.
+pub fn accumulator_keys(condition: &Condition, hasher: &dyn Hasher) -> Option<Vec<BigUint>> {
+    let addresses = condition.required_addresses()?;
+    if addresses.is_empty() {
+        return None;
+    }
+    Some(addresses.iter().map(|address| address_prime(address, hasher)).collect())
+}
+
+/// Vos and matches of the blocks a query walks, with the headers bounding its
+/// window, and the block a page stopped at if any are left below it.
+#[derive(Debug, Default)]
+pub(crate) struct Walk {
+    pub(crate) res_txs: ResultTxs,
+    pub(crate) res_vos: ResultVos,
+    pub(crate) boundary: WindowBoundary,
+    pub(crate) next_cursor: Option<IdType>,
+}
+
+/// Walks `block_ids`, from the latest, the blocks out of the window being out
+/// of range and the others pruned or evaluated. With a `limit`, a few blocks
+/// per worker are walked at a time, the page stopping after the block its
+/// matches reach the limit at.
+pub(crate) fn walk_blocks(
+    block_ids: &[IdType],
+    selection: &Selection,
+    q_param: &QueryParam,
+    plan: &QueryPlan,
+    first_id: IdType,
+    param: &Parameter,
+    chain: &(impl ReadInterface + Sync),
+) -> Result<Walk> {
+    let condition = &selection.condition;
+    let excluded = accumulator_keys(condition, param.hasher());
+    let batch = match q_param.limit {
+        Some(_) => query_workers(),
+        None => block_ids.len(),
+    };
+    let mut walk = Walk::default();
+    let mut walked = 0;
+    let mut matched = 0;
+    for batch_ids in block_ids.chunks(batch.max(1)) {
+        let visits = map_blocks(batch_ids, |block_id| {
+            visit_block(block_id, selection.window, plan.bloom_filter, condition, excluded.as_deref(), param, chain)
+        })?;
+        let candidates: Vec<IdType> = batch_ids.iter().zip(visits.iter())
+            .filter(|(_, visit)| matches!(visit, BlockVisit::Candidate))
+            .map(|(block_id, _)| *block_id)
+            .collect();
+        let mut evaluated: HashMap<IdType, (BlockVo, Option<HashMap<IdType,Transaction>>)> =
+            evaluate_blocks(&candidates, condition, q_param.top_k, &plan.intra_index, first_id, param, chain)?
+                .into_iter()
+                .map(|(block_id, block_vo, block_txs)| (block_id, (block_vo, block_txs)))
+                .collect();
+        for (block_id, visit) in batch_ids.iter().copied().zip(visits) {
+            walked += 1;
+            match visit {
+                BlockVisit::OutOfRange(block_header) => {
+                    walk.res_vos.0.push(BlockVo::OutOfRange { block_id });
+                    // visiting downwards, the last block above the window and the first one below it
+                    if selection.window.is_some_and(|(_, right)| block_header.time_stamp > right) {
+                        walk.boundary.right = Some(block_header);
+                    } else if walk.boundary.left.is_none() {
+                        walk.boundary.left = Some(block_header);
+                    }
+                }
+                BlockVisit::Pruned(block_vo) => walk.res_vos.0.push(block_vo),
+                BlockVisit::Candidate => {
+                    let (block_vo, block_txs) = evaluated.remove(&block_id).context("block left unevaluated")?;
+                    walk.res_vos.0.push(block_vo);
+                    match block_txs {
+                        Some(block_txs) if !block_txs.is_empty() => {
+                            matched += block_txs.len();
+                            walk.res_txs.0.push(BlockTxs { block_id, Txs: block_txs });
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            if q_param.limit.is_some_and(|limit| matched >= limit) && walked < block_ids.len() {
+                walk.next_cursor = Some(block_id);
+                return Ok(walk);
             }
-            let mut counter = BLOCK_ACCESS_COUNTER.lock().unwrap();
-            let height = index;
-            if counter.is_empty() {
-             // 初始化第一行
-             counter.push(Vec::new());
-             }
-             let index_1=(height-start_id) as usize;
-             if let Some(last_row) = counter.last_mut() {
-                 if index_1 >= last_row.len(){
-                     last_row.resize((index_1 + 1), 0);
-                 }
-                 last_row[index_1] += 1;
-             }
-            let block_txs=query_in_block(&requests,index,chain)?;
-            let block_res = BlockTxs {
-                block_id:index,
-                Txs: block_txs,
-            };
-            res_txs.0.push(block_res);
         }
-        index -= 1;
     }
+    Ok(walk)
+}
 
-    
-    Ok((res_txs))
+/// What a block of the range a query walks is before being evaluated.
+enum BlockVisit {
+    /// outside of the timestamp window, with its header
+    OutOfRange(BlockHeader),
+    /// skipped by its bloom filter or accumulator
+    Pruned(BlockVo),
+    Candidate,
 }
-fn judge_contain_key(requests: &Vec<QueryRequest>, bf: SeededBloomFilter) -> bool {
-    for request in requests {
-        if request.key == "timestamp".to_string() {
-            continue;
+
+fn visit_block(
+    block_id: IdType,
+    window: Option<(TsType, TsType)>,
+    bloom_filter: bool,
+    condition: &Condition,
+    excluded: Option<&[BigUint]>,
+    param: &Parameter,
+    chain: &impl ReadInterface,
+) -> Result<BlockVisit> {
+    if let Some((left, right)) = window {
+        let block_header = chain.read_block_header(block_id)?;
+        if block_header.time_stamp < left || block_header.time_stamp > right {
+            return Ok(BlockVisit::OutOfRange(block_header));
+        }
+    }
+    if bloom_filter {
+        if let Some(block_vo) = prune_block(condition, excluded, block_id, param, chain)? {
+            return Ok(BlockVisit::Pruned(block_vo));
         }
+    }
+    Ok(BlockVisit::Candidate)
+}
 
-        if request.key == "address" {
-            if bf.contains(&request.value[0].clone().unwrap()) {
-                info!{"address exists"}
-                return true
-            }
-        }else{
-             let (left, right) = match (request.value[0].as_ref().and_then(|s| s.parse::<u64>().ok()), request.value[1].as_ref().and_then(|s| s.parse::<u64>().ok())) {
-                (Some(l), Some(r)) => (l, r),
-                 _ => panic!("inexistence of left bound or right bound from judge_contain_key"), 
-                 };
+/// `f` of every block of `block_ids`, in their order, the ids being split in
+/// contiguous runs across `query_workers()` threads.
+fn map_blocks<T: Send>(block_ids: &[IdType], f: impl Fn(IdType) -> Result<T> + Sync) -> Result<Vec<T>> {
+    let workers = query_workers().min(block_ids.len());
+    if workers <= 1 {
+        return block_ids.iter().map(|block_id| f(*block_id)).collect();
+    }
+    let run = block_ids.len().div_ceil(workers);
+    thread::scope(|scope| {
+        let handles: Vec<_> = block_ids.chunks(run)
+            .map(|run| scope.spawn(|| run.iter().map(|block_id| f(*block_id)).collect::<Result<Vec<T>>>()))
+            .collect();
+        let mut res = Vec::with_capacity(block_ids.len());
+        for handle in handles {
+            res.extend(handle.join().map_err(|_| anyhow::anyhow!("block worker panicked"))??);
+        }
+        Ok(res)
+    })
+}
 
-            for i in left..=right { // 遍历左、右边界间的所有值
-                 if bf.contains(&i) {
-                  return true; // 如果存在布隆过滤器中的值，则返回 true
-              }
-           }
+/// Vo skipping the block `block_id` when it can not hold a result: its bloom
+/// filter rejects the keys of `condition`, or its accumulator excludes every
+/// address of `excluded`, which bloom filter false positives do not defeat.
+fn prune_block(
+    condition: &Condition,
+    excluded: Option<&[BigUint]>,
+    block_id: IdType,
+    param: &Parameter,
+    chain: &impl ReadInterface,
+) -> Result<Option<BlockVo>> {
+    let block_bloom = chain.read_block_bloom(block_id)?;
+    if !judge_contain_key(condition, &block_bloom, param.hasher()) {
+        return Ok(Some(BlockVo::BloomSkipped { bloom: block_bloom }));
+    }
+    if let Some(excluded) = excluded {
+        let primes = chain.read_block_data(block_id)?.address_primes(param.hasher());
+        if let Some(witness) = param.accumulator.prove_non_membership(&primes, excluded) {
+            let value = param.accumulator.accumulate(&primes);
+            return Ok(Some(BlockVo::AccumulatorSkipped { block_id, value, witness }));
         }
     }
-    false
+    Ok(None)
 }
 
-fn query_in_block(
-    requests: &Vec<QueryRequest>,
+/// A block with its vo, and its matches unless a top-k query stopped before it.
+type EvaluatedBlock = (IdType, BlockVo, Option<HashMap<IdType,Transaction>>);
+
+/// Evaluates the blocks `block_ids` left after pruning, in the given order,
+/// with their matches, `None` for the blocks a top-k query stops before.
+///
+/// The blocks are evaluated in parallel, except for a top-k query, which
+/// visits them by decreasing `max_value` of their header and stops once the
+/// next one can not beat the k-th value found, returning only the k largest matches.
+fn evaluate_blocks(
+    block_ids: &[IdType],
+    condition: &Condition,
+    top_k: Option<usize>,
+    indexed: &[KeyType],
+    first_id: IdType,
+    param: &Parameter,
+    chain: &(impl ReadInterface + Sync),
+) -> Result<Vec<EvaluatedBlock>> {
+    let Some(k) = top_k else {
+        let evaluated = map_blocks(block_ids, |block_id| evaluate_block(block_id, condition, indexed, first_id, chain))?;
+        return Ok(block_ids.iter().zip(evaluated).map(|(block_id, (block_data, res))| {
+            let block_vo = BlockVo::evaluated(&block_data, &res, condition, param.hasher());
+            (*block_id, block_vo, Some(res))
+        }).collect());
+    };
+    let mut max_values = HashMap::new();
+    for block_id in block_ids {
+        max_values.insert(*block_id, chain.read_block_header(*block_id)?.max_value);
+    }
+    let mut order = block_ids.to_vec();
+    order.sort_by_key(|block_id| (Reverse(max_values[block_id]), Reverse(*block_id)));
+    let mut evaluated = HashMap::new();
+    // the k largest (value, tx id) found so far, smallest on top
+    let mut top: BinaryHeap<Reverse<(TxType, IdType)>> = BinaryHeap::new();
+    for block_id in order {
+        if top.len() == k && top.peek().is_some_and(|Reverse((kth, _))| max_values[&block_id] <= *kth) {
+            break;
+        }
+        let (block_data, res) = evaluate_block(block_id, condition, indexed, first_id, chain)?;
+        for tx in res.values() {
+            top.push(Reverse((tx.value.trans_value, tx.id)));
+            if top.len() > k {
+                top.pop();
+            }
+        }
+        evaluated.insert(block_id, (block_data, res));
+    }
+    let kept: HashSet<IdType> = top.iter().map(|Reverse((_, tx_id))| *tx_id).collect();
+    let mut blocks = Vec::new();
+    for block_id in block_ids {
+        match evaluated.remove(block_id) {
+            Some((block_data, mut res)) => {
+                res.retain(|tx_id, _| kept.contains(tx_id));
+                let block_vo = BlockVo::evaluated(&block_data, &res, condition, param.hasher());
+                blocks.push((*block_id, block_vo, Some(res)));
+            }
+            None => blocks.push((*block_id, BlockVo::BelowTopK { block_id: *block_id }, None)),
+        }
+    }
+    Ok(blocks)
+}
+
+/// Data of the block `block_id` with its transactions matching `condition`.
+fn evaluate_block(
     block_id: IdType,
+    condition: &Condition,
+    indexed: &[KeyType],
+    first_id: IdType,
     chain: &impl ReadInterface,
-) -> Result<(HashMap<IdType,Transaction>)> {
+) -> Result<(BlockData, HashMap<IdType,Transaction>)> {
+    record_block_access(block_id, first_id);
     let block_data = chain.read_block_data(block_id)?;
-    let intraindex=chain.read_intra_index(block_id)?;
+    let intraindex = chain.read_intra_index(block_id)?;
+    let res = evaluate_in_block(condition, &block_data, &intraindex, indexed)?;
+    Ok((block_data, res))
+}
+
+fn record_block_access(block_id: IdType, first_id: IdType) {
+    let mut counter = BLOCK_ACCESS_COUNTER.lock().unwrap();
+    if counter.is_empty() {
+        // 初始化第一行
+        counter.push(Vec::new());
+    }
+    let index_1 = (block_id - first_id) as usize;
+    if let Some(last_row) = counter.last_mut() {
+        if index_1 >= last_row.len() {
+            last_row.resize(index_1 + 1, 0);
+        }
+        last_row[index_1] += 1;
+    }
+}
+
+/// Transactions of a block matching `condition`, intersecting the matches of
+/// AND, unioning the ones of OR and taking NOT from the whole block, the
+/// requests on `indexed` attributes being looked up in their intra index if any.
+fn evaluate_in_block(
+    condition: &Condition,
+    block_data: &BlockData,
+    intraindex: &IntraIndex,
+    indexed: &[KeyType],
+) -> Result<HashMap<IdType,Transaction>> {
     let mut res:HashMap<IdType,Transaction> = HashMap::new();
-    for request in requests{
-        let index_key = request.key.clone();
-            if index_key == "timestamp".to_string() {
-                continue;
-            }
-            let _ = match intraindex.index.get(&index_key) {
-                Some(btree) => query_with_intra_index(&mut res,btree, &request.value),
-                None => query_no_intra_index(&mut res,&request.key,&block_data,&request.value),
+    match condition {
+        Condition::And(conditions) => {
+            let mut conditions = conditions.iter();
+            res = match conditions.next() {
+                Some(condition) => evaluate_in_block(condition, block_data, intraindex, indexed)?,
+                None => block_data.txs.iter().map(|tx| (tx.id, tx.clone())).collect(),
             };
+            for condition in conditions {
+                if res.is_empty() {
+                    break;
+                }
+                let matched = evaluate_in_block(condition, block_data, intraindex, indexed)?;
+                res.retain(|id, _| matched.contains_key(id));
+            }
+        }
+        Condition::Or(conditions) => {
+            for condition in conditions {
+                res.extend(evaluate_in_block(condition, block_data, intraindex, indexed)?);
+            }
+        }
+        Condition::Not(condition) => {
+            let matched = evaluate_in_block(condition, block_data, intraindex, indexed)?;
+            for tx in block_data.txs.iter().filter(|tx| !matched.contains_key(&tx.id)) {
+                res.insert(tx.id, tx.clone());
+            }
+        }
+        Condition::Request(request) => match intraindex.index.get(&request.key).filter(|_| indexed.contains(&request.key)) {
+            Some(btree) => query_with_intra_index(&mut res,btree, &request.predicate)?,
+            None => query_no_intra_index(&mut res,request,block_data)?,
+        },
     }
     Ok(res)
 }
 
 
+/// Evaluates every block of the chain, or of the timestamp window when the
+/// plan does not locate it with the inter index, the others being out of range.
 fn query_chain_no_inter_index(
     q_param: &QueryParam,
-    chain: &impl ReadInterface,
-) -> Result<(ResultTxs)>{
-    let requests=extract_request(q_param)?;
-    let mut res_txs = ResultTxs::new();
-    let start_index = chain.get_parameter()?.start_block_id;
-    let mut block_index = start_index + chain.get_parameter()?.block_count.clone() - 1;
-    while block_index >= start_index as u64 {
-        let block_header = chain.read_block_header(block_index)?;
-            if q_param.bloom_filter
-            && !judge_contain_key(&requests, block_header.BMT_root) {
-                block_index-=1;
-                continue;
-            }
-            let mut counter = BLOCK_ACCESS_COUNTER.lock().unwrap();
-            let height = block_index;
-            if counter.is_empty() {
-                // 初始化第一行
-                counter.push(Vec::new());
-                }
-                let index_1=(height-start_index) as usize;
-                if let Some(last_row) = counter.last_mut() {
-                    if index_1 >= last_row.len(){
-                        last_row.resize((index_1 + 1), 0);
-                    }
-                    last_row[index_1] += 1;
-                }
-            let block_txs=query_in_block(&requests,block_index,chain)?;
-            if !block_txs.is_empty(){
-                let block_res = BlockTxs {
-                    block_id:block_index,
-                    Txs: block_txs,
-                };
-                res_txs.0.push(block_res);
-            }
-            block_index -= 1;
-    }
-
-    Ok(res_txs)
+    selection: &Selection,
+    plan: &QueryPlan,
+    chain: &(impl ReadInterface + Sync),
+) -> Result<Walk>{
+    let param = chain.get_parameter()?;
+    let start_index = param.start_block_id;
+    let block_ids: Vec<IdType> = (start_index..start_index + param.block_count).rev()
+        .filter(|block_id| q_param.cursor.is_none_or(|cursor| *block_id < cursor))
+        .collect();
+    walk_blocks(&block_ids, selection, q_param, plan, start_index, &param, chain)
 }
 
+/// Transactions of `btree` satisfying `predicate`, looked up within its bounds
+/// and then filtered as `query_no_intra_index` does.
 fn query_with_intra_index(
     res : &mut HashMap<IdType,Transaction>,
     btree: &BTreeEnum,
-    values: &[Option<KeyType>; 2],
+    predicate: &Predicate,
 ) -> Result<()> {
+    let (start, end) = match predicate.bounds() {
+        Some(bounds) => bounds,
+        None => return Ok(()),
+    };
     match btree {
         BTreeEnum::U64(btree_map) => {
-            let start = values[0].as_ref().and_then(|s| s.parse::<u64>().ok());
-            let end = values[1].as_ref().and_then(|s| s.parse::<u64>().ok());
-            let start_bound = start.map_or(Bound::Unbounded, Bound::Included);
-            let end_bound = end.map_or(Bound::Unbounded, Bound::Excluded);
-            for (_, v) in btree_map.range((start_bound, end_bound)) {
-                res.entry(v.id).or_insert_with(|| v.clone());
+            let number = |value: Value| value.as_number().context("text value on a numeric index");
+            let range = (map_bound(start, number)?, map_bound(end, number)?);
+            for (key, txs) in btree_map.range(range) {
+                if predicate.matches(&Value::Number(*key)) {
+                    for tx in txs {
+                        res.entry(tx.id).or_insert_with(|| tx.clone());
+                    }
+                }
             }
         },
         BTreeEnum::String(btree_map) => {
-            let start = values[0].as_ref();
-            let end = values[1].as_ref();
-            let start_bound = start.map_or(Bound::Unbounded, |s| Bound::Included(s.clone()));
-            let end_bound = end.map_or(Bound::Unbounded, |s| Bound::Excluded(s.clone()));
-            for (_, v) in btree_map.range((start_bound, end_bound)) {
-                res.entry(v.id).or_insert_with(|| v.clone());
+            let text = |value: Value| match value {
+                Value::Text(text) => Ok(text),
+                Value::Number(_) => bail!("numeric value on a text index"),
+            };
+            let range = (map_bound(start, text)?, map_bound(end, text)?);
+            for (key, txs) in btree_map.range(range) {
+                if predicate.matches(&Value::Text(key.clone())) {
+                    for tx in txs {
+                        res.entry(tx.id).or_insert_with(|| tx.clone());
+                    }
+                }
             }
         },
-        // ... 其他枚举匹配
-        _ => panic!("BTreeEnum type mismatch"),
     }
-    
     Ok(())
-    // if let BTreeEnum::U64(btree_map) = btree {
-    //     let start = values[0].as_ref().and_then(|s| s.parse::<u64>().ok());
-    //     let end = values[1].as_ref().and_then(|s| s.parse::<u64>().ok());
-    //     let start_bound = start.map_or(Bound::Unbounded, Bound::Included);
-    //     let end_bound = end.map_or(Bound::Unbounded, Bound::Excluded);
-    //     for (_, v) in btree_map.range((start_bound, end_bound)) {
-    //         res.insert(v.id, *v);
-    //     }
-    // } else if let BTreeEnum::String(btree_map) = btree {
-    //     let start = values[0].as_ref();
-    //     let end = values[1].as_ref();
-    //     let start_bound = start.map_or(Bound::Unbounded, |s| Bound::Included(*s));
-    //     let end_bound = end.map_or(Bound::Unbounded, |s| Bound::Excluded(*s));
-    //     for (k, v) in btree_map.range((start_bound, end_bound)) {
-    //         res.insert(v.id, *v);
-    //     }
-    // } else {
-    //     panic!("BTreeEnum type mismatch");
-    // }
-    // Ok (())
+}
+
+fn map_bound<T>(bound: Bound<Value>, f: impl Fn(Value) -> Result<T>) -> Result<Bound<T>> {
+    Ok(match bound {
+        Bound::Included(value) => Bound::Included(f(value)?),
+        Bound::Excluded(value) => Bound::Excluded(f(value)?),
+        Bound::Unbounded => Bound::Unbounded,
+    })
 }
 
 fn query_no_intra_index (   
     res : &mut HashMap<IdType,Transaction>,
-    attribute: &KeyType,
+    request: &QueryRequest,
     block_data: &BlockData,
-    values: &[Option<KeyType>; 2],
 )->Result<()>{
-    if attribute == "address"{
-        for x in 0..block_data.txs.len(){
-            if block_data.txs[x].value.address <= values[1].clone().unwrap() && block_data.txs[x].value.address >= values[0].clone().unwrap() {
-                // if !res.contains_key(&block_data.txs[x].id) {
-                    res.insert(block_data.txs[x].id, block_data.txs[x].clone());
-            }
-        }
-    }else{
-        let start = values[0].as_ref().and_then(|s| s.parse::<u64>().ok()).unwrap();
-        let end = values[1].as_ref().and_then(|s| s.parse::<u64>().ok()).unwrap();
-        match attribute.as_str() {
-            "id" => {for x in 0..block_data.txs.len(){
-                if block_data.txs[x].id >=start && block_data.txs[x].id<=end {
-                    // if !res.contains_key(&block_data.txs[x].id) {
-                        res.insert(block_data.txs[x].id, block_data.txs[x].clone());
-                }
-            }}
-            "value" => {for x in 0..block_data.txs.len(){
-                res.insert(block_data.txs[x].id, block_data.txs[x].clone());
-            }
-            }
-            _ => panic!("attrubute error from query_no_intra_index!")
-        }
+    for tx in block_data.txs.iter().filter(|tx| request.matches(tx)) {
+        res.insert(tx.id, tx.clone());
     }
     Ok(())
-}
\ No newline at end of file
+}
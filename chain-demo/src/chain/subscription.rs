@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use super::*;
+
+pub type SubscriptionId = u64;
+
+/// Transactions of a newly appended block matching a subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    pub subscription: SubscriptionId,
+    pub block_id: IdType,
+    pub time_stamp: TsType,
+    pub txs: Vec<Transaction>,
+}
+
+/// Standing queries, checked against every block appended to the chain.
+#[derive(Debug, Default)]
+pub struct SubscriptionRegistry {
+    next_id: SubscriptionId,
+    subscriptions: BTreeMap<SubscriptionId, Selection>,
+}
+
+impl SubscriptionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `q_param`, whose matches are told block by block, so that
+    /// they can neither be ranked, aggregated nor paged.
+    pub fn subscribe(&mut self, q_param: &QueryParam) -> Result<SubscriptionId> {
+        let selection = q_param.selection()?;
+        if q_param.top_k.is_some() || q_param.aggregate.is_some() || q_param.limit.is_some() || q_param.cursor.is_some() {
+            bail!("a subscription is told about matching transactions only");
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.subscriptions.insert(id, selection);
+        Ok(id)
+    }
+
+    /// Whether the subscription `id` was registered and is now dropped.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        self.subscriptions.remove(&id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// Notifications of the subscriptions the block `block_id` matches, its
+    /// bloom filter ruling out most of them before its transactions are read.
+    pub fn check_block(&self, block_id: IdType, chain: &impl ReadInterface) -> Result<Vec<Notification>> {
+        let mut notifications = Vec::new();
+        if self.subscriptions.is_empty() {
+            return Ok(notifications);
+        }
+        let param = chain.get_parameter()?;
+        let block_header = chain.read_block_header(block_id)?;
+        // a block without any key has no bloom filter
+        let block_bloom = chain.read_block_bloom(block_id).ok();
+        let mut block_data = None;
+        for (id, selection) in self.subscriptions.iter() {
+            if matches!(selection.window, Some((left, right)) if block_header.time_stamp < left || block_header.time_stamp > right) {
+                continue;
+            }
+            if let Some(block_bloom) = &block_bloom {
+                if !judge_contain_key(&selection.condition, block_bloom, param.hasher()) {
+                    continue;
+                }
+            }
+            let block_data = match &mut block_data {
+                Some(block_data) => block_data,
+                None => block_data.insert(chain.read_block_data(block_id)?),
+            };
+            let txs: Vec<Transaction> = block_data.txs.iter()
+                .filter(|tx| selection.condition.matches(tx))
+                .cloned()
+                .collect();
+            if !txs.is_empty() {
+                notifications.push(Notification {
+                    subscription: *id,
+                    block_id,
+                    time_stamp: block_header.time_stamp,
+                    txs,
+                });
+            }
+        }
+        Ok(notifications)
+    }
+}
@@ -0,0 +1,1381 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use futures::executor::block_on;
+use anyhow::Context;
+use super::*;
+
+const TEST_TXS: &str = "
+1 [addr_a] {in, 10, 100}
+1 [addr_b] {out, 20, 100}
+2 [addr_c] {in, 30, 200}
+2 [addr_d] {out, 40, 200}
+3 [addr_e] {in, 50, 300}
+3 [addr_f] {out, 60, 300}
+4 [addr_g] {in, 70, 400}
+";
+
+#[derive(Default)]
+struct MemChain {
+    param: Option<Parameter>,
+    block_headers: BTreeMap<IdType, BlockHeader>,
+    block_datas: BTreeMap<IdType, BlockData>,
+    block_blooms: BTreeMap<IdType, BlockBloom>,
+    intra_indexs: BTreeMap<IdType, IntraIndex>,
+    inter_indexs: BTreeMap<TsType, InterIndex>,
+    index_configs: HashMap<KeyType, IndexConfigs>,
+    txs: HashMap<IdType, Transaction>,
+    mmr_peaks: MmrPeaks,
+    mmr_nodes: HashMap<(u32, u64), Digest>,
+    account_keys: HashMap<KeyType, PkType>,
+}
+
+impl ReadInterface for MemChain {
+    fn get_parameter(&self) -> Result<Parameter> {
+        self.param.clone().context("failed to read parameter")
+    }
+    fn read_block_header(&self, id: IdType) -> Result<BlockHeader> {
+        self.block_headers.get(&id).cloned().context("failed to read block header")
+    }
+    fn read_block_data(&self, id: IdType) -> Result<BlockData> {
+        self.block_datas.get(&id).cloned().context("failed to read block data")
+    }
+    fn read_block_bloom(&self, id: IdType) -> Result<BlockBloom> {
+        self.block_blooms.get(&id).cloned().context("failed to read block bloom")
+    }
+    fn read_intra_index(&self, id: IdType) -> Result<IntraIndex> {
+        Ok(self.intra_indexs.get(&id).cloned().unwrap_or_else(|| IntraIndex::new(id)))
+    }
+    fn read_intra_indexs_size(&self) -> usize {
+        self.intra_indexs.len()
+    }
+    fn read_transaction(&self, id: IdType) -> Result<Transaction> {
+        self.txs.get(&id).cloned().context("failed to read transaction")
+    }
+    fn read_inter_index(&self, timestamp: TsType) -> Result<InterIndex> {
+        self.inter_indexs.get(&timestamp).cloned().context("failed to read inter index")
+    }
+    fn read_inter_indexs(&self) -> Result<Vec<InterIndex>> {
+        Ok(self.inter_indexs.values().cloned().collect())
+    }
+    fn read_index_config(&self, attribute: KeyType) -> Result<IndexConfigs> {
+        self.index_configs.get(&attribute).cloned().context("failed to read index config")
+    }
+    fn read_mmr_peaks(&self) -> Result<MmrPeaks> {
+        Ok(self.mmr_peaks.clone())
+    }
+    fn read_mmr_node(&self, height: u32, index: u64) -> Result<Digest> {
+        self.mmr_nodes.get(&(height, index)).copied().context("failed to read mmr node")
+    }
+    fn read_account_key(&self, address: &KeyType) -> Result<Option<PkType>> {
+        Ok(self.account_keys.get(address).copied())
+    }
+}
+
+impl WriteInterface for MemChain {
+    fn set_parameter(&mut self, param: Parameter) -> Result<()> {
+        self.param = Some(param);
+        Ok(())
+    }
+    fn write_block_header(&mut self, header: BlockHeader) -> Result<()> {
+        self.block_headers.insert(header.block_id, header);
+        Ok(())
+    }
+    fn write_block_data(&mut self, data: BlockData) -> Result<()> {
+        self.block_datas.insert(data.block_id, data);
+        Ok(())
+    }
+    fn write_block_bloom(&mut self, bloom: BlockBloom) -> Result<()> {
+        self.block_blooms.insert(bloom.block_id, bloom);
+        Ok(())
+    }
+    fn write_intra_index(&mut self, index: IntraIndex) -> Result<()> {
+        self.intra_indexs.insert(index.blockId, index);
+        Ok(())
+    }
+    fn write_transaction(&mut self, tx: Transaction) -> Result<()> {
+        self.txs.insert(tx.id, tx);
+        Ok(())
+    }
+    fn write_inter_index(&mut self, index: InterIndex) -> Result<()> {
+        self.inter_indexs.insert(index.start_timestamp, index);
+        Ok(())
+    }
+    fn write_index_config(&mut self, config: IndexConfigs) -> Result<()> {
+        self.index_configs.insert(config.attribute.clone(), config);
+        Ok(())
+    }
+    fn update_intra_index(&mut self, indexs: Vec<IntraIndex>) -> Result<()> {
+        self.intra_indexs.clear();
+        for index in indexs {
+            self.intra_indexs.insert(index.blockId, index);
+        }
+        Ok(())
+    }
+    fn write_mmr_peaks(&mut self, peaks: MmrPeaks) -> Result<()> {
+        self.mmr_peaks = peaks;
+        Ok(())
+    }
+    fn write_mmr_node(&mut self, node: MmrNode) -> Result<()> {
+        self.mmr_nodes.insert((node.height, node.index), node.digest);
+        Ok(())
+    }
+    fn write_account_key(&mut self, address: &KeyType, key: PkType) -> Result<()> {
+        self.account_keys.insert(address.clone(), key);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl LightNodeInterface for MemChain {
+    async fn lightnode_get_parameter(&self) -> Result<Parameter> {
+        self.get_parameter()
+    }
+    async fn lightnode_read_block_header(&self, id: IdType) -> Result<BlockHeader> {
+        self.read_block_header(id)
+    }
+}
+
+lazy_static::lazy_static! {
+    // small modulus generated once, to keep the tests fast
+    static ref TEST_ACCUMULATOR: RsaAccumulator = RsaAccumulator::setup(&mut rand_core::OsRng, 512).unwrap();
+}
+
+fn test_parameter(producer: &Keypair) -> Parameter {
+    test_parameter_with(producer, HashAlgorithm::default())
+}
+
+fn test_parameter_with(producer: &Keypair, hash_algorithm: HashAlgorithm) -> Parameter {
+    Parameter {
+        error_bounds: 5.0,
+        inter_index: true,
+        intra_index: true,
+        start_block_id: 0,
+        block_count: 0,
+        inter_index_timestamps: Vec::new(),
+        producer_key: producer.public,
+        accumulator: TEST_ACCUMULATOR.clone(),
+        hash_algorithm,
+    }
+}
+
+fn build_test_chain() -> MemChain {
+    build_test_chain_with(HashAlgorithm::default())
+}
+
+fn build_test_chain_with(hash_algorithm: HashAlgorithm) -> MemChain {
+    build_chain(TEST_TXS, hash_algorithm)
+}
+
+fn build_chain(txs: &str, hash_algorithm: HashAlgorithm) -> MemChain {
+    build_chain_with_keys(txs, hash_algorithm).0
+}
+
+/// The chain of `txs`, with the keys of its producer and of the senders of
+/// its transactions, to append blocks with.
+fn build_chain_with_keys(txs: &str, hash_algorithm: HashAlgorithm) -> (MemChain, Keypair, HashMap<KeyType, Keypair>) {
+    let mut chain = MemChain::default();
+    let producer = Keypair::generate(&mut rand_core::OsRng);
+    let mut param = test_parameter_with(&producer, hash_algorithm);
+    chain.set_parameter(param.clone()).unwrap();
+    let mut raw_txs = load_raw_tx_from_str(txs).unwrap();
+    let accounts = sign_raw_txs(&mut raw_txs, &mut rand_core::OsRng);
+    let mut configs_map = IndexConfigs_map::new();
+    let mut pre_hash = Digest::default();
+    let mut block_headers = Vec::new();
+    for (id, txs) in raw_txs.iter() {
+        let block_header = build_block(*id, pre_hash, txs.iter(), &producer, &mut chain, &mut configs_map).unwrap();
+        pre_hash = block_header.to_digest_with(param.hasher());
+        block_headers.push(block_header);
+    }
+    param.start_block_id = *raw_txs.keys().min().unwrap();
+    param.block_count = raw_txs.len() as IdType;
+    chain.set_parameter(param).unwrap();
+    build_inter_index(block_headers, &producer, &mut chain).unwrap();
+    (chain, producer, accounts)
+}
+
+fn value_query(left: &str, right: &str) -> QueryParam {
+    QueryParam {
+        key: vec!["value".to_string()],
+        value: vec![[Some(left.to_string()), Some(right.to_string())]],
+        bloom_filter: false,
+        intra_index: true,
+        ..QueryParam::default()
+    }
+}
+
+fn result_tx_count(result: &OverallResult) -> usize {
+    result.res_txs.0.iter().map(|block| block.Txs.len()).sum()
+}
+
+fn request(key: &str, predicate: Predicate) -> Condition {
+    Condition::Request(QueryRequest::new(key, predicate).unwrap())
+}
+
+fn number(number: u64) -> Value {
+    Value::Number(number)
+}
+
+#[test]
+fn test_verify_query_result() {
+    let mut chain = build_test_chain();
+    let result = historical_query(&value_query("15", "45"), &mut chain).unwrap();
+    assert_eq!(result_tx_count(&result), 3);
+    assert_eq!(result.res_vos.0.len(), 4);
+    assert!(result.vo_size > 0);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+}
+
+#[test]
+fn test_verify_timestamp_query_result() {
+    let mut chain = build_test_chain();
+    let mut q_param = value_query("0", "100");
+    q_param.key.push("timestamp".to_string());
+    q_param.value.push([Some("150".to_string()), Some("350".to_string())]);
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    assert_eq!(result_tx_count(&result), 4);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+}
+
+#[test]
+fn test_textual_query() {
+    let mut chain = build_test_chain();
+    let q_param = parse_query("value BETWEEN 15 AND 45 AND timestamp IN [150, 350]").unwrap();
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    assert_eq!(result_tx_count(&result), 2);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+
+    // mismatched attributes and ranges are rejected, not panicked on
+    let mut q_param = value_query("15", "45");
+    q_param.key.push("timestamp".to_string());
+    assert!(historical_query(&q_param, &mut chain).is_err());
+}
+
+#[test]
+fn test_timestamp_only_query() {
+    let mut chain = build_test_chain();
+    let json_param: QueryParam = serde_json::from_str(
+        r#"{"query_attribute":["timestamp"],"range":[["150","350"]],"bloom_filter":false,"intra_index":false}"#,
+    ).unwrap();
+    let text_param = parse_query("timestamp IN [150, 350]").unwrap();
+    let values = |q_param: &QueryParam, chain: &mut MemChain| {
+        let result = historical_query(q_param, chain).unwrap();
+        let (verify_result, _) = block_on(result.verify(chain)).unwrap();
+        assert!(verify_result.is_ok(), "{:?}", verify_result);
+        let mut values: Vec<TxType> = result.res_txs.txs().map(|tx| tx.value.trans_value).collect();
+        values.sort_unstable();
+        values
+    };
+    // every transaction of blocks 2 and 3, whichever form the query is given in
+    assert_eq!(values(&json_param, &mut chain), vec![30, 40, 50, 60]);
+    assert_eq!(values(&text_param, &mut chain), vec![30, 40, 50, 60]);
+}
+
+#[test]
+fn test_parse_query() {
+    let q_param = parse_query(
+        "(address = 'addr_a' OR value BETWEEN 10 AND 50) and timestamp IN [100, 300] USING bloom_filter, INTRA_INDEX",
+    ).unwrap();
+    assert_eq!(q_param, QueryParam {
+        bloom_filter: true,
+        intra_index: true,
+        condition: Some(Condition::And(vec![
+            Condition::Or(vec![
+                request("address", Predicate::Eq { value: Value::Text("addr_a".to_string()) }),
+                request("value", Predicate::inclusive(Some(number(10)), Some(number(50)))),
+            ]),
+            request("timestamp", Predicate::inclusive(Some(number(100)), Some(number(300)))),
+        ])),
+        ..QueryParam::default()
+    });
+
+    let q_param: QueryParam = "timestamp >= 200 AND id < 3 OR NOT address STARTS WITH 'it''s' AND value IN (1, 5) OR NOT (value <> 7)"
+        .parse()
+        .unwrap();
+    assert_eq!(q_param.condition, Some(Condition::Or(vec![
+        Condition::And(vec![
+            request("timestamp", Predicate::inclusive(Some(number(200)), None)),
+            request("id", Predicate::Range { start: Bound::Unbounded, end: Bound::Excluded(number(3)) }),
+        ]),
+        Condition::And(vec![
+            Condition::Not(Box::new(request("address", Predicate::Prefix { prefix: "it's".to_string() }))),
+            request("value", Predicate::In { values: vec![number(1), number(5)] }),
+        ]),
+        Condition::Not(Box::new(request("value", Predicate::NotEq { value: number(7) }))),
+    ])));
+    assert!(q_param.key.is_empty() && q_param.predicates.is_empty() && !q_param.bloom_filter);
+    // a timestamp range under OR is not a window
+    assert_eq!(q_param.selection().unwrap().window, None);
+
+    let selection = parse_query("value = 1 AND timestamp > 100 AND timestamp <= 300").unwrap().selection().unwrap();
+    assert_eq!(selection.window, Some((101, 300)));
+    assert_eq!(selection.condition, request("value", Predicate::Eq { value: number(1) }));
+
+    let q_param = parse_query("select SUM(value), count WHERE address = 'addr_a' GROUP BY time_bucket(3600)").unwrap();
+    assert_eq!(q_param.aggregate, Some(Aggregate {
+        functions: vec![AggregateFunction::Sum, AggregateFunction::Count],
+        group_by: Some(GroupBy::TimeBucket(3600)),
+    }));
+    let q_param = parse_query("SELECT AVG GROUP BY direction USING intra_index").unwrap();
+    assert_eq!(q_param.condition, Some(Condition::And(Vec::new())));
+    assert_eq!(q_param.aggregate.unwrap().group_by, Some(GroupBy::Direction));
+    assert_eq!(parse_query("timestamp IN [1, 2] TOP 3 USING intra_index").unwrap().top_k, Some(3));
+    let q_param = parse_query("explain address = 'addr_a' USING planner").unwrap();
+    assert!(q_param.explain && q_param.planner && !q_param.bloom_filter);
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |query: &str| parse_query(query).unwrap_err();
+    assert_eq!(error("value = 'ten'"), ParseError::new(8, "expected a number, found string 'ten'"));
+    assert_eq!(error("address = 10").position, 10);
+    assert_eq!(error("balance = 10"), ParseError::new(0, "unknown attribute `balance`"));
+    assert_eq!(error("value BETWEEN 10"), ParseError::new(16, "expected AND, found end of query"));
+    assert_eq!(error("value IN [10 20]").position, 13);
+    assert_eq!(error("value IN (10, )").position, 14);
+    assert_eq!(error("value STARTS WITH '1'").position, 0);
+    assert_eq!(error("NOT (value = 10").position, 15);
+    assert_eq!(error("value = 10 AND NOT").position, 18);
+    assert_eq!(error("address = 'a").position, 10);
+    assert_eq!(error("value = 10 USING cache").position, 17);
+    assert_eq!(error("value = 10)").position, 10);
+    assert_eq!(error("value ! 10").position, 6);
+    assert_eq!(error("value = 99999999999999999999").message, "number out of range");
+    assert_eq!(error("value = 1 AND").pointer("value = 1 AND"), "value = 1 AND\n             ^");
+    assert_eq!(error("SELECT total WHERE value = 1").position, 7);
+    assert_eq!(error("SELECT SUM(id) WHERE value = 1").position, 11);
+    assert_eq!(error("SELECT SUM GROUP BY time_bucket(0)").position, 32);
+    assert_eq!(error("value = 1 GROUP BY address").position, 10);
+    assert_eq!(error("value = 1 TOP 0").position, 14);
+    assert_eq!(error("value = 1 TOP k").position, 14);
+    // each word is reported as what is expected in its place
+    assert_eq!(error("value = 1 USING 7"), ParseError::new(16, "expected an option, found number 7"));
+    assert_eq!(error("SELECT (value)"), ParseError::new(7, "expected an aggregate function, found `(`"));
+    assert_eq!(error("SELECT SUM GROUP BY"), ParseError::new(19, "expected a grouping, found end of query"));
+    assert_eq!(error("value = 1 AND 5 = 1"), ParseError::new(14, "expected an attribute, found number 5"));
+    // parsed, but the windows do not overlap
+    assert!(parse_query("value = 1 AND timestamp > 5 AND timestamp < 3").unwrap().selection().is_err());
+}
+
+#[test]
+fn test_predicates_with_and_without_intra_index() {
+    // duplicated values and addresses within a block
+    let mut chain = build_chain("
+1 [addr_a] {in, 10, 100}
+1 [addr_a] {out, 10, 100}
+1 [addr_b] {in, 20, 100}
+2 [addr_ab] {out, 20, 200}
+2 [addr_c] {in, 30, 200}
+", HashAlgorithm::default());
+    let queries = [
+        ("value = 10", 2),
+        ("value != 10", 3),
+        ("value > 10", 3),
+        ("value >= 10 AND timestamp < 200", 3),
+        ("value < 30", 4),
+        ("value IN (10, 30, 99)", 3),
+        ("value IN [20, 30]", 3),
+        ("value > 30", 0),
+        ("value BETWEEN 30 AND 10", 0),
+        ("address = 'addr_a'", 2),
+        ("address STARTS WITH 'addr_a'", 3),
+        ("address > 'addr_a' AND timestamp > 100", 2),
+        ("address IN ('addr_b', 'addr_c') OR value = 10", 4),
+    ];
+    for (query, count) in queries.iter() {
+        for intra_index in [true, false] {
+            for bloom_filter in [true, false] {
+                let mut q_param = parse_query(query).unwrap();
+                q_param.intra_index = intra_index;
+                q_param.bloom_filter = bloom_filter;
+                let result = historical_query(&q_param, &mut chain).unwrap();
+                assert_eq!(result_tx_count(&result), *count, "{} intra_index: {}", query, intra_index);
+                let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+                assert!(verify_result.is_ok(), "{}: {:?}", query, verify_result);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_boolean_conditions() {
+    let mut chain = build_test_chain();
+    let queries = [
+        ("address = 'addr_c' AND value > 25", 1),
+        ("address = 'addr_c' AND value > 35", 0),
+        ("address = 'addr_c' OR value > 55", 3),
+        ("NOT value BETWEEN 20 AND 60", 2),
+        ("NOT (address = 'addr_a' OR address = 'addr_b') AND value < 50", 2),
+        ("value > 10 AND NOT value > 40 OR address STARTS WITH 'addr_g'", 4),
+        ("(value < 30 OR value > 50) AND timestamp >= 200", 2),
+        ("timestamp = 300 OR value = 10", 3),
+    ];
+    for (query, count) in queries.iter() {
+        for intra_index in [true, false] {
+            for bloom_filter in [true, false] {
+                let mut q_param = parse_query(query).unwrap();
+                q_param.intra_index = intra_index;
+                q_param.bloom_filter = bloom_filter;
+                let result = historical_query(&q_param, &mut chain).unwrap();
+                assert_eq!(result_tx_count(&result), *count, "{} intra_index: {}", query, intra_index);
+                let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+                assert!(verify_result.is_ok(), "{}: {:?}", query, verify_result);
+            }
+        }
+    }
+
+    // the ranges keep matching any of them, joined to the condition with AND
+    let mut q_param = value_query("10", "30");
+    q_param.key.push("address".to_string());
+    q_param.value.push([Some("addr_g".to_string()), Some("addr_g".to_string())]);
+    q_param.condition = parse_query("NOT value = 20").unwrap().condition;
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    assert_eq!(result_tx_count(&result), 3);
+}
+
+#[test]
+fn test_bloom_skip_follows_conditions() {
+    let mut chain = build_test_chain();
+    // each key is in some block, never both in the same one
+    let mut q_param = parse_query("address = 'addr_a' AND value = 60 USING bloom_filter").unwrap();
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    assert_eq!(result_tx_count(&result), 0);
+    assert!(result.res_vos.0.iter().all(|vo| matches!(vo, BlockVo::BloomSkipped { .. })), "{:?}", result.res_vos);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+
+    // a negation can not be ruled out by a bloom filter
+    q_param.condition = parse_query("address = 'addr_a' OR NOT value = 60").unwrap().condition;
+    let mut result = historical_query(&q_param, &mut chain).unwrap();
+    assert_eq!(result_tx_count(&result), 6);
+    let bloom = chain.read_block_bloom(2).unwrap();
+    result.res_txs.0.retain(|block| block.block_id != 2);
+    for block_vo in result.res_vos.0.iter_mut() {
+        if block_vo.block_id() == 2 {
+            *block_vo = BlockVo::BloomSkipped { bloom: bloom.clone() };
+        }
+    }
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidBloomSkip { block_id: 2 }]);
+}
+
+#[test]
+fn test_aggregates() {
+    let mut chain = build_test_chain();
+    let q_param = parse_query("SELECT SUM, COUNT, MIN, MAX, AVG WHERE value >= 20 AND timestamp <= 300 USING intra_index").unwrap();
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    assert_eq!(result.aggregates, Some(vec![AggregateRow {
+        group: None,
+        sum: Some(200),
+        count: Some(5),
+        min: Some(20),
+        max: Some(60),
+        avg: Some(40.0),
+    }]));
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+
+    // a total not backed by the returned transactions
+    let mut forged = result.clone();
+    forged.aggregates.as_mut().unwrap()[0].sum = Some(210);
+    let (verify_result, _) = block_on(forged.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidAggregate]);
+
+    // the aggregates alone can not be verified, the matches are shipped with them
+    let mut stripped = result.clone();
+    stripped.res_txs.0.clear();
+    let (verify_result, _) = block_on(stripped.verify(&chain)).unwrap();
+    let reasons = verify_result.reasons();
+    assert_eq!(reasons.iter().filter(|reason| matches!(reason, InvalidReason::MissingResult { .. })).count(), 5);
+    assert_eq!(reasons.last(), Some(&InvalidReason::InvalidAggregate));
+
+    let groups = |query: &str, chain: &mut MemChain| -> Vec<(Option<GroupKey>, Option<u128>)> {
+        let result = historical_query(&parse_query(query).unwrap(), chain).unwrap();
+        let (verify_result, _) = block_on(result.verify(&*chain)).unwrap();
+        assert!(verify_result.is_ok(), "{}: {:?}", query, verify_result);
+        result.aggregates.unwrap().into_iter().map(|row| (row.group, row.sum)).collect()
+    };
+    assert_eq!(groups("SELECT SUM GROUP BY direction USING bloom_filter", &mut chain), vec![
+        (Some(GroupKey::Direction(Direction::In)), Some(160)),
+        (Some(GroupKey::Direction(Direction::Out)), Some(120)),
+    ]);
+    assert_eq!(groups("SELECT SUM WHERE address IN ('addr_c', 'addr_d') GROUP BY address USING bloom_filter", &mut chain), vec![
+        (Some(GroupKey::Address("addr_c".to_string())), Some(30)),
+        (Some(GroupKey::Address("addr_d".to_string())), Some(40)),
+    ]);
+    assert_eq!(groups("SELECT SUM WHERE timestamp >= 200 GROUP BY time_bucket(200)", &mut chain), vec![
+        (Some(GroupKey::TimeBucket(200)), Some(180)),
+        (Some(GroupKey::TimeBucket(400)), Some(70)),
+    ]);
+    assert_eq!(groups("SELECT SUM WHERE value > 100 GROUP BY block", &mut chain), vec![]);
+    assert_eq!(groups("SELECT SUM WHERE value > 100", &mut chain), vec![(None, Some(0))]);
+}
+
+#[test]
+fn test_top_k() {
+    let mut chain = build_test_chain();
+    let values = |result: &OverallResult| -> Vec<TxType> {
+        let mut values: Vec<TxType> = result.res_txs.txs().map(|tx| tx.value.trans_value).collect();
+        values.sort_unstable();
+        values
+    };
+    for intra_index in [true, false] {
+        let mut q_param = parse_query("timestamp IN [100, 300] TOP 2").unwrap();
+        q_param.intra_index = intra_index;
+        let result = historical_query(&q_param, &mut chain).unwrap();
+        assert_eq!(values(&result), vec![50, 60]);
+        // the blocks of the window below 50 are not read
+        let skipped: Vec<IdType> = result.res_vos.0.iter()
+            .filter(|vo| matches!(vo, BlockVo::BelowTopK { .. }))
+            .map(|vo| vo.block_id())
+            .collect();
+        assert_eq!(skipped, vec![2, 1]);
+        let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+        assert!(verify_result.is_ok(), "{:?}", verify_result);
+    }
+    let queries = [
+        ("address != 'addr_f' TOP 2", vec![50, 70]),
+        ("value < 45 TOP 3", vec![20, 30, 40]),
+        ("value > 40 TOP 10", vec![50, 60, 70]),
+        ("(value = 10 OR value = 20) AND timestamp >= 100 TOP 1", vec![20]),
+    ];
+    for (query, expected) in queries.iter() {
+        let result = historical_query(&parse_query(query).unwrap(), &mut chain).unwrap();
+        assert_eq!(&values(&result), expected, "{}", query);
+        let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+        assert!(verify_result.is_ok(), "{}: {:?}", query, verify_result);
+    }
+
+    // withholding the largest value leaves the skips unjustified
+    let result = historical_query(&parse_query("value > 0 TOP 2").unwrap(), &mut chain).unwrap();
+    let mut forged = result.clone();
+    for block in forged.res_txs.0.iter_mut() {
+        block.Txs.retain(|_, tx| tx.value.trans_value != 70);
+    }
+    let (verify_result, _) = block_on(forged.verify(&chain)).unwrap();
+    assert!(verify_result.reasons().contains(&InvalidReason::InvalidTopK { block_id: Some(2) }), "{:?}", verify_result);
+    // nor can a block above the k-th value be skipped
+    let mut forged = result.clone();
+    forged.res_txs.0.retain(|block| block.block_id != 4);
+    for block_vo in forged.res_vos.0.iter_mut() {
+        if block_vo.block_id() == 4 {
+            *block_vo = BlockVo::BelowTopK { block_id: 4 };
+        }
+    }
+    let (verify_result, _) = block_on(forged.verify(&chain)).unwrap();
+    assert!(verify_result.reasons().contains(&InvalidReason::InvalidTopK { block_id: Some(4) }), "{:?}", verify_result);
+}
+
+#[test]
+fn test_account_view() {
+    let mut chain = build_chain("
+1 [addr_a] {in, 10, 100}
+1 [addr_b] {out, 20, 100}
+2 [addr_a] {out, 4, 200}
+3 [addr_c] {in, 50, 300}
+4 [addr_a] {in, 7, 400}
+4 [addr_a] {out, 1, 400}
+", HashAlgorithm::default());
+    let (view, result) = account_view("addr_a", None, &mut chain).unwrap();
+    let balances: Vec<(IdType, i128)> = view.history.iter().map(|entry| (entry.block_id, entry.balance)).collect();
+    assert_eq!(balances, vec![(1, 10), (2, 6), (4, 13), (4, 12)]);
+    assert_eq!((view.first_seen, view.last_seen), (Some(1), Some(4)));
+    assert_eq!((view.total_in, view.total_out, view.net_flow), (17, 5, 12));
+    // the block without the address is skipped by its bloom filter
+    assert!(result.res_vos.0.iter().any(|vo| matches!(vo, BlockVo::BloomSkipped { bloom } if bloom.block_id == 3)));
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+
+    let (view, result) = account_view("addr_a", Some((150, 400)), &mut chain).unwrap();
+    assert_eq!(view.history.len(), 3);
+    assert_eq!((view.opening_balance, view.net_flow), (10, 2));
+    assert_eq!(view, AccountView::from_result("addr_a", Some((150, 400)), &result));
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+
+    let (view, _) = account_view("addr_z", None, &mut chain).unwrap();
+    assert!(view.history.is_empty() && view.first_seen.is_none());
+}
+
+#[test]
+fn test_account_view_with_history_before_window() {
+    let mut chain = build_chain("
+1 [addr_a] {in, 10, 100}
+1 [addr_b] {out, 20, 100}
+2 [addr_a] {out, 4, 200}
+3 [addr_a] {in, 3, 300}
+4 [addr_a] {in, 7, 400}
+", HashAlgorithm::default());
+    let (view, result) = account_view("addr_a", Some((150, 300)), &mut chain).unwrap();
+    // balances carry on from the transactions before the window
+    let balances: Vec<(IdType, i128)> = view.history.iter().map(|entry| (entry.block_id, entry.balance)).collect();
+    assert_eq!(balances, vec![(2, 6), (3, 9)]);
+    assert_eq!((view.opening_balance, view.net_flow), (10, -1));
+    // while the blocks seen and the totals are those of the whole chain
+    assert_eq!((view.first_seen, view.last_seen), (Some(1), Some(4)));
+    assert_eq!((view.total_in, view.total_out), (20, 4));
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+
+    // a window before the first transaction opens at 0
+    let (view, _) = account_view("addr_a", Some((0, 50)), &mut chain).unwrap();
+    assert!(view.history.is_empty());
+    assert_eq!((view.opening_balance, view.net_flow, view.first_seen), (0, 0, Some(1)));
+}
+
+#[test]
+fn test_query_planner() {
+    let mut chain = build_test_chain();
+    // a single address is best found skipping blocks by their bloom filter,
+    // blocks of two transactions being cheaper to scan than to look up
+    let q_param = parse_query("EXPLAIN address = 'addr_c' USING planner").unwrap();
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    let explain = result.explain.clone().unwrap();
+    assert!(explain.plan.bloom_filter && explain.plan.intra_index.is_empty());
+    assert_eq!(explain.plan, explain.alternatives[0]);
+    assert!(explain.alternatives.windows(2).all(|plans| plans[0].estimated_cost <= plans[1].estimated_cost));
+    assert_eq!(explain.actual_blocks, 1);
+    assert_eq!(result_tx_count(&result), 1);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+
+    // reading the few headers of the chain beats the inter index
+    let window = "value >= 20 AND timestamp IN [200, 300]";
+    let planned = historical_query(&parse_query(&format!("EXPLAIN {} USING planner", window)).unwrap(), &mut chain).unwrap();
+    let explain = planned.explain.clone().unwrap();
+    assert!(!explain.plan.inter_index && !planned.use_inter_index);
+    assert!(planned.res_inter_vo.is_none() && planned.res_boundary.is_none());
+    assert_eq!(planned.res_vos.0.len(), 4);
+    assert_eq!(explain.actual_blocks, 2);
+    let (verify_result, _) = block_on(planned.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+    // the fixed rule takes the inter index, to the same result
+    let fixed = historical_query(&parse_query(&format!("EXPLAIN {}", window)).unwrap(), &mut chain).unwrap();
+    assert!(fixed.explain.as_ref().unwrap().plan.inter_index && fixed.res_inter_vo.is_some());
+    assert_eq!(result_tx_count(&fixed), result_tx_count(&planned));
+    assert!(historical_query(&parse_query(window).unwrap(), &mut chain).unwrap().explain.is_none());
+
+    // without an inter index proof the whole chain has to be covered
+    let mut tampered = planned.clone();
+    tampered.res_vos.0.retain(|vo| vo.block_id() != 1);
+    let (verify_result, _) = block_on(tampered.verify(&chain)).unwrap();
+    assert!(!verify_result.is_ok());
+}
+
+#[test]
+fn test_parallel_evaluation() {
+    let txs: String = (1..=40).flat_map(|block| (0..3).map(move |i| {
+        format!("{} [addr_{}] {{{}, {}, {}}}\n", block, (block + i) % 7, if i % 2 == 0 { "in" } else { "out" }, block * 3 + i, block * 100)
+    })).collect();
+    let mut chain = build_chain(&txs, HashAlgorithm::default());
+    let queries = [
+        "value BETWEEN 20 AND 90",
+        "address = 'addr_3' AND timestamp IN [500, 3000] USING bloom_filter",
+        "NOT address IN ('addr_1', 'addr_2') USING intra_index",
+    ];
+    for query in queries {
+        let q_param = parse_query(query).unwrap();
+        QUERY_WORKERS.store(1, std::sync::atomic::Ordering::Relaxed);
+        let sequential = historical_query(&q_param, &mut chain).unwrap();
+        QUERY_WORKERS.store(8, std::sync::atomic::Ordering::Relaxed);
+        let parallel = historical_query(&q_param, &mut chain).unwrap();
+        QUERY_WORKERS.store(0, std::sync::atomic::Ordering::Relaxed);
+        // merged in block order whatever the workers
+        assert_eq!(parallel.res_txs, sequential.res_txs, "{}", query);
+        assert_eq!(parallel.res_vos, sequential.res_vos, "{}", query);
+        assert!(result_tx_count(&parallel) > 0, "{}", query);
+        let (verify_result, _) = block_on(parallel.verify(&chain)).unwrap();
+        assert!(verify_result.is_ok(), "{:?}", verify_result);
+    }
+}
+
+#[test]
+fn test_paged_query() {
+    let txs: String = (1..=12).flat_map(|block| (0..2).map(move |i| {
+        format!("{} [addr_{}] {{in, {}, {}}}\n", block, i, block * 10 + i, block * 100)
+    })).collect();
+    let mut chain = build_chain(&txs, HashAlgorithm::default());
+    for query in ["value >= 30", "value >= 30 AND timestamp IN [250, 950]", "address = 'addr_1' USING bloom_filter, planner"] {
+        let whole = historical_query(&parse_query(query).unwrap(), &mut chain).unwrap();
+        assert!(whole.next_cursor.is_none());
+        let q_param = QueryParam { limit: Some(3), ..parse_query(query).unwrap() };
+        let pages: Vec<OverallResult> = paged_query(&q_param, &mut chain).collect::<Result<_>>().unwrap();
+        assert!(pages.len() > 1, "{}", query);
+        let mut cursor = None;
+        for page in pages.iter() {
+            // a page stops at the block its matches reach the limit
+            assert_eq!(page.query_param.cursor, cursor);
+            let block_ids: Vec<IdType> = page.res_txs.0.iter().map(|block| block.block_id).collect();
+            assert!(block_ids.windows(2).all(|ids| ids[0] > ids[1]), "{:?}", block_ids);
+            if page.next_cursor.is_some() {
+                assert!((3..5).contains(&result_tx_count(page)));
+            }
+            let (verify_result, _) = block_on(page.verify(&chain)).unwrap();
+            assert!(verify_result.is_ok(), "{} {:?}", query, verify_result);
+            cursor = page.next_cursor;
+        }
+        assert!(cursor.is_none());
+        let paged: Vec<IdType> = pages.iter().flat_map(|page| page.res_txs.txs().map(|tx| tx.id)).collect::<BTreeSet<_>>().into_iter().collect();
+        let all: Vec<IdType> = whole.res_txs.txs().map(|tx| tx.id).collect::<BTreeSet<_>>().into_iter().collect();
+        assert_eq!(paged, all, "{}", query);
+    }
+
+    // a page can not skip blocks by stopping above its claimed next cursor
+    let q_param = QueryParam { limit: Some(3), ..parse_query("value >= 30").unwrap() };
+    let mut page = historical_query(&q_param, &mut chain).unwrap();
+    let next_cursor = page.next_cursor.unwrap();
+    page.next_cursor = Some(next_cursor - 2);
+    let (verify_result, _) = block_on(page.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[
+        InvalidReason::MissingBlock { block_id: next_cursor - 2 },
+        InvalidReason::MissingBlock { block_id: next_cursor - 1 },
+    ]);
+    // nor by claiming to be the last
+    page.next_cursor = None;
+    let (verify_result, _) = block_on(page.verify(&chain)).unwrap();
+    assert!(!verify_result.is_ok());
+
+    let invalid = [
+        QueryParam { limit: Some(0), ..value_query("10", "20") },
+        QueryParam { limit: Some(2), top_k: Some(2), ..value_query("10", "20") },
+        QueryParam { cursor: Some(2), ..parse_query("SELECT COUNT").unwrap() },
+    ];
+    for q_param in invalid.iter() {
+        assert!(historical_query(q_param, &mut chain).is_err(), "{:?}", q_param);
+    }
+}
+
+#[test]
+fn test_subscriptions() {
+    let (mut chain, producer, mut accounts) = build_chain_with_keys(TEST_TXS, HashAlgorithm::default());
+    let mut registry = SubscriptionRegistry::new();
+    let large = registry.subscribe(&parse_query("address = 'addr_x' AND value > 1000").unwrap()).unwrap();
+    let window = registry.subscribe(&parse_query("value >= 5 AND timestamp IN [500, 600]").unwrap()).unwrap();
+    assert!(registry.subscribe(&parse_query("value >= 5 TOP 1").unwrap()).is_err());
+    assert!(registry.subscribe(&QueryParam { key: vec!["balance".to_string()], ..value_query("1", "2") }).is_err());
+
+    let mut notified = Vec::new();
+    for txs in [
+        "5 [addr_x] {in, 500, 500}\n5 [addr_y] {in, 2000, 500}",
+        "6 [addr_x] {out, 1500, 700}\n6 [addr_x] {in, 10, 700}",
+        "7 [addr_z] {in, 3000, 800}",
+    ] {
+        let mut raw_txs = load_raw_tx_from_str(txs).unwrap();
+        sign_raw_txs_with(&mut raw_txs, &mut accounts, &mut rand_core::OsRng);
+        let (block_id, raw_txs) = raw_txs.into_iter().next().unwrap();
+        let header = append_block(&raw_txs, &producer, &mut chain).unwrap();
+        assert_eq!(header.block_id, block_id);
+        notified.extend(registry.check_block(block_id, &chain).unwrap());
+    }
+    let matches: Vec<(SubscriptionId, IdType, Vec<TxType>)> = notified.iter()
+        .map(|notification| (notification.subscription, notification.block_id, notification.txs.iter().map(|tx| tx.value.trans_value).collect()))
+        .collect();
+    assert_eq!(matches, vec![(window, 5, vec![500, 2000]), (large, 6, vec![1500])]);
+
+    // the appended blocks are queried and verified like the others
+    let result = historical_query(&parse_query("value > 1000 AND timestamp IN [300, 800]").unwrap(), &mut chain).unwrap();
+    assert_eq!(result_tx_count(&result), 3);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+    let mut raw_txs = load_raw_tx_from_str("9 [addr_x] {in, 1, 900}").unwrap();
+    sign_raw_txs_with(&mut raw_txs, &mut accounts, &mut rand_core::OsRng);
+    assert!(append_block(&raw_txs[&9], &producer, &mut chain).is_err());
+
+    assert!(registry.unsubscribe(large) && !registry.unsubscribe(large));
+    assert_eq!(registry.len(), 1);
+}
+
+#[test]
+fn test_append_block_extends_inter_index() {
+    // blocks coming fast, then slowly, then fast again, for the fit to start new segments
+    let appended = (5..=40).map(|block_id: IdType| match block_id {
+        5..=16 => (block_id, 400 + (block_id - 4) * 10),
+        17..=28 => (block_id, 520 + (block_id - 16) * 1000),
+        _ => (block_id, 12520 + (block_id - 28) * 10),
+    });
+    let (mut chain, producer, mut accounts) = build_chain_with_keys(TEST_TXS, HashAlgorithm::default());
+    let mut all_txs = TEST_TXS.to_string();
+    for (block_id, timestamp) in appended {
+        let line = format!("{} [addr_{}] {{in, {}, {}}}\n", block_id, block_id, block_id * 10, timestamp);
+        all_txs.push_str(&line);
+        let mut raw_txs = load_raw_tx_from_str(&line).unwrap();
+        sign_raw_txs_with(&mut raw_txs, &mut accounts, &mut rand_core::OsRng);
+        append_block(&raw_txs[&block_id], &producer, &mut chain).unwrap();
+    }
+    // the same segments as fitted over every header at once
+    let built = build_chain(&all_txs, HashAlgorithm::default());
+    let segments = chain.read_inter_indexs().unwrap();
+    assert!(segments.len() > 1);
+    assert_eq!(segments, built.read_inter_indexs().unwrap());
+    assert_eq!(chain.get_parameter().unwrap().inter_index_timestamps, built.get_parameter().unwrap().inter_index_timestamps);
+    assert_eq!(chain.read_block_header(40).unwrap().inter_index_root, built.read_block_header(40).unwrap().inter_index_root);
+
+    let result = historical_query(&parse_query("value >= 0 AND timestamp IN [1500, 4600]").unwrap(), &mut chain).unwrap();
+    assert!(result.use_inter_index);
+    assert_eq!(result_tx_count(&result), 4);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+}
+
+#[test]
+fn test_query_cache() {
+    let (mut chain, producer, mut accounts) = build_chain_with_keys(TEST_TXS, HashAlgorithm::default());
+    let queries = [
+        parse_query("value >= 10 AND timestamp IN [100, 200]").unwrap(),
+        parse_query("address = 'addr_a' OR value >= 60 USING bloom_filter").unwrap(),
+        parse_query("value >= 50 AND timestamp IN [300, 1000]").unwrap(),
+    ];
+    let values = |result: &OverallResult| {
+        let mut values: Vec<TxType> = result.res_txs.txs().map(|tx| tx.value.trans_value).collect();
+        values.sort_unstable();
+        values
+    };
+    let mut cache = QueryCache::new(8);
+    for q_param in queries.iter() {
+        cache.query(q_param, &mut chain).unwrap();
+    }
+    // the same query, its terms in another order
+    let reordered = parse_query("value >= 60 OR address = 'addr_a' USING bloom_filter").unwrap();
+    assert_eq!(reordered.normalized(), queries[1].normalized());
+    let result = cache.query(&reordered, &mut chain).unwrap();
+    assert_eq!(result.query_param, reordered);
+    assert_eq!(values(&result), vec![10, 60, 70]);
+    assert_eq!(cache.stats(), CacheStats { hits: 1, refreshes: 0, misses: 3, entries: 3 });
+
+    for txs in ["5 [addr_a] {in, 80, 500}", "6 [addr_h] {out, 90, 600}"] {
+        let mut raw_txs = load_raw_tx_from_str(txs).unwrap();
+        sign_raw_txs_with(&mut raw_txs, &mut accounts, &mut rand_core::OsRng);
+        let (_, raw_txs) = raw_txs.into_iter().next().unwrap();
+        append_block(&raw_txs, &producer, &mut chain).unwrap();
+    }
+    // the closed window is reused, the open ended queries extended with blocks 5 and 6
+    for q_param in queries.iter() {
+        let cached = cache.query(q_param, &mut chain).unwrap();
+        let fresh = historical_query(q_param, &mut chain).unwrap();
+        assert_eq!(values(&cached), values(&fresh), "{:?}", q_param);
+        let (verify_result, _) = block_on(cached.verify(&chain)).unwrap();
+        assert!(verify_result.is_ok(), "{:?} {:?}", q_param, verify_result);
+    }
+    assert_eq!(cache.stats(), CacheStats { hits: 2, refreshes: 2, misses: 3, entries: 3 });
+    cache.query(&queries[1], &mut chain).unwrap();
+    assert_eq!(cache.stats().hits, 3);
+
+    // pages are not cached, and the least recently used result is evicted
+    let mut cache = QueryCache::new(1);
+    cache.query(&QueryParam { limit: Some(1), ..queries[1].clone() }, &mut chain).unwrap();
+    for q_param in [&queries[0], &queries[1], &queries[0]] {
+        cache.query(q_param, &mut chain).unwrap();
+    }
+    assert_eq!(cache.stats(), CacheStats { hits: 0, refreshes: 0, misses: 3, entries: 1 });
+}
+
+#[test]
+fn test_invalid_predicates() {
+    let mut chain = build_test_chain();
+    let invalid = [
+        value_query("ten", "20"),
+        QueryParam { key: vec!["balance".to_string()], ..value_query("10", "20") },
+        QueryParam {
+            predicates: vec![QueryRequest { key: "value".to_string(), predicate: Predicate::Prefix { prefix: "1".to_string() } }],
+            ..QueryParam::default()
+        },
+        QueryParam {
+            predicates: vec![QueryRequest {
+                key: "address".to_string(),
+                predicate: Predicate::Eq { value: Value::Number(1) },
+            }],
+            ..QueryParam::default()
+        },
+    ];
+    for q_param in invalid.iter() {
+        assert!(historical_query(q_param, &mut chain).is_err(), "{:?}", q_param);
+    }
+    // an inverted range matches nothing, whichever path evaluates it
+    for intra_index in [true, false] {
+        let q_param = QueryParam { intra_index, ..value_query("50", "20") };
+        let result = historical_query(&q_param, &mut chain).unwrap();
+        assert_eq!(result_tx_count(&result), 0);
+    }
+    let q_param: QueryParam = serde_json::from_str(
+        r#"{"predicates":[{"attribute":"value","predicate":{"op":"range","start":{"Excluded":30},"end":{"Excluded":30}}}]}"#,
+    ).unwrap();
+    assert_eq!(result_tx_count(&historical_query(&q_param, &mut chain).unwrap()), 0);
+}
+
+#[test]
+fn test_verify_with_each_hash_algorithm() {
+    for algorithm in [HashAlgorithm::Blake2b, HashAlgorithm::Sha256, HashAlgorithm::Sha3_256] {
+        let mut chain = build_test_chain_with(algorithm);
+        let mut q_param = value_query("15", "45");
+        q_param.key.push("timestamp".to_string());
+        q_param.value.push([Some("100".to_string()), Some("300".to_string())]);
+        q_param.bloom_filter = true;
+        let result = historical_query(&q_param, &mut chain).unwrap();
+        assert_eq!(result_tx_count(&result), 3);
+        let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+        assert!(verify_result.is_ok(), "{:?}: {:?}", algorithm, verify_result);
+
+        // the proofs do not check out under another hash
+        let mut param = chain.get_parameter().unwrap();
+        param.hash_algorithm = if algorithm == HashAlgorithm::Sha256 { HashAlgorithm::Blake2b } else { HashAlgorithm::Sha256 };
+        chain.set_parameter(param).unwrap();
+        let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+        assert!(!verify_result.is_ok(), "{:?}", algorithm);
+    }
+}
+
+#[test]
+fn test_verify_bloom_filter_skip() {
+    let mut chain = build_test_chain();
+    let q_param = QueryParam {
+        key: vec!["address".to_string()],
+        value: vec![[Some("addr_c".to_string()), Some("addr_c".to_string())]],
+        bloom_filter: true,
+        intra_index: true,
+        ..QueryParam::default()
+    };
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    assert_eq!(result_tx_count(&result), 1);
+    assert!(result.res_vos.0.iter().any(|vo| matches!(vo, BlockVo::BloomSkipped { .. })));
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+}
+
+#[test]
+fn test_verify_forged_bloom_skip() {
+    let mut chain = build_test_chain();
+    let q_param = QueryParam {
+        key: vec!["address".to_string()],
+        value: vec![[Some("addr_c".to_string()), Some("addr_c".to_string())]],
+        bloom_filter: true,
+        intra_index: true,
+        ..QueryParam::default()
+    };
+    let mut result = historical_query(&q_param, &mut chain).unwrap();
+    // skip the block holding the result with the bloom of another block
+    let bloom = chain.read_block_bloom(1).unwrap();
+    result.res_txs.0.retain(|block| block.block_id != 2);
+    for block_vo in result.res_vos.0.iter_mut() {
+        if block_vo.block_id() == 2 {
+            *block_vo = BlockVo::BloomSkipped { bloom: BlockBloom { block_id: 2, ..bloom.clone() } };
+        }
+    }
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidBloomSkip { block_id: 2 }]);
+}
+
+#[test]
+fn test_verify_accumulator_skip() {
+    let mut chain = build_test_chain();
+    // a bloom filter false positive: block 1 lets through the keys of block 2
+    let bloom = chain.read_block_bloom(2).unwrap();
+    chain.block_blooms.insert(1, BlockBloom { block_id: 1, ..bloom });
+    let q_param = QueryParam {
+        key: vec!["address".to_string()],
+        value: vec![[Some("addr_c".to_string()), Some("addr_c".to_string())]],
+        bloom_filter: true,
+        intra_index: true,
+        ..QueryParam::default()
+    };
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    assert_eq!(result_tx_count(&result), 1);
+    assert!(result.res_vos.0.iter().any(|vo| matches!(vo, BlockVo::AccumulatorSkipped { block_id: 1, .. })));
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+}
+
+#[test]
+fn test_verify_forged_accumulator_skip() {
+    let mut chain = build_test_chain();
+    let q_param = QueryParam {
+        key: vec!["address".to_string()],
+        value: vec![[Some("addr_c".to_string()), Some("addr_c".to_string())]],
+        bloom_filter: true,
+        intra_index: true,
+        ..QueryParam::default()
+    };
+    let mut result = historical_query(&q_param, &mut chain).unwrap();
+    // skip the block holding the result with the accumulator of another block
+    let param = chain.get_parameter().unwrap();
+    let accumulator = param.accumulator.clone();
+    let primes = chain.read_block_data(1).unwrap().address_primes(param.hasher());
+    let witness = accumulator.prove_non_membership(&primes, &[address_prime("addr_c", param.hasher())]).unwrap();
+    result.res_txs.0.retain(|block| block.block_id != 2);
+    for block_vo in result.res_vos.0.iter_mut() {
+        if block_vo.block_id() == 2 {
+            *block_vo = BlockVo::AccumulatorSkipped {
+                block_id: 2,
+                value: accumulator.accumulate(&primes),
+                witness: witness.clone(),
+            };
+        }
+    }
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidAccumulatorSkip { block_id: 2 }]);
+}
+
+#[test]
+fn test_verify_withheld_transaction() {
+    let mut chain = build_test_chain();
+    let mut result = historical_query(&value_query("15", "45"), &mut chain).unwrap();
+    let block = result.res_txs.0.iter_mut().find(|block| !block.Txs.is_empty()).unwrap();
+    let block_id = block.block_id;
+    let tx_id = *block.Txs.keys().next().unwrap();
+    block.Txs.remove(&tx_id);
+    // the server can aggregate the signatures of the remaining txs again
+    let param = chain.get_parameter().unwrap();
+    let block_sig = result.res_sigs.0.iter_mut().find(|block_sig| block_sig.block_id() == block_id).unwrap();
+    *block_sig = BlockSig::new(block_sig.header.clone(), &param, &block.Txs);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::MissingResult { block_id, tx_id }]);
+}
+
+#[test]
+fn test_verify_tampered_witness() {
+    let mut chain = build_test_chain();
+    let mut result = historical_query(&value_query("15", "45"), &mut chain).unwrap();
+    let mut tampered = Vec::new();
+    for block_vo in result.res_vos.0.iter_mut() {
+        if let BlockVo::Indexed { block_id, runs, .. } = block_vo {
+            let witness = runs.iter_mut().flat_map(|run| run.txs.iter_mut()).find_map(|tx_proof| tx_proof.witness.as_mut());
+            if let Some(tx) = witness {
+                // keeps its place around the looked up values
+                tx.value.address = "addr_z".to_string();
+                tampered.push(InvalidReason::InvalidMerkleProof { block_id: *block_id, tx_id: None });
+            }
+        }
+    }
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(!tampered.is_empty());
+    assert_eq!(verify_result.reasons(), &tampered[..]);
+}
+
+#[test]
+fn test_verify_disclosed_block() {
+    // a negation is not narrowed down by a lookup, every tx of the block is proven
+    let mut chain = build_test_chain();
+    let mut result = historical_query(&parse_query("NOT value BETWEEN 15 AND 45").unwrap(), &mut chain).unwrap();
+    assert!(result.res_vos.0.iter().all(|block_vo| matches!(block_vo, BlockVo::Evaluated { .. })));
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+    let block_vo = result.res_vos.0.iter_mut().find(|block_vo| block_vo.block_id() == 2).unwrap();
+    if let BlockVo::Evaluated { txs, .. } = block_vo {
+        txs.iter_mut().find_map(|tx_proof| tx_proof.witness.as_mut()).unwrap().value.trans_value = 35;
+    }
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidMerkleProof { block_id: 2, tx_id: None }]);
+}
+
+#[test]
+fn test_verify_truncated_run() {
+    let txs: String = (0..8).map(|i| format!("1 [addr_{}] {{in, {}, 100}}\n", i, i * 10)).collect();
+    let mut chain = build_chain(&txs, HashAlgorithm::default());
+    let mut result = historical_query(&value_query("20", "40"), &mut chain).unwrap();
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+    let BlockVo::Indexed { runs, .. } = &mut result.res_vos.0[0] else {
+        panic!("value lookup not proven by its sorted tree");
+    };
+    // the neighbours at 10 and 50 around the matches at 20, 30 and 40
+    assert_eq!(runs[0].txs.len(), 5);
+    // dropping the leaf above the run leaves room for a hidden match there
+    runs[0].txs.pop();
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[
+        InvalidReason::IncompleteBlockProof { block_id: 1 },
+        InvalidReason::InvalidMerkleProof { block_id: 1, tx_id: None },
+    ]);
+}
+
+#[test]
+fn test_selective_query_vo_smaller_than_block() {
+    let txs: String = (0..64).map(|i| format!("1 [addr_{}] {{in, {}, 100}}\n", i % 16, i)).collect();
+    let mut chain = build_chain(&txs, HashAlgorithm::default());
+    let block_size = bincode::serialize(&chain.read_block_data(1).unwrap()).unwrap().len();
+    for query in ["value BETWEEN 10 AND 12", "address = 'addr_3'", "value IN (5, 40) OR address = 'addr_7'"] {
+        let result = historical_query(&parse_query(query).unwrap(), &mut chain).unwrap();
+        let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+        assert!(verify_result.is_ok(), "{}: {:?}", query, verify_result);
+        assert!(result.res_vos.size() < block_size / 2, "{}: vo of {} bytes for a block of {}", query, result.res_vos.size(), block_size);
+    }
+}
+
+#[test]
+fn test_verify_missing_block() {
+    let mut chain = build_test_chain();
+    let mut result = historical_query(&value_query("15", "45"), &mut chain).unwrap();
+    result.res_vos.0.retain(|vo| vo.block_id() != 4);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::MissingBlock { block_id: 4 }]);
+}
+
+#[test]
+fn test_verify_missing_boundary_block() {
+    let mut chain = build_test_chain();
+    let mut q_param = value_query("0", "100");
+    q_param.key.push("timestamp".to_string());
+    q_param.value.push([Some("150".to_string()), Some("350".to_string())]);
+    let mut result = historical_query(&q_param, &mut chain).unwrap();
+    assert_eq!(result.res_boundary.as_ref().unwrap().left.as_ref().unwrap().block_id, 1);
+    assert_eq!(result.res_boundary.as_ref().unwrap().right.as_ref().unwrap().block_id, 4);
+    // drop the out of range block above the window together with the last block inside it
+    result.res_vos.0.retain(|vo| vo.block_id() < 3);
+    result.res_txs.0.retain(|block| block.block_id < 3);
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[
+        InvalidReason::InvalidInterIndex { block_id: 4 },
+        InvalidReason::MissingBlock { block_id: 3 },
+    ]);
+    // and its header
+    result.res_boundary.as_mut().unwrap().right = None;
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[
+        InvalidReason::InvalidInterIndex { block_id: 4 },
+        InvalidReason::MissingBoundaryBlock { block_id: 3 },
+    ]);
+}
+
+#[test]
+fn test_verify_forged_boundary_block() {
+    let mut chain = build_test_chain();
+    let mut q_param = value_query("0", "100");
+    q_param.key.push("timestamp".to_string());
+    q_param.value.push([Some("150".to_string()), Some("350".to_string())]);
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    // a boundary inside the window
+    let mut forged = result.clone();
+    forged.res_boundary.as_mut().unwrap().right = Some(chain.read_block_header(3).unwrap());
+    let (verify_result, _) = block_on(forged.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidTimeRange { block_id: 3 }]);
+    // a boundary header differing from the chain
+    let mut forged = result;
+    forged.res_boundary.as_mut().unwrap().left.as_mut().unwrap().time_stamp = 50;
+    let (verify_result, _) = block_on(forged.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidHeaderDigest { block_id: 1 }]);
+}
+
+#[test]
+fn test_build_block_rejects_decreasing_timestamp() {
+    let mut chain = MemChain::default();
+    let producer = Keypair::generate(&mut rand_core::OsRng);
+    chain.set_parameter(test_parameter(&producer)).unwrap();
+    let mut raw_txs = load_raw_tx_from_str(TEST_TXS).unwrap();
+    sign_raw_txs(&mut raw_txs, &mut rand_core::OsRng);
+    let mut configs_map = IndexConfigs_map::new();
+    let header = build_block(2, Digest::default(), raw_txs[&2].iter(), &producer, &mut chain, &mut configs_map).unwrap();
+    let err = build_block(3, header.to_digest(), raw_txs[&1].iter(), &producer, &mut chain, &mut configs_map).unwrap_err();
+    assert_eq!(err.downcast_ref::<InvalidReason>(), Some(&InvalidReason::NonMonotonicTimestamp { block_id: 3 }));
+}
+
+#[test]
+fn test_verify_inter_index() {
+    let mut chain = build_test_chain();
+    assert_ne!(chain.read_block_header(4).unwrap().inter_index_root, Digest::default());
+    let mut q_param = value_query("0", "100");
+    q_param.key.push("timestamp".to_string());
+    q_param.value.push([Some("150".to_string()), Some("350".to_string())]);
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    let inter_vo = result.res_inter_vo.as_ref().unwrap();
+    assert_eq!(inter_vo.block_id, 4);
+    assert_eq!(inter_vo.error_bounds, 5.0);
+
+    // a segment other than the committed one
+    let mut forged = result.clone();
+    forged.res_inter_vo.as_mut().unwrap().segments[0].segment.regression_b -= 10.0;
+    let (verify_result, _) = block_on(forged.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidInterIndex { block_id: 4 }]);
+
+    // a window planned without the inter index has to span the whole chain
+    let mut unplanned = result;
+    unplanned.res_inter_vo = None;
+    let (verify_result, _) = block_on(unplanned.verify(&chain)).unwrap();
+    assert!(verify_result.is_ok(), "{:?}", verify_result);
+    unplanned.res_boundary = None;
+    unplanned.res_vos.0.retain(|vo| vo.block_id() != 1);
+    let (verify_result, _) = block_on(unplanned.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::MissingBoundaryBlock { block_id: 1 }]);
+
+    // a server shifting its regression to hide blocks
+    for inter_index in chain.inter_indexs.values_mut() {
+        inter_index.regression_b += 100.0;
+    }
+    let result = historical_query(&q_param, &mut chain).unwrap();
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.reasons().contains(&InvalidReason::InvalidInterIndex { block_id: 4 }));
+}
+
+#[test]
+fn test_build_block_rejects_invalid_signature() {
+    let mut chain = MemChain::default();
+    let producer = Keypair::generate(&mut rand_core::OsRng);
+    chain.set_parameter(test_parameter(&producer)).unwrap();
+    let mut raw_txs = load_raw_tx_from_str(TEST_TXS).unwrap();
+    sign_raw_txs(&mut raw_txs, &mut rand_core::OsRng);
+    let txs = raw_txs.get_mut(&1).unwrap();
+    txs[1].value.trans_value += 1;
+    let err = build_block(1, Digest::default(), txs.iter(), &producer, &mut chain, &mut IndexConfigs_map::new()).unwrap_err();
+    match err.downcast_ref::<InvalidReason>() {
+        Some(InvalidReason::InvalidSignature { block_id: 1, tx_id: Some(_) }) => {}
+        _ => panic!("unexpected error {:?}", err),
+    }
+    // unsigned transactions are rejected as well
+    let raw_txs = load_raw_tx_from_str(TEST_TXS).unwrap();
+    assert!(build_block(1, Digest::default(), raw_txs[&1].iter(), &producer, &mut chain, &mut IndexConfigs_map::new()).is_err());
+}
+
+#[test]
+fn test_build_block_binds_address_keys() {
+    let (mut chain, producer, mut accounts) = build_chain_with_keys(TEST_TXS, HashAlgorithm::default());
+    // addr_a signed for by a key other than the one of its first transaction
+    let mut raw_txs = load_raw_tx_from_str("5 [addr_a] {out, 10, 500}").unwrap();
+    sign_raw_txs(&mut raw_txs, &mut rand_core::OsRng);
+    let err = append_block(&raw_txs[&5], &producer, &mut chain).unwrap_err();
+    match err.downcast_ref::<InvalidReason>() {
+        Some(InvalidReason::InvalidSignature { block_id: 5, tx_id: Some(_) }) => {}
+        _ => panic!("unexpected error {:?}", err),
+    }
+    // two keys for a new address within a block
+    let mut raw_txs = load_raw_tx_from_str("5 [addr_x] {in, 10, 500}\n5 [addr_x] {in, 20, 500}").unwrap();
+    sign_raw_txs(&mut raw_txs, &mut rand_core::OsRng);
+    raw_txs.get_mut(&5).unwrap()[1].sign(&Keypair::generate(&mut rand_core::OsRng));
+    assert!(append_block(&raw_txs[&5], &producer, &mut chain).is_err());
+    assert_eq!(chain.read_account_key(&"addr_x".to_string()).unwrap(), None);
+
+    // the key of addr_a, and a new address bound to the key it comes with
+    let mut raw_txs = load_raw_tx_from_str("5 [addr_a] {out, 10, 500}\n5 [addr_x] {in, 10, 500}").unwrap();
+    sign_raw_txs_with(&mut raw_txs, &mut accounts, &mut rand_core::OsRng);
+    append_block(&raw_txs[&5], &producer, &mut chain).unwrap();
+    assert_eq!(chain.read_account_key(&"addr_x".to_string()).unwrap(), Some(accounts["addr_x"].public));
+}
+
+#[test]
+fn test_verify_result_signatures() {
+    let mut chain = build_test_chain();
+    let param = chain.get_parameter().unwrap();
+    let result = historical_query(&value_query("20", "50"), &mut chain).unwrap();
+    assert_eq!(result.res_sigs.0.len(), result.res_txs.0.len());
+    for (block_sig, block) in result.res_sigs.0.iter().zip(result.res_txs.0.iter()) {
+        assert!(block_sig.verify(&param, &block.Txs));
+        let other_param = test_parameter(&Keypair::generate(&mut rand_core::OsRng));
+        assert!(!block_sig.verify(&other_param, &block.Txs));
+    }
+    let (verify_result, _) = block_on(result.verify(&chain)).unwrap();
+    assert!(verify_result.reasons().is_empty());
+
+    // a header signed by someone else
+    let mut forged = result.clone();
+    let block_sig = &mut forged.res_sigs.0[0];
+    let block_id = block_sig.block_id();
+    let other = Keypair::generate(&mut rand_core::OsRng);
+    block_sig.header.signature = other.sign(&mut block_sig.header.signing_transcript(param.hasher()));
+    let txs = &forged.res_txs.0[0].Txs;
+    *block_sig = BlockSig::new(block_sig.header.clone(), &test_parameter(&other), txs);
+    let (verify_result, _) = block_on(forged.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidSignature { block_id, tx_id: None }]);
+
+    // a missing aggregate
+    let mut unsigned = result;
+    unsigned.res_sigs.0.remove(0);
+    let (verify_result, _) = block_on(unsigned.verify(&chain)).unwrap();
+    assert_eq!(verify_result.reasons(), &[InvalidReason::InvalidSignature { block_id, tx_id: None }]);
+}
+
+#[test]
+fn test_invalid_reason_json() {
+    let reason = InvalidReason::MissingResult { block_id: 2, tx_id: 5 };
+    let json = serde_json::to_string(&reason).unwrap();
+    assert_eq!(json, r#"{"reason":"MissingResult","block_id":2,"tx_id":5}"#);
+    assert_eq!(serde_json::from_str::<InvalidReason>(&json).unwrap(), reason);
+}
+
+#[test]
+fn test_header_commits_to_block_data() {
+    let chain = build_test_chain();
+    for (id, header) in chain.block_headers.iter() {
+        let block_data = chain.read_block_data(*id).unwrap();
+        assert_eq!(header.rmt_root, block_data.merkle_root(&BLAKE2B));
+    }
+    let header = chain.read_block_header(2).unwrap();
+    let next_header = chain.read_block_header(3).unwrap();
+    assert_eq!(next_header.pre_hash, header.to_digest());
+
+    let mut tampered = header.clone();
+    tampered.BMT_root = Digest::default();
+    assert_ne!(tampered.to_digest(), header.to_digest());
+
+    let mut tampered = header.clone();
+    tampered.rmt_root = Digest::default();
+    assert_ne!(tampered.to_digest(), header.to_digest());
+}
+
+#[test]
+fn test_mmr_proof() {
+    let leaves: Vec<Digest> = (0..13u64).map(|i| i.to_digest()).collect();
+    let mut peaks = MmrPeaks::default();
+    for (count, leaf) in leaves.iter().enumerate() {
+        peaks.append(leaf, &BLAKE2B);
+        let prefix = &leaves[..=count];
+        for (index, leaf) in prefix.iter().enumerate() {
+            let proof = MmrProof::new(prefix, index as u64, &BLAKE2B).unwrap();
+            assert!(proof.verify(leaf, &peaks.root(&BLAKE2B), &BLAKE2B));
+            assert!(!proof.verify(&leaves[(index + 1) % leaves.len()], &peaks.root(&BLAKE2B), &BLAKE2B));
+        }
+        assert!(MmrProof::new(prefix, prefix.len() as u64, &BLAKE2B).is_none());
+    }
+}
+
+#[test]
+fn test_mmr_consistency_proof() {
+    let leaves: Vec<Digest> = (0..13u64).map(|i| i.to_digest()).collect();
+    let root = |count: usize| {
+        let mut peaks = MmrPeaks::default();
+        leaves[..count].iter().for_each(|leaf| peaks.append(leaf, &BLAKE2B));
+        peaks.root(&BLAKE2B)
+    };
+    for new_count in 0..=leaves.len() {
+        for old_count in 0..=new_count {
+            let proof = ConsistencyProof::new(&leaves[..new_count], old_count as u64, &BLAKE2B).unwrap();
+            assert!(proof.verify(&root(old_count), &root(new_count), &BLAKE2B));
+            if new_count > old_count {
+                let mut forked = leaves[..new_count].to_vec();
+                forked[new_count - 1] = 100u64.to_digest();
+                let forked_proof = ConsistencyProof::new(&forked, old_count as u64, &BLAKE2B).unwrap();
+                assert!(!forked_proof.verify(&root(old_count), &root(new_count), &BLAKE2B));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_verify_header_with_mmr() {
+    let chain = build_test_chain();
+    let param = chain.get_parameter().unwrap();
+    let tip = chain.read_block_header(4).unwrap();
+    assert_eq!(chain.read_mmr_peaks().unwrap().root(param.hasher()), tip.mmr_root);
+    for id in 1..4 {
+        let header = chain.read_block_header(id).unwrap();
+        let proof = mmr_proof(id, &chain).unwrap();
+        assert!(proof.verify_header(&header, &tip, &param));
+        let mut forged = header.clone();
+        forged.time_stamp += 1;
+        assert!(!proof.verify_header(&forged, &tip, &param));
+    }
+    // the tip is not committed by its own mmr
+    assert!(mmr_proof(4, &chain).is_err());
+
+    // the proofs from the stored nodes are the ones from every header
+    let leaves: Vec<Digest> = (1..4).map(|id| chain.read_block_header(id).unwrap().to_digest_with(param.hasher())).collect();
+    for id in 1..4 {
+        assert_eq!(mmr_proof(id, &chain).unwrap(), MmrProof::new(&leaves, id - 1, param.hasher()).unwrap());
+    }
+    for old_id in 1..=4 {
+        let old_tip = chain.read_block_header(old_id).unwrap();
+        let proof = mmr_consistency_proof(old_id, 4, &chain).unwrap();
+        assert!(proof.verify_headers(&old_tip, &tip, &param));
+        assert_eq!(proof, ConsistencyProof::new(&leaves, old_id - 1, param.hasher()).unwrap());
+    }
+    assert!(mmr_consistency_proof(3, 2, &chain).is_err());
+}
@@ -1,6 +1,7 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use super::*;
+use merlin::Transcript;
 use serde::{Deserialize, Serialize};
 
 static TX_ID_CNT: AtomicU64 = AtomicU64::new(0);
@@ -21,29 +22,41 @@ impl TransactionValue{
         result
     }
 }
-#[derive(Debug, Default, Clone, Eq, PartialEq)]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct RawTransaction {
     pub block_id: IdType,
     pub key: KeyType,
     pub value: TransactionValue,
+    pub public_key: PkType,
+    pub signature: Signature,
 }
 
-// impl RawTransaction{
-//     fn sign<'a>(&self, key_pair:&'a Keypair) -> Signature {
-//         let ctx = signing_context(b"");
-//         let raw_data = String::from(&self.block_id.to_string())
-//             + &String::from(&self.key)
-//             + &String::from(&self.value.to_string());
-        
-//         key_pair.sign(ctx.bytes(raw_data.as_bytes()))
-//     }
-// }
+impl RawTransaction{
+    /// Sign the transaction with the keypair of its sender.
+    pub fn sign(&mut self, key_pair: &Keypair) {
+        self.public_key = key_pair.public;
+        self.signature = key_pair.sign(&mut signing_transcript(self.block_id, &self.value));
+    }
+}
+
+/// Transcript a transaction is signed under, binding the block and every field of `value`.
+pub fn signing_transcript(block_id: IdType, value: &TransactionValue) -> Transcript {
+    let mut transcript = Transcript::new(b"chain-demo transaction");
+    transcript.append_u64(b"block_id", block_id);
+    transcript.append_message(b"address", value.address.as_bytes());
+    transcript.append_u64(b"trans_in", value.trans_in as u64);
+    transcript.append_u64(b"trans_value", value.trans_value);
+    transcript.append_u64(b"time_stamp", value.time_stamp);
+    transcript
+}
 
 #[derive(Debug, Clone, Eq, PartialEq,Serialize, Deserialize)]
 pub struct Transaction {
     pub id: IdType,
     pub block_id: IdType,
     pub value: TransactionValue,
+    pub public_key: PkType,
+    pub signature: Signature,
 }
 
 impl Transaction {
@@ -55,8 +68,36 @@ impl Transaction {
             id,
             block_id,
             value,
+            public_key: tx.public_key,
+            signature: tx.signature,
         }
     }
+
+    /// Makes the transactions created from now on have ids from `id` up, as
+    /// when appending to a chain built by another process.
+    pub fn reserve_ids_below(id: IdType) {
+        TX_ID_CNT.fetch_max(id, Ordering::SeqCst);
+    }
+
+    pub fn verify_signature(&self) -> bool {
+        self.signature.verify(&self.public_key, &mut signing_transcript(self.block_id, &self.value))
+    }
+}
+
+impl Digestible for Transaction {
+    fn to_digest_with(&self, hasher: &dyn Hasher) -> Digest {
+        let mut state = hasher.context();
+        state.update(&self.id.to_le_bytes());
+        state.update(&self.block_id.to_le_bytes());
+        state.update(self.value.address.as_bytes());
+        state.update(&[self.value.trans_in as u8]);
+        state.update(&self.value.trans_value.to_le_bytes());
+        state.update(&self.value.time_stamp.to_le_bytes());
+        state.update(self.public_key.as_bytes());
+        state.update(self.signature.r.as_bytes());
+        state.update(self.signature.s.as_bytes());
+        state.finish()
+    }
 }
 
 
@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use std::path::Path;
-use std::collections::{BTreeMap};
+use std::collections::{BTreeMap, HashMap};
+use rand_core::{CryptoRng, RngCore};
 use super::*;
 use std::io::BufReader;
 use std::fs::File;
@@ -50,22 +51,79 @@ pub fn load_raw_tx_from_str(input: &str) -> Result<BTreeMap<IdType, Vec<RawTrans
             .filter(|s| !s.is_empty())
             .collect();
         let mut iter = raw_value.iter();
+        let mut field = |name: &str| iter.next().with_context(|| format!("no {} in line {}", name, line));
         let value = TransactionValue {
-            trans_in: iter.next().unwrap().eq("in"),
-            trans_value: iter.next().unwrap().parse::<TxType>().unwrap(),
-            time_stamp: iter.next().unwrap().parse::<TsType>().unwrap(),
+            trans_in: field("direction")?.eq("in"),
+            trans_value: field("value")?
+                .parse::<TxType>()
+                .with_context(|| format!("failed to parse value of line {}", line))?,
+            time_stamp: field("timestamp")?
+                .parse::<TsType>()
+                .with_context(|| format!("failed to parse timestamp of line {}", line))?,
             address: key.clone(),
         };
+        // left unsigned, see `RawTransaction::sign`
         let raw_tx = RawTransaction {
             block_id,
             key,
             value,
+            ..Default::default()
         };
         res.entry(block_id).or_insert_with(Vec::new).push(raw_tx);
     }
     Ok(res)
 }
 
+/// Sign every raw transaction with the keypair of its sender address,
+/// generating one the first time an address shows up.
+pub fn sign_raw_txs<R: RngCore + CryptoRng>(
+    raw_txs: &mut BTreeMap<IdType, Vec<RawTransaction>>,
+    rng: &mut R,
+) -> HashMap<KeyType, Keypair> {
+    let mut key_pairs: HashMap<KeyType, Keypair> = HashMap::new();
+    sign_raw_txs_with(raw_txs, &mut key_pairs, rng);
+    key_pairs
+}
+
+/// Sign every raw transaction with the keypair of its sender in `key_pairs`,
+/// generating and adding one for an address not in it yet.
+pub fn sign_raw_txs_with<R: RngCore + CryptoRng>(
+    raw_txs: &mut BTreeMap<IdType, Vec<RawTransaction>>,
+    key_pairs: &mut HashMap<KeyType, Keypair>,
+    rng: &mut R,
+) {
+    for raw_tx in raw_txs.values_mut().flatten() {
+        let key_pair = key_pairs
+            .entry(raw_tx.key.clone())
+            .or_insert_with(|| Keypair::generate(rng));
+        raw_tx.sign(key_pair);
+    }
+}
+
+/// Write the secrets of `key_pairs` to `path` as json, in hex by address.
+pub fn save_account_keys(path: &Path, key_pairs: &HashMap<KeyType, Keypair>) -> Result<()> {
+    let secrets: BTreeMap<&KeyType, String> = key_pairs.iter()
+        .map(|(address, key_pair)| (address, hex::encode(key_pair.to_bytes())))
+        .collect();
+    std::fs::write(path, serde_json::to_string_pretty(&secrets)?)
+        .with_context(|| format!("failed to write account keys to {:?}", path))
+}
+
+/// Keypairs of the addresses written to `path` by `save_account_keys`.
+pub fn load_account_keys(path: &Path) -> Result<HashMap<KeyType, Keypair>> {
+    let data = std::fs::read_to_string(path).with_context(|| format!("failed to read account keys from {:?}", path))?;
+    let secrets: BTreeMap<KeyType, String> = serde_json::from_str(&data)?;
+    let mut key_pairs = HashMap::new();
+    for (address, secret) in secrets {
+        let key_pair = hex::decode(&secret).ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .and_then(Keypair::from_bytes)
+            .with_context(|| format!("invalid key of {}", address))?;
+        key_pairs.insert(address, key_pair);
+    }
+    Ok(key_pairs)
+}
+
 /// Linear Regression
 /// Default data type FloatType=f64
 pub fn linear_regression(arr_x: &[TsType], arr_y: &[IdType]) -> (FloatType, FloatType) {
@@ -147,7 +205,8 @@ mod tests {
                             trans_value: 50,
                             time_stamp: 1571443461,
                             address:"muhtvdmsnbQEPFuEmxcChX58fGvXaaUoVt".to_string()
-                        }
+                        },
+                        ..Default::default()
                     },
                     RawTransaction {
                         block_id: 1,
@@ -157,11 +216,33 @@ mod tests {
                             trans_value: 10,
                             time_stamp: 1571443461,
                             address:"mvbnrCX3bg1cDRUu8pkecrvP6vQkSLDSou".to_string(),
-                        }
+                        },
+                        ..Default::default()
                     },
                 ],);
             out
         };
         assert_eq!(load_raw_tx_from_str(&input).unwrap(),expect);
     }
+
+    #[test]
+    fn test_account_keys_round_trip() {
+        let mut raw_txs = load_raw_tx_from_str("1 [addr_a] {in, 10, 100}\n1 [addr_b] {out, 20, 100}").unwrap();
+        let key_pairs = sign_raw_txs(&mut raw_txs, &mut rand_core::OsRng);
+        let path = std::env::temp_dir().join(format!("account-keys-{}.json", std::process::id()));
+        save_account_keys(&path, &key_pairs).unwrap();
+        let loaded = load_account_keys(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        for (address, key_pair) in key_pairs {
+            assert_eq!(loaded[&address].public, key_pair.public);
+        }
+    }
+
+    #[test]
+    fn test_load_from_str_errors() {
+        for input in ["1 [a] {in}", "1 [a] {in, 10}", "1 [a] {in, x, 5}", "1 [a] {in, 10, -5}", "1 [a]", "x [a] {in, 10, 5}"] {
+            assert!(load_raw_tx_from_str(input).is_err(), "{}", input);
+        }
+    }
 }
\ No newline at end of file
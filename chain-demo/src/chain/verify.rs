@@ -1,13 +1,63 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use serde::{Serialize, Deserialize};
+use crate::digest::Digestible;
+use super::*;
 
 
-/// To be improved
-/// 
+/// Why a query result failed verification, pointing at the offending block and tx.
+///
+/// Serialized with a `reason` tag, e.g. `{"reason":"MissingResult","block_id":2,"tx_id":5}`.
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "reason")]
 pub enum InvalidReason {
-    InvalidSignature,
+    /// a signature does not match, `tx_id` is none for the block itself
+    InvalidSignature { block_id: IdType, tx_id: Option<IdType> },
+    /// the header is not linked to the digest of the previous header
+    InvalidHeaderDigest { block_id: IdType },
+    /// the merkle proof does not lead to the rmt_root of its block, `tx_id` is
+    /// none for the proof of the block as a whole
+    InvalidMerkleProof { block_id: IdType, tx_id: Option<IdType> },
+    /// the merkle paths do not cover every transaction of the block
+    IncompleteBlockProof { block_id: IdType },
+    /// a block of the query range is not covered by the vo
+    MissingBlock { block_id: IdType },
+    /// the vo stops inside the timestamp window, `block_id` is the block left out beyond it
+    MissingBoundaryBlock { block_id: IdType },
+    /// the inter index proof is not committed by the latest header `block_id`,
+    /// or the vo does not span the block range it plans
+    InvalidInterIndex { block_id: IdType },
+    /// a bloom filter skip is not backed by the BMT_root of the block
+    InvalidBloomSkip { block_id: IdType },
+    /// an accumulator skip is not backed by the address_acc of the block, or
+    /// does not exclude every queried address
+    InvalidAccumulatorSkip { block_id: IdType },
+    /// the timestamp of a block is before the one of its previous block
+    NonMonotonicTimestamp { block_id: IdType },
+    /// a block is claimed out of the timestamp window but is not, or the other way round
+    InvalidTimeRange { block_id: IdType },
+    /// a matching transaction is left out of the result
+    MissingResult { block_id: IdType, tx_id: IdType },
+    /// a returned transaction does not match the query
+    ResultOutOfRange { block_id: IdType, tx_id: IdType },
+    /// a returned transaction is not proven by the vo
+    UnprovenResult { block_id: IdType, tx_id: IdType },
+    /// the aggregates are not the ones of the returned transactions
+    InvalidAggregate,
+    /// a block is skipped by a top-k query although it may beat the k-th
+    /// value, `block_id` is none when more than k transactions are returned
+    InvalidTopK { block_id: Option<IdType> },
 }
 
+impl fmt::Display for InvalidReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for InvalidReason {}
+
 #[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct VerifyResult(Vec<InvalidReason>);
 
@@ -23,4 +73,185 @@ impl VerifyResult {
     pub fn is_ok(&self) -> bool {
         self.0.is_empty()
     }
-}
\ No newline at end of file
+
+    pub fn reasons(&self) -> &[InvalidReason] {
+        &self.0
+    }
+}
+
+/// Check the vo of a single block against its header.
+///
+/// `block_txs` are the transactions returned for this block, `selection` the
+/// one of the query and `param` the one of the chain. `threshold` is the k-th
+/// value of a top-k query returning k transactions, no match left out being above it.
+pub fn verify_block_vo(
+    block_vo: &BlockVo,
+    block_header: &BlockHeader,
+    block_txs: Option<&BlockTxs>,
+    selection: &Selection,
+    threshold: Option<TxType>,
+    param: &Parameter,
+) -> VerifyResult {
+    let hasher = param.hasher();
+    let (window, condition) = (selection.window, &selection.condition);
+    let mut result = VerifyResult::default();
+    let block_id = block_vo.block_id();
+    let in_window = window.is_none_or(|(left, right)| {
+        block_header.time_stamp >= left && block_header.time_stamp <= right
+    });
+    let returned: HashMap<IdType, Transaction> = block_txs
+        .map(|block| block.Txs.clone())
+        .unwrap_or_default();
+    let mut proven: HashSet<IdType> = HashSet::new();
+    match block_vo {
+        BlockVo::OutOfRange { .. } => {
+            if in_window {
+                result.add(InvalidReason::InvalidTimeRange { block_id });
+            }
+        }
+        BlockVo::BloomSkipped { bloom } => {
+            if !bloom.validate(&block_header.BMT_root, hasher)
+            || judge_contain_key(condition, bloom, hasher) {
+                result.add(InvalidReason::InvalidBloomSkip { block_id });
+            }
+        }
+        BlockVo::AccumulatorSkipped { value, witness, .. } => {
+            if value.to_digest_with(hasher) != block_header.address_acc
+            || !accumulator_keys(condition, hasher).is_some_and(|excluded| witness.verify(&param.accumulator, value, &excluded)) {
+                result.add(InvalidReason::InvalidAccumulatorSkip { block_id });
+            }
+        }
+        BlockVo::BelowTopK { .. } => {
+            if !in_window {
+                result.add(InvalidReason::InvalidTimeRange { block_id });
+            }
+            if threshold.is_none_or(|threshold| block_header.max_value > threshold) {
+                result.add(InvalidReason::InvalidTopK { block_id: Some(block_id) });
+            }
+        }
+        BlockVo::Evaluated { tx_count, txs, proof, .. } => {
+            if !in_window {
+                result.add(InvalidReason::InvalidTimeRange { block_id });
+            }
+            let mut covered: HashSet<usize> = HashSet::new();
+            let mut leaves: BTreeMap<usize, Digest> = BTreeMap::new();
+            for tx_proof in txs {
+                let fresh_index = covered.insert(tx_proof.index);
+                let Some(tx) = proven_tx(tx_proof, &returned, block_id, condition, threshold, &mut proven, &mut result) else {
+                    continue;
+                };
+                leaves.insert(tx_proof.index, tx.to_digest_with(hasher));
+                if tx.id != tx_proof.tx_id || !fresh_index {
+                    result.add(InvalidReason::InvalidMerkleProof { block_id, tx_id: Some(tx_proof.tx_id) });
+                }
+            }
+            // every leaf of the block has to be proven, matched or not
+            if covered.len() != *tx_count || covered.iter().any(|index| *index >= *tx_count) {
+                result.add(InvalidReason::IncompleteBlockProof { block_id });
+            }
+            // a withheld result leaves nothing to hash, it is reported above
+            let indices: Vec<usize> = leaves.keys().copied().collect();
+            let leaves: Vec<Digest> = leaves.into_values().collect();
+            if indices.len() == covered.len()
+            && (proof.indices != indices
+                || !proof.validate(hasher, &block_header.rmt_root.0, &leaves, *tx_count)) {
+                result.add(InvalidReason::InvalidMerkleProof { block_id, tx_id: None });
+            }
+        }
+        BlockVo::Indexed { tx_count, roots, runs, .. } => {
+            if !in_window {
+                result.add(InvalidReason::InvalidTimeRange { block_id });
+            }
+            let mut valid_proof = roots.len() == SORTED_ATTRIBUTES.len()
+                && intra_index_root(roots, hasher) == block_header.intra_index_root;
+            let lookups = condition.sorted_lookups();
+            // every lookup has to be proven, or a match may be left out
+            let mut complete = lookups.as_ref().is_some_and(|lookups| lookups.len() == runs.len());
+            for (lookup, run) in lookups.iter().flatten().zip(runs.iter()) {
+                let range = run.proof.range(run.txs.len());
+                if range.is_empty() {
+                    complete = false;
+                    continue;
+                }
+                let mut leaves = Vec::new();
+                for (index, tx_proof) in range.clone().zip(run.txs.iter()) {
+                    let Some(tx) = proven_tx(tx_proof, &returned, block_id, condition, threshold, &mut proven, &mut result) else {
+                        continue;
+                    };
+                    if tx.id != tx_proof.tx_id || tx_proof.index != index {
+                        result.add(InvalidReason::InvalidMerkleProof { block_id, tx_id: Some(tx_proof.tx_id) });
+                    }
+                    // the leaf below the matches, the matches, then the leaf above them,
+                    // the ends of the run being outer leaves unless they end the tree
+                    let (first, last) = (index == range.start, index + 1 == range.end);
+                    let below_required = first && range.start > 0;
+                    let above_required = last && range.end < *tx_count;
+                    let in_place = match attribute_value(&lookup.key, tx).and_then(|value| lookup.predicate.position(&value)) {
+                        Some(Ordering::Less) => first && !above_required,
+                        Some(Ordering::Equal) => !below_required && !above_required,
+                        Some(Ordering::Greater) => last && !below_required,
+                        None => false,
+                    };
+                    if !in_place {
+                        complete = false;
+                    }
+                    leaves.push(tx.to_digest_with(hasher));
+                }
+                // a withheld result leaves nothing to hash, it is reported above
+                let root = SORTED_ATTRIBUTES.iter().position(|attribute| *attribute == lookup.key)
+                    .and_then(|attribute| roots.get(attribute));
+                if leaves.len() == run.txs.len()
+                && !root.is_some_and(|root| run.proof.validate(hasher, &root.0, &leaves, *tx_count)) {
+                    valid_proof = false;
+                }
+            }
+            if !complete {
+                result.add(InvalidReason::IncompleteBlockProof { block_id });
+            }
+            if !valid_proof {
+                result.add(InvalidReason::InvalidMerkleProof { block_id, tx_id: None });
+            }
+        }
+    }
+    let mut unproven: Vec<IdType> = returned.keys().filter(|id| !proven.contains(id)).copied().collect();
+    unproven.sort_unstable();
+    for tx_id in unproven {
+        result.add(InvalidReason::UnprovenResult { block_id, tx_id });
+    }
+    result
+}
+
+/// The transaction `tx_proof` stands for: its witness, which must not be a
+/// match left out, or the returned one, which is then proven. `None` when the
+/// vo claims a result the server did not return.
+fn proven_tx<'a>(
+    tx_proof: &'a TxProof,
+    returned: &'a HashMap<IdType, Transaction>,
+    block_id: IdType,
+    condition: &Condition,
+    threshold: Option<TxType>,
+    proven: &mut HashSet<IdType>,
+    result: &mut VerifyResult,
+) -> Option<&'a Transaction> {
+    match &tx_proof.witness {
+        Some(tx) => {
+            if condition.matches(tx) && threshold.is_none_or(|threshold| tx.value.trans_value > threshold) {
+                result.add(InvalidReason::MissingResult { block_id, tx_id: tx.id });
+            }
+            Some(tx)
+        }
+        None => match returned.get(&tx_proof.tx_id) {
+            Some(tx) => {
+                if !condition.matches(tx) {
+                    result.add(InvalidReason::ResultOutOfRange { block_id, tx_id: tx.id });
+                }
+                proven.insert(tx.id);
+                Some(tx)
+            }
+            None => {
+                result.add(InvalidReason::MissingResult { block_id, tx_id: tx_proof.tx_id });
+                None
+            }
+        },
+    }
+}
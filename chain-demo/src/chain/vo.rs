@@ -0,0 +1,342 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use crate::merkle_tree::{MerkleLemma, MerkleTree, MultiProof, Proof, RangeProof};
+use merlin::Transcript;
+use super::*;
+
+/// The `index`-th leaf of a tree of a block, as covered by a multiproof or
+/// a range proof.
+///
+/// Transactions in the result are referred to by `tx_id`; the others are
+/// carried in `witness` so that the verifier can check they do not match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxProof {
+    pub index: usize,
+    pub tx_id: IdType,
+    pub witness: Option<Transaction>,
+}
+
+/// Leaves of a sorted tree of a block hit by a lookup: its matches, with the
+/// leaf right below and the one right above them if any, which show that no
+/// other leaf matches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunProof {
+    pub txs: Vec<TxProof>,
+    pub proof: RangeProof,
+}
+
+/// Verification object of a single block touched by a query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockVo {
+    /// block time_stamp is outside of the queried window
+    OutOfRange { block_id: IdType },
+    /// bloom filter at the root of the block's bloom merkle tree rejects every queried key
+    BloomSkipped { bloom: BlockBloom },
+    /// accumulator over the addresses of the block, committed by `address_acc`,
+    /// excludes every queried address
+    AccumulatorSkipped {
+        block_id: IdType,
+        value: AccumulatorValue,
+        witness: NonMembershipWitness,
+    },
+    /// the `max_value` of the block header is not above the k-th value of a
+    /// top-k result, so no transaction of the block can make it
+    BelowTopK { block_id: IdType },
+    /// every transaction of the block is proven against `rmt_root` by `proof`
+    Evaluated {
+        block_id: IdType,
+        tx_count: usize,
+        txs: Vec<TxProof>,
+        proof: MultiProof,
+    },
+    /// the matches are in the `runs` of the `sorted_lookups` of the condition,
+    /// one each, proven against the root of the tree sorted by its attribute
+    /// among `roots`, which `intra_index_root` commits
+    Indexed {
+        block_id: IdType,
+        tx_count: usize,
+        roots: Vec<Digest>,
+        runs: Vec<RunProof>,
+    },
+}
+
+impl BlockVo {
+    pub fn block_id(&self) -> IdType {
+        match self {
+            BlockVo::OutOfRange { block_id } => *block_id,
+            BlockVo::BloomSkipped { bloom } => bloom.block_id,
+            BlockVo::AccumulatorSkipped { block_id, .. } => *block_id,
+            BlockVo::BelowTopK { block_id } => *block_id,
+            BlockVo::Evaluated { block_id, .. } => *block_id,
+            BlockVo::Indexed { block_id, .. } => *block_id,
+        }
+    }
+
+    /// Prove the transactions of `block_data` matching `condition`, `matched`
+    /// being the ones returned, through the sorted lookups of the condition,
+    /// or else by proving every transaction of the block.
+    pub fn evaluated(
+        block_data: &BlockData,
+        matched: &HashMap<IdType, Transaction>,
+        condition: &Condition,
+        hasher: &'static dyn Hasher,
+    ) -> Self {
+        match condition.sorted_lookups() {
+            Some(lookups) if !block_data.txs.is_empty() => Self::indexed(block_data, matched, &lookups, hasher),
+            _ => Self::disclosed(block_data, matched, hasher),
+        }
+    }
+
+    fn indexed(
+        block_data: &BlockData,
+        matched: &HashMap<IdType, Transaction>,
+        lookups: &[QueryRequest],
+        hasher: &'static dyn Hasher,
+    ) -> Self {
+        let sorted: Vec<(Vec<usize>, MerkleTree<Digest>)> = SORTED_ATTRIBUTES.iter().map(|attribute| {
+            let positions = block_data.sorted_positions(attribute);
+            let tree = block_data.sorted_tree(&positions, hasher);
+            (positions, tree)
+        }).collect();
+        let roots = sorted.iter().map(|(_, tree)| {
+            let mut root = Digest::default();
+            root.0.copy_from_slice(tree.root_hash());
+            root
+        }).collect();
+        let runs = lookups.iter().map(|lookup| {
+            let attribute = SORTED_ATTRIBUTES.iter().position(|attribute| *attribute == lookup.key).expect("sorted attribute");
+            let (positions, tree) = &sorted[attribute];
+            let place = |position: &usize| {
+                let value = attribute_value(&lookup.key, &block_data.txs[*position]).expect("attribute of a tx");
+                lookup.predicate.position(&value).expect("interval lookup")
+            };
+            let start = positions.partition_point(|position| place(position) == Ordering::Less);
+            let end = positions.partition_point(|position| place(position) != Ordering::Greater);
+            let range = start.saturating_sub(1)..(end + 1).min(positions.len());
+            let txs = range.clone().map(|index| {
+                let tx = &block_data.txs[positions[index]];
+                TxProof {
+                    index,
+                    tx_id: tx.id,
+                    witness: if matched.contains_key(&tx.id) { None } else { Some(tx.clone()) },
+                }
+            }).collect();
+            RunProof { txs, proof: tree.gen_range_proof(range).expect("range within tree") }
+        }).collect();
+        BlockVo::Indexed {
+            block_id: block_data.block_id,
+            tx_count: block_data.txs.len(),
+            roots,
+            runs,
+        }
+    }
+
+    fn disclosed(block_data: &BlockData, matched: &HashMap<IdType, Transaction>, hasher: &'static dyn Hasher) -> Self {
+        let tree = block_data.merkle_tree(hasher);
+        let txs = block_data.txs.iter().enumerate().map(|(index, tx)| {
+            TxProof {
+                index,
+                tx_id: tx.id,
+                witness: if matched.contains_key(&tx.id) { None } else { Some(tx.clone()) },
+            }
+        }).collect();
+        let indices: Vec<usize> = (0..block_data.txs.len()).collect();
+        BlockVo::Evaluated {
+            block_id: block_data.block_id,
+            tx_count: block_data.txs.len(),
+            txs,
+            proof: tree.gen_multi_proof(&indices).expect("indices within tree"),
+        }
+    }
+}
+
+/// Verification objects of every block touched by a query, in visiting order.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultVos(pub Vec<BlockVo>);
+
+impl ResultVos {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn size(&self) -> usize {
+        bincode::serialize(self).map(|bytes| bytes.len()).unwrap_or(0)
+    }
+}
+
+/// Headers of the blocks right below and right above a timestamp window,
+/// `None` where the window reaches the end of the chain.
+///
+/// As timestamps do not decrease along the chain, the blocks strictly between
+/// them are exactly the ones inside the window.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowBoundary {
+    pub left: Option<BlockHeader>,
+    pub right: Option<BlockHeader>,
+}
+
+impl WindowBoundary {
+    pub fn size(&self) -> usize {
+        bincode::serialize(self).map(|bytes| bytes.len()).unwrap_or(0)
+    }
+}
+
+/// Signatures of a returned block folded into one: the producer's over `header`,
+/// then the sender's over every returned transaction, by ascending tx id.
+///
+/// Checking it is a single multiscalar multiplication, and does not need the
+/// header chain beyond the producer key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSig {
+    pub header: BlockHeader,
+    pub signature: AggregateSignature,
+}
+
+impl BlockSig {
+    pub fn new(header: BlockHeader, param: &Parameter, txs: &HashMap<IdType, Transaction>) -> Self {
+        let sorted_txs = sorted_txs(txs);
+        let mut signatures = vec![header.signature];
+        signatures.extend(sorted_txs.iter().map(|tx| tx.signature));
+        let signature = AggregateSignature::aggregate(statements(&header, param, &sorted_txs), &signatures);
+        Self { header, signature }
+    }
+
+    pub fn block_id(&self) -> IdType {
+        self.header.block_id
+    }
+
+    /// Check that the producer signed `header` and the senders signed `txs`.
+    pub fn verify(&self, param: &Parameter, txs: &HashMap<IdType, Transaction>) -> bool {
+        txs.values().all(|tx| tx.block_id == self.header.block_id)
+            && self.signature.verify(statements(&self.header, param, &sorted_txs(txs)))
+    }
+}
+
+fn sorted_txs(txs: &HashMap<IdType, Transaction>) -> Vec<&Transaction> {
+    let mut sorted: Vec<&Transaction> = txs.values().collect();
+    sorted.sort_unstable_by_key(|tx| tx.id);
+    sorted
+}
+
+fn statements(header: &BlockHeader, param: &Parameter, txs: &[&Transaction]) -> Vec<(PkType, Transcript)> {
+    let mut statements = vec![(param.producer_key, header.signing_transcript(param.hasher()))];
+    statements.extend(txs.iter().map(|tx| (tx.public_key, signing_transcript(tx.block_id, &tx.value))));
+    statements
+}
+
+/// One aggregated signature per returned block.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultSigs(pub Vec<BlockSig>);
+
+impl ResultSigs {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn size(&self) -> usize {
+        bincode::serialize(self).map(|bytes| bytes.len()).unwrap_or(0)
+    }
+}
+
+/// Inclusion proof of the `index`-th segment of the inter index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SegmentProof {
+    pub index: usize,
+    pub segment: InterIndex,
+    pub lemma: MerkleLemma,
+}
+
+/// Proof of the inter index segments a timestamp window was planned with,
+/// against the `inter_index_root` of the latest header `block_id`.
+///
+/// Next to the segments covering both ends of the window, their successors are
+/// proven too, showing that no later segment covers the ends instead.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InterIndexVo {
+    pub block_id: IdType,
+    pub error_bounds: FloatType,
+    pub tree_root: Digest,
+    pub segment_count: usize,
+    pub segments: Vec<SegmentProof>,
+}
+
+impl InterIndexVo {
+    pub fn new(
+        block_id: IdType,
+        error_bounds: FloatType,
+        segments: &[InterIndex],
+        (left, right): (TsType, TsType),
+        hasher: &'static dyn Hasher,
+    ) -> Self {
+        let tree = inter_index_tree(segments, hasher);
+        let mut tree_root = Digest::default();
+        tree_root.0.copy_from_slice(tree.root_hash());
+        let start_timestamps: Vec<TsType> = segments.iter().map(|segment| segment.start_timestamp).collect();
+        let mut indexes = Vec::new();
+        for timestamp in [left, right].iter() {
+            let index = segment_position(&start_timestamps, *timestamp);
+            indexes.push(index);
+            if index + 1 < segments.len() {
+                indexes.push(index + 1);
+            }
+        }
+        indexes.sort_unstable();
+        indexes.dedup();
+        let segment_proofs = indexes.into_iter().map(|index| {
+            let proof = tree.gen_nth_proof(index).expect("index within tree");
+            SegmentProof { index, segment: segments[index].clone(), lemma: proof.lemma }
+        }).collect();
+        Self {
+            block_id,
+            error_bounds,
+            tree_root,
+            segment_count: segments.len(),
+            segments: segment_proofs,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        bincode::serialize(self).map(|bytes| bytes.len()).unwrap_or(0)
+    }
+
+    /// Block range the window `[left, right]` is planned to, `None` if the
+    /// segments are not committed by `latest_header` or do not cover the window.
+    pub fn predicted_range(
+        &self,
+        latest_header: &BlockHeader,
+        window: (TsType, TsType),
+        chain_range: (IdType, IdType),
+        hasher: &'static dyn Hasher,
+    ) -> Option<(IdType, IdType)> {
+        if latest_header.block_id != self.block_id
+        || latest_header.inter_index_root != inter_index_root(&self.tree_root, self.error_bounds, hasher) {
+            return None;
+        }
+        let mut segments: BTreeMap<usize, &InterIndex> = BTreeMap::new();
+        for proof in self.segments.iter() {
+            let merkle_proof = Proof::new(
+                hasher,
+                self.tree_root.0.to_vec(),
+                proof.lemma.clone(),
+                proof.segment.to_digest_with(hasher),
+            );
+            if !merkle_proof.validate_nth(&self.tree_root.0, proof.index, self.segment_count) {
+                return None;
+            }
+            segments.insert(proof.index, &proof.segment);
+        }
+        let start_segment = self.covering_segment(&segments, window.0)?;
+        let end_segment = self.covering_segment(&segments, window.1)?;
+        Some(predicted_range(start_segment, end_segment, window, self.error_bounds, chain_range))
+    }
+
+    /// The proven segment covering `timestamp`, as picked by `segment_position`.
+    fn covering_segment<'a>(&self, segments: &BTreeMap<usize, &'a InterIndex>, timestamp: TsType) -> Option<&'a InterIndex> {
+        segments.iter().find(|(index, segment)| {
+            (**index == 0 || segment.start_timestamp <= timestamp)
+                && (**index + 1 == self.segment_count
+                    || segments.get(&(**index + 1)).is_some_and(|next| next.start_timestamp > timestamp))
+        }).map(|(_, segment)| *segment)
+    }
+}
@@ -5,6 +5,8 @@ use serde::{
     Deserialize, Serialize,
 };
 
+use crate::hasher::{Hasher, BLAKE2B};
+
 pub const DIGEST_LEN: usize = 32;
 
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Default)]
@@ -95,6 +97,12 @@ impl<'de> Deserialize<'de> for Digest {
     }
 }
 
+impl AsRef<[u8]> for Digest {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl From<blake2b_simd::Hash> for Digest {
     fn from(input: blake2b_simd::Hash) -> Self {
         let data = input.as_bytes();
@@ -111,33 +119,38 @@ pub fn blake2() -> blake2b_simd::Params {
     params
 }
 
+/// Values hashed into a `Digest`, by BLAKE2b unless a `Hasher` is given.
 pub trait Digestible {
-    fn to_digest(&self) -> Digest;
+    fn to_digest_with(&self, hasher: &dyn Hasher) -> Digest;
+
+    fn to_digest(&self) -> Digest {
+        self.to_digest_with(&BLAKE2B)
+    }
 }
 
 impl Digestible for [u8] {
-    fn to_digest(&self) -> Digest {
-        Digest::from(blake2().hash(self))
+    fn to_digest_with(&self, hasher: &dyn Hasher) -> Digest {
+        hasher.hash(self)
     }
 }
 
 impl Digestible for str {
-    fn to_digest(&self) -> Digest {
-        self.as_bytes().to_digest()
+    fn to_digest_with(&self, hasher: &dyn Hasher) -> Digest {
+        self.as_bytes().to_digest_with(hasher)
     }
 }
 
 impl Digestible for String {
-    fn to_digest(&self) -> Digest {
-        self.as_bytes().to_digest()
+    fn to_digest_with(&self, hasher: &dyn Hasher) -> Digest {
+        self.as_bytes().to_digest_with(hasher)
     }
 }
 
 macro_rules! impl_digestable_for_numeric {
     ($x: ty) => {
         impl Digestible for $x {
-            fn to_digest(&self) -> Digest {
-                self.to_le_bytes().to_digest()
+            fn to_digest_with(&self, hasher: &dyn Hasher) -> Digest {
+                self.to_le_bytes().to_digest_with(hasher)
             }
         }
     };
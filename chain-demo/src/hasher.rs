@@ -0,0 +1,184 @@
+use core::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use sha2::Digest as _;
+use crate::digest::{Digest, DIGEST_LEN};
+
+/// A hash function with `DIGEST_LEN` bytes of output, shared by the digests
+/// of the chain and the merkle trees, so that a proof never mixes two of them.
+pub trait Hasher: fmt::Debug + Send + Sync {
+    /// The name of the function, as recorded in the parameter of a chain.
+    fn algorithm(&self) -> HashAlgorithm;
+
+    /// Starts hashing a message fed by `HashContext::update`.
+    fn context(&self) -> Box<dyn HashContext>;
+
+    fn hash(&self, data: &[u8]) -> Digest {
+        let mut context = self.context();
+        context.update(data);
+        context.finish()
+    }
+}
+
+/// The state of a message being hashed.
+pub trait HashContext {
+    fn update(&mut self, data: &[u8]);
+
+    fn finish(self: Box<Self>) -> Digest;
+}
+
+/// Hash functions a chain can be built with.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    #[default]
+    Blake2b,
+    Sha256,
+    Sha3_256,
+}
+
+impl HashAlgorithm {
+    pub fn hasher(self) -> &'static dyn Hasher {
+        match self {
+            HashAlgorithm::Blake2b => &BLAKE2B,
+            HashAlgorithm::Sha256 => &SHA256,
+            HashAlgorithm::Sha3_256 => &SHA3_256,
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgorithm::Blake2b => write!(f, "blake2b"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+            HashAlgorithm::Sha3_256 => write!(f, "sha3-256"),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match &s.to_ascii_lowercase()[..] {
+            "blake2b" => Ok(HashAlgorithm::Blake2b),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha3-256" | "sha3_256" => Ok(HashAlgorithm::Sha3_256),
+            _ => anyhow::bail!("unknown hash algorithm {}, expected blake2b, sha256 or sha3-256", s),
+        }
+    }
+}
+
+/// BLAKE2b with `DIGEST_LEN` bytes of output.
+#[derive(Debug, Clone, Copy)]
+pub struct Blake2b;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sha256;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sha3_256;
+
+pub static BLAKE2B: Blake2b = Blake2b;
+pub static SHA256: Sha256 = Sha256;
+pub static SHA3_256: Sha3_256 = Sha3_256;
+
+impl Hasher for Blake2b {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Blake2b
+    }
+
+    fn context(&self) -> Box<dyn HashContext> {
+        Box::new(crate::digest::blake2().to_state())
+    }
+}
+
+impl HashContext for blake2b_simd::State {
+    fn update(&mut self, data: &[u8]) {
+        blake2b_simd::State::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Digest {
+        Digest::from(self.finalize())
+    }
+}
+
+impl Hasher for Sha256 {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Sha256
+    }
+
+    fn context(&self) -> Box<dyn HashContext> {
+        Box::new(sha2::Sha256::new())
+    }
+}
+
+impl HashContext for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Digest {
+        digest_from_slice(&self.finalize())
+    }
+}
+
+impl Hasher for Sha3_256 {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Sha3_256
+    }
+
+    fn context(&self) -> Box<dyn HashContext> {
+        Box::new(sha3::Sha3_256::new())
+    }
+}
+
+impl HashContext for sha3::Sha3_256 {
+    fn update(&mut self, data: &[u8]) {
+        sha3::Digest::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Digest {
+        digest_from_slice(&self.finalize())
+    }
+}
+
+fn digest_from_slice(data: &[u8]) -> Digest {
+    debug_assert_eq!(data.len(), DIGEST_LEN);
+    let mut out = Digest::default();
+    out.0.copy_from_slice(&data[..DIGEST_LEN]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::digest::Digestible;
+
+    #[test]
+    fn test_known_digests() {
+        assert_eq!(BLAKE2B.hash(b"hello"), b"hello"[..].to_digest());
+        assert_eq!(
+            SHA256.hash(b"abc").0,
+            hex_literal::hex!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        );
+        assert_eq!(
+            SHA3_256.hash(b"abc").0,
+            hex_literal::hex!("3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"),
+        );
+    }
+
+    #[test]
+    fn test_incremental_hash() {
+        for algorithm in [HashAlgorithm::Blake2b, HashAlgorithm::Sha256, HashAlgorithm::Sha3_256] {
+            let hasher = algorithm.hasher();
+            assert_eq!(hasher.algorithm(), algorithm);
+            let mut context = hasher.context();
+            context.update(b"hello ");
+            context.update(b"world");
+            assert_eq!(context.finish(), hasher.hash(b"hello world"));
+            assert_eq!(algorithm.to_string().parse::<HashAlgorithm>().unwrap(), algorithm);
+        }
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+}
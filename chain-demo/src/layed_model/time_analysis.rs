@@ -7,8 +7,11 @@ pub fn convert_to_normalized_matrix(block_count: usize) -> Array2<f64> {
     let counter = BLOCK_ACCESS_COUNTER.lock().unwrap();
     let row_count = counter.len();
 
+    // rows may be longer than this chain, the counter being shared by every chain of the process
+    let column_count = counter.iter().map(Vec::len).max().unwrap_or(0).max(block_count + 1);
+
     // 创建一个新的 Array2<f64> 矩阵
-    let mut matrix = Array2::zeros((row_count, block_count + 1));
+    let mut matrix = Array2::zeros((row_count, column_count));
 
     for (i, row) in counter.iter().enumerate() {
         let row_sum: f64 = row.iter().map(|&x| x as f64).sum();
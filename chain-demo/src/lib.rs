@@ -5,6 +5,15 @@ extern crate lazy_static;
 pub mod digest;
 pub use digest::*;
 
+pub mod hasher;
+pub use hasher::*;
+
+pub mod signature;
+pub use signature::*;
+
+pub mod accumulator;
+pub use accumulator::*;
+
 pub mod bloom_filter;
 pub use bloom_filter::*;
 
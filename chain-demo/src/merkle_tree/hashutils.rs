@@ -1,8 +1,9 @@
-use ring::digest::{digest, Algorithm, Context, Digest};
+use crate::digest::Digest;
+use crate::hasher::{HashContext, Hasher};
 
 /// The type of values stored in a `MerkleTree` must implement
 /// this trait, in order for them to be able to be fed
-/// to a Ring `Context` when computing the hash of a leaf.
+/// to a `HashContext` when computing the hash of a leaf.
 ///
 /// A default instance for types that already implements
 /// `AsRef<[u8]>` is provided.
@@ -14,7 +15,7 @@ use ring::digest::{digest, Algorithm, Context, Digest};
 ///
 /// ```ignore
 /// impl Hashable for PublicKey {
-///     fn update_context(&self, context: &mut Context) {
+///     fn update_context(&self, context: &mut dyn HashContext) {
 ///         let bytes: Vec<u8> = self.to_bytes();
 ///         context.update(&bytes);
 ///     }
@@ -23,18 +24,18 @@ use ring::digest::{digest, Algorithm, Context, Digest};
 pub trait Hashable {
     /// Update the given `context` with `self`.
     ///
-    /// See `ring::digest::Context::update` for more information.
-    fn update_context(&self, context: &mut Context);
+    /// See `HashContext::update` for more information.
+    fn update_context(&self, context: &mut dyn HashContext);
 }
 
 impl<T: AsRef<[u8]>> Hashable for T {
-    fn update_context(&self, context: &mut Context) {
+    fn update_context(&self, context: &mut dyn HashContext) {
         context.update(self.as_ref());
     }
 }
 
 /// The sole purpose of this trait is to extend the standard
-/// `ring::algo::Algorithm` type with a couple utility functions.
+/// `Hasher` trait with a couple utility functions.
 pub trait HashUtils {
     /// Compute the hash of the empty string
     fn hash_empty(&'static self) -> Digest;
@@ -52,18 +53,18 @@ pub trait HashUtils {
         T: Hashable;
 }
 
-impl HashUtils for Algorithm {
+impl HashUtils for dyn Hasher {
     fn hash_empty(&'static self) -> Digest {
-        digest(self, &[])
+        self.hash(&[])
     }
 
     fn hash_leaf<T>(&'static self, leaf: &T) -> Digest
     where
         T: Hashable,
     {
-        let mut ctx = Context::new(self);
+        let mut ctx = self.context();
         ctx.update(&[0x00]);
-        leaf.update_context(&mut ctx);
+        leaf.update_context(ctx.as_mut());
         ctx.finish()
     }
 
@@ -71,10 +72,10 @@ impl HashUtils for Algorithm {
     where
         T: Hashable,
     {
-        let mut ctx = Context::new(self);
+        let mut ctx = self.context();
         ctx.update(&[0x01]);
-        left.update_context(&mut ctx);
-        right.update_context(&mut ctx);
+        left.update_context(ctx.as_mut());
+        right.update_context(ctx.as_mut());
         ctx.finish()
     }
 }
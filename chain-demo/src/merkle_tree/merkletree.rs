@@ -1,19 +1,21 @@
 use std::cmp::Ordering;
-use std::hash::{Hash, Hasher};
+use std::hash::{self, Hash};
 
-use ring::digest::Algorithm;
+use crate::hasher::Hasher;
 
 use crate::merkle_tree::hashutils::{HashUtils, Hashable};
 use crate::merkle_tree::tree::{LeavesIntoIterator, LeavesIterator, Tree};
 
+use crate::merkle_tree::multiproof::{MultiProof, RangeProof};
 use crate::merkle_tree::proof::{Lemma, Proof};
+use std::ops::Range;
 
 /// A Merkle tree is a binary tree, with values of type `T` at the leafs,
 /// and where every internal node holds the hash of the concatenation of the hashes of its children nodes.
 #[derive(Clone, Debug)]
 pub struct MerkleTree<T> {
     /// The hashing algorithm used by this Merkle tree
-    pub algorithm: &'static Algorithm,
+    pub algorithm: &'static dyn Hasher,
 
     /// The root of the inner binary tree
     root: Tree<T>,
@@ -26,12 +28,11 @@ pub struct MerkleTree<T> {
 }
 
 impl<T: PartialEq> PartialEq for MerkleTree<T> {
-    #[allow(trivial_casts)]
     fn eq(&self, other: &MerkleTree<T>) -> bool {
         self.root == other.root
             && self.height == other.height
             && self.count == other.count
-            && (self.algorithm as *const Algorithm) == (other.algorithm as *const Algorithm)
+            && self.algorithm.algorithm() == other.algorithm.algorithm()
     }
 }
 
@@ -44,30 +45,28 @@ impl<T: Ord> PartialOrd for MerkleTree<T> {
 }
 
 impl<T: Ord> Ord for MerkleTree<T> {
-    #[allow(trivial_casts)]
     fn cmp(&self, other: &MerkleTree<T>) -> Ordering {
         self.height
             .cmp(&other.height)
             .then(self.count.cmp(&other.count))
-            .then((self.algorithm as *const Algorithm).cmp(&(other.algorithm as *const Algorithm)))
+            .then(self.algorithm.algorithm().cmp(&other.algorithm.algorithm()))
             .then_with(|| self.root.cmp(&other.root))
     }
 }
 
 impl<T: Hash> Hash for MerkleTree<T> {
-    #[allow(trivial_casts)]
-    fn hash<H: Hasher>(&self, state: &mut H) {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
         <Tree<T> as Hash>::hash(&self.root, state);
         self.height.hash(state);
         self.count.hash(state);
-        (self.algorithm as *const Algorithm).hash(state);
+        self.algorithm.algorithm().hash(state);
     }
 }
 
 impl<T> MerkleTree<T> {
     /// Constructs a Merkle Tree from a vector of data blocks.
     /// Returns `None` if `values` is empty.
-    pub fn from_vec(algorithm: &'static Algorithm, values: Vec<T>) -> Self
+    pub fn from_vec(algorithm: &'static dyn Hasher, values: Vec<T>) -> Self
     where
         T: Hashable,
     {
@@ -170,6 +169,18 @@ impl<T> MerkleTree<T> {
             .map(|(lemma, value)| Proof::new(self.algorithm, root_hash, lemma, value.clone()))
     }
 
+    /// Generate a single inclusion proof for the leaves at `indices`.
+    /// Returns `None` if an index is out of the tree.
+    pub fn gen_multi_proof(&self, indices: &[usize]) -> Option<MultiProof> {
+        MultiProof::new(&self.root, indices, self.count)
+    }
+
+    /// Generate a single inclusion proof for the contiguous leaves in `range`.
+    /// Returns `None` if the range is empty or ends out of the tree.
+    pub fn gen_range_proof(&self, range: Range<usize>) -> Option<RangeProof> {
+        RangeProof::new(&self.root, range, self.count)
+    }
+
     /// Creates an `Iterator` over the values contained in this Merkle tree.
     pub fn iter(&self) -> LeavesIterator<T> {
         self.root.iter()
@@ -12,8 +12,6 @@
 
 //! *merkle* implements a Merkle Tree in Rust.
 
-extern crate ring;
-
 #[cfg(feature = "serialization-protobuf")]
 extern crate protobuf;
 
@@ -28,6 +26,10 @@ pub use crate::merkle_tree::merkletree::MerkleTree;
 
 mod proof;
 pub use crate::merkle_tree::proof::Proof;
+pub use crate::merkle_tree::proof::Lemma as MerkleLemma;
+
+mod multiproof;
+pub use crate::merkle_tree::multiproof::{MultiProof, RangeProof};
 
 mod hashutils;
 pub use crate::merkle_tree::hashutils::Hashable;
@@ -38,6 +40,8 @@ pub use crate::merkle_tree::tree::{LeavesIntoIterator, LeavesIterator};
 #[cfg(feature = "serialization-protobuf")]
 #[allow(unused_qualifications)]
 mod proto;
+#[cfg(feature = "serialization-protobuf")]
+pub use crate::merkle_tree::proto::{LemmaProto, ProofProto};
 
 #[cfg(test)]
 mod tests;
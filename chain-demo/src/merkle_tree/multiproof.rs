@@ -0,0 +1,162 @@
+use std::ops::Range;
+
+use crate::hasher::Hasher;
+use serde::{Deserialize, Serialize};
+
+use crate::merkle_tree::hashutils::{HashUtils, Hashable};
+use crate::merkle_tree::tree::Tree;
+
+/// An inclusion proof of the leaves at `indices` of a `MerkleTree` at once,
+/// sharing the nodes their paths have in common.
+///
+/// `hashes` are the roots of the subtrees holding none of the proven leaves,
+/// from left to right; every other node is recomputed from the leaves, which
+/// are not part of the proof.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct MultiProof {
+    /// The indices of the proven leaves, strictly increasing
+    pub indices: Vec<usize>,
+    /// The hashes of the subtrees off the paths of the proven leaves
+    pub hashes: Vec<Vec<u8>>,
+}
+
+impl MultiProof {
+    /// Generates the proof of the leaves at `indices` of `tree`, holding `count` leaves.
+    /// Returns `None` if an index is out of the tree.
+    pub fn new<T>(tree: &Tree<T>, indices: &[usize], count: usize) -> Option<MultiProof> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.last().is_some_and(|idx| *idx >= count) {
+            return None;
+        }
+        let mut hashes = Vec::new();
+        collect_hashes(tree, 0, count, &indices, &mut hashes);
+        Some(MultiProof { indices, hashes })
+    }
+
+    /// Computes the root hash of a tree holding `count` leaves, where `leaves`
+    /// are the values at `indices`. Returns `None` if the proof is malformed.
+    pub fn root_hash<T>(&self, algorithm: &'static dyn Hasher, leaves: &[T], count: usize) -> Option<Vec<u8>>
+    where
+        T: Hashable,
+    {
+        let increasing = self.indices.windows(2).all(|pair| pair[0] < pair[1]);
+        if !increasing
+            || leaves.len() != self.indices.len()
+            || self.indices.last().is_some_and(|idx| *idx >= count)
+        {
+            return None;
+        }
+        let mut leaves = leaves.iter();
+        let mut hashes = self.hashes.iter();
+        let root = compute_root(algorithm, 0, count, &self.indices, &mut leaves, &mut hashes)?;
+        // every hash has to be used
+        if hashes.next().is_some() {
+            return None;
+        }
+        Some(root)
+    }
+
+    /// Checks whether `leaves` are the values at `indices` of the tree with
+    /// root hash `root_hash`, holding `count` leaves.
+    pub fn validate<T>(&self, algorithm: &'static dyn Hasher, root_hash: &[u8], leaves: &[T], count: usize) -> bool
+    where
+        T: Hashable,
+    {
+        self.root_hash(algorithm, leaves, count)
+            .is_some_and(|root| root == root_hash)
+    }
+}
+
+/// A `MultiProof` of the contiguous leaves `start..start + leaves.len()`, as
+/// returned by a range query over a tree whose leaves are sorted.
+///
+/// The indices are implied by `start`, and the hashes left of the range, then
+/// right of it, are enough to recompute the root.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RangeProof {
+    /// The index of the first proven leaf
+    pub start: usize,
+    /// The hashes of the subtrees off the paths of the proven leaves
+    pub hashes: Vec<Vec<u8>>,
+}
+
+impl RangeProof {
+    /// Generates the proof of the leaves in `range` of `tree`, holding `count` leaves.
+    /// Returns `None` if the range is empty or ends out of the tree.
+    pub fn new<T>(tree: &Tree<T>, range: Range<usize>, count: usize) -> Option<RangeProof> {
+        if range.start >= range.end || range.end > count {
+            return None;
+        }
+        let start = range.start;
+        let indices: Vec<usize> = range.collect();
+        let mut hashes = Vec::new();
+        collect_hashes(tree, 0, count, &indices, &mut hashes);
+        Some(RangeProof { start, hashes })
+    }
+
+    /// The indices of the proven leaves, given the number of `leaves`.
+    pub fn range(&self, leaves: usize) -> Range<usize> {
+        self.start..self.start + leaves
+    }
+
+    /// Checks whether `leaves` are the values at `start..start + leaves.len()`
+    /// of the tree with root hash `root_hash`, holding `count` leaves.
+    pub fn validate<T>(&self, algorithm: &'static dyn Hasher, root_hash: &[u8], leaves: &[T], count: usize) -> bool
+    where
+        T: Hashable,
+    {
+        let proof = MultiProof {
+            indices: self.range(leaves.len()).collect(),
+            hashes: self.hashes.clone(),
+        };
+        !leaves.is_empty() && proof.validate(algorithm, root_hash, leaves, count)
+    }
+}
+
+/// Pushes the hashes of the subtrees of `tree` holding none of `indices`,
+/// `tree` holding the `count` leaves starting at `offset`.
+fn collect_hashes<T>(tree: &Tree<T>, offset: usize, count: usize, indices: &[usize], hashes: &mut Vec<Vec<u8>>) {
+    if indices.is_empty() {
+        hashes.push(tree.hash().clone());
+        return;
+    }
+    if let Tree::Node { ref left, ref right, .. } = *tree {
+        let left_count = count.next_power_of_two() / 2;
+        let split = indices.partition_point(|idx| *idx < offset + left_count);
+        collect_hashes(left, offset, left_count, &indices[..split], hashes);
+        collect_hashes(right, offset + left_count, count - left_count, &indices[split..], hashes);
+    }
+}
+
+/// Recomputes the hash of the subtree holding the `count` leaves starting at
+/// `offset`, taking the proven leaves and the other subtrees in order.
+fn compute_root<'a, T, L, H>(
+    algorithm: &'static dyn Hasher,
+    offset: usize,
+    count: usize,
+    indices: &[usize],
+    leaves: &mut L,
+    hashes: &mut H,
+) -> Option<Vec<u8>>
+where
+    T: Hashable + 'a,
+    L: Iterator<Item = &'a T>,
+    H: Iterator<Item = &'a Vec<u8>>,
+{
+    if indices.is_empty() {
+        return hashes.next().cloned();
+    }
+    match count {
+        0 => None,
+        1 => leaves.next().map(|leaf| algorithm.hash_leaf(leaf).as_ref().to_vec()),
+        _ => {
+            let left_count = count.next_power_of_two() / 2;
+            let split = indices.partition_point(|idx| *idx < offset + left_count);
+            let left = compute_root(algorithm, offset, left_count, &indices[..split], leaves, hashes)?;
+            let right = compute_root(algorithm, offset + left_count, count - left_count, &indices[split..], leaves, hashes)?;
+            Some(algorithm.hash_nodes(&left, &right).as_ref().to_vec())
+        }
+    }
+}
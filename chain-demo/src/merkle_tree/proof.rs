@@ -1,9 +1,10 @@
 use std::cmp::Ordering;
-use std::hash::{Hash, Hasher};
+use std::hash::{self, Hash};
 
-use ring::digest::Algorithm;
+use crate::hasher::Hasher;
+use serde::{Deserialize, Serialize};
 
-use crate::merkle_tree::hashutils::HashUtils;
+use crate::merkle_tree::hashutils::{HashUtils, Hashable};
 use crate::merkle_tree::tree::Tree;
 
 /// An inclusion proof represent the fact that a `value` is a member
@@ -13,7 +14,7 @@ use crate::merkle_tree::tree::Tree;
 pub struct Proof<T> {
     /// The hashing algorithm used in the original `MerkleTree`
     #[cfg_attr(feature = "serialization-serde", serde(with = "algorithm_serde"))]
-    pub algorithm: &'static Algorithm,
+    pub algorithm: &'static dyn Hasher,
 
     /// The hash of the root of the original `MerkleTree`
     pub root_hash: Vec<u8>,
@@ -27,49 +28,32 @@ pub struct Proof<T> {
 
 #[cfg(feature = "serialization-serde")]
 mod algorithm_serde {
-    use ring::digest::{self, Algorithm};
-    use serde::de::Error;
+    use crate::hasher::{HashAlgorithm, Hasher};
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
     pub fn serialize<S: Serializer>(
-        algorithm: &'static Algorithm,
+        algorithm: &&'static dyn Hasher,
         se: S,
     ) -> Result<S::Ok, S::Error> {
-        // The `Debug` implementation of `Algorithm` prints its ID.
-        format!("{:?}", algorithm).serialize(se)
+        algorithm.algorithm().serialize(se)
     }
 
-    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<&'static Algorithm, D::Error> {
-        let alg_str: String = Deserialize::deserialize(de)?;
-        match &*alg_str {
-            "SHA1" => Ok(&digest::SHA1_FOR_LEGACY_USE_ONLY),
-            "SHA256" => Ok(&digest::SHA256),
-            "SHA384" => Ok(&digest::SHA384),
-            "SHA512" => Ok(&digest::SHA512),
-            "SHA512_256" => Ok(&digest::SHA512_256),
-            _ => Err(D::Error::custom("unknown hash algorithm")),
-        }
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<&'static dyn Hasher, D::Error> {
+        let algorithm: HashAlgorithm = Deserialize::deserialize(de)?;
+        Ok(algorithm.hasher())
     }
 
     #[cfg(test)]
     mod test {
         use super::*;
-        use ring::digest::{
-            SHA1_FOR_LEGACY_USE_ONLY as sha1, SHA256 as sha256, SHA384 as sha384, SHA512 as sha512,
-            SHA512_256 as sha512_256,
-        };
-
-        static SHA1: &Algorithm = &sha1;
-        static SHA256: &Algorithm = &sha256;
-        static SHA384: &Algorithm = &sha384;
-        static SHA512: &Algorithm = &sha512;
-        static SHA512_256: &Algorithm = &sha512_256;
+        use crate::hasher::{BLAKE2B, SHA256, SHA3_256};
 
         #[test]
         fn test_serialize_known_algorithms() {
             extern crate serde_json;
 
-            for alg in &[SHA1, SHA256, SHA384, SHA512, SHA512_256] {
+            let algorithms: [&'static dyn Hasher; 3] = [&BLAKE2B, &SHA256, &SHA3_256];
+            for alg in algorithms.iter() {
                 let mut serializer = serde_json::Serializer::with_formatter(
                     vec![],
                     serde_json::ser::PrettyFormatter::new(),
@@ -82,7 +66,7 @@ mod algorithm_serde {
                 ))
                 .unwrap_or_else(|_| panic!("{:?}", alg));
 
-                assert_eq!(*alg, alg_);
+                assert_eq!(alg.algorithm(), alg_.algorithm());
             }
         }
 
@@ -91,7 +75,7 @@ mod algorithm_serde {
         fn test_serialize_unknown_algorithm() {
             extern crate serde_json;
             {
-                let alg_str = "\"BLAKE2b\"";
+                let alg_str = "\"SHA512\"";
                 let mut deserializer = serde_json::Deserializer::from_str(alg_str);
                 let _ = deserialize(&mut deserializer)
                     .unwrap_or_else(|_| panic!("unknown hash algorithm {:?}", alg_str));
@@ -124,7 +108,7 @@ impl<T: Ord> Ord for Proof<T> {
 }
 
 impl<T: Hash> Hash for Proof<T> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
         self.root_hash.hash(state);
         self.lemma.hash(state);
         self.value.hash(state);
@@ -133,7 +117,7 @@ impl<T: Hash> Hash for Proof<T> {
 
 impl<T> Proof<T> {
     /// Constructs a new `Proof`
-    pub fn new(algorithm: &'static Algorithm, root_hash: Vec<u8>, lemma: Lemma, value: T) -> Self {
+    pub fn new(algorithm: &'static dyn Hasher, root_hash: Vec<u8>, lemma: Lemma, value: T) -> Self {
         Proof {
             algorithm,
             root_hash,
@@ -160,16 +144,30 @@ impl<T> Proof<T> {
     pub fn index(&self, count: usize) -> usize {
         self.lemma.index(count)
     }
+
+    /// Checks whether this inclusion proof is valid for `root_hash`,
+    /// whether its leaf is the hash of `value`, and whether that leaf
+    /// is the `idx`-th one of a tree holding `count` leaves.
+    pub fn validate_nth(&self, root_hash: &[u8], idx: usize, count: usize) -> bool
+    where
+        T: Hashable,
+    {
+        self.validate(root_hash)
+            && self.lemma.is_path_of(idx, count)
+            && self.lemma.leaf_hash() == self.algorithm.hash_leaf(&self.value).as_ref()
+    }
 }
 
 /// A `Lemma` holds the hash of a node, the hash of its sibling node,
 /// and a sub lemma, whose `node_hash`, when combined with this `sibling_hash`
 /// must be equal to this `node_hash`.
-#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Lemma {
+    /// The hash of the node this lemma stands for
     pub node_hash: Vec<u8>,
+    /// The hash of the sibling node, if any
     pub sibling_hash: Option<Positioned<Vec<u8>>>,
+    /// The lemma of the child node on the path to the leaf
     pub sub_lemma: Option<Box<Lemma>>,
 }
 
@@ -258,6 +256,30 @@ impl Lemma {
         }
     }
 
+    /// Returns the hash of the leaf this lemma leads to.
+    fn leaf_hash(&self) -> &[u8] {
+        match self.sub_lemma {
+            Some(ref sub) => sub.leaf_hash(),
+            None => &self.node_hash,
+        }
+    }
+
+    /// Checks whether the shape of this lemma is the path to the `idx`-th
+    /// leaf of a tree holding `count` leaves, see `new_by_index`.
+    fn is_path_of(&self, idx: usize, count: usize) -> bool {
+        let left_count = count.next_power_of_two() / 2;
+        match (self.sub_lemma.as_ref(), self.sibling_hash.as_ref()) {
+            (None, None) => idx == 0 && count == 1,
+            (Some(l), Some(&Positioned::Right(_))) => {
+                idx < left_count && l.is_path_of(idx, left_count)
+            }
+            (Some(l), Some(&Positioned::Left(_))) => {
+                idx >= left_count && idx < count && l.is_path_of(idx - left_count, count - left_count)
+            }
+            (None, Some(_)) | (Some(_), None) => false,
+        }
+    }
+
     fn new_leaf_proof(hash: &[u8], needle: &[u8]) -> Option<Lemma> {
         if *hash == *needle {
             Some(Lemma {
@@ -297,7 +319,7 @@ impl Lemma {
             })
     }
 
-    fn validate(&self, algorithm: &'static Algorithm) -> bool {
+    fn validate(&self, algorithm: &'static dyn Hasher) -> bool {
         match self.sub_lemma {
             None => self.sibling_hash.is_none(),
 
@@ -321,8 +343,7 @@ impl Lemma {
 }
 
 /// Tags a value so that we know from which branch of a `Tree` (if any) it was found.
-#[cfg_attr(feature = "serialization-serde", derive(Serialize, Deserialize))]
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Positioned<T> {
     /// The value was found in the left branch
     Left(T),
@@ -1,19 +1,18 @@
-#[allow(missing_debug_implementations)]
-mod proof;
+// declares `proof`, generated by build.rs from protobuf/proof.proto
+include!(concat!(env!("OUT_DIR"), "/merkle_tree/mod.rs"));
 
-use ring::digest::Algorithm;
+use crate::hasher::Hasher;
 
 use protobuf::error::ProtobufResult;
-use protobuf::parse_from_bytes;
+use protobuf::Message;
 
-use crate::proof::{Lemma, Positioned, Proof};
-use crate::protobuf::Message;
+use crate::merkle_tree::proof::{Lemma, Positioned, Proof};
 
 pub use self::proof::{LemmaProto, ProofProto};
 
 impl<T> Proof<T> {
     /// Constructs a `Proof` struct from its Protobuf representation.
-    pub fn from_protobuf(algorithm: &'static Algorithm, proto: ProofProto) -> Option<Self>
+    pub fn from_protobuf(algorithm: &'static dyn Hasher, proto: ProofProto) -> Option<Self>
     where
         T: From<Vec<u8>>,
     {
@@ -31,12 +30,12 @@ impl<T> Proof<T> {
     /// Parse a `Proof` from its Protobuf binary representation.
     pub fn parse_from_bytes(
         bytes: &[u8],
-        algorithm: &'static Algorithm,
+        algorithm: &'static dyn Hasher,
     ) -> ProtobufResult<Option<Self>>
     where
         T: From<Vec<u8>>,
     {
-        parse_from_bytes::<ProofProto>(bytes).map(|proto| proto.into_proof(algorithm))
+        ProofProto::parse_from_bytes(bytes).map(|proto| proto.into_proof(algorithm))
     }
 
     /// Serialize this `Proof` with Protobuf.
@@ -49,6 +48,7 @@ impl<T> Proof<T> {
 }
 
 impl ProofProto {
+    /// Encode `proof`, its value being stored as bytes.
     pub fn from_proof<T>(proof: Proof<T>) -> Self
     where
         T: Into<Vec<u8>>,
@@ -71,7 +71,8 @@ impl ProofProto {
         proto
     }
 
-    pub fn into_proof<T>(mut self, algorithm: &'static Algorithm) -> Option<Proof<T>>
+    /// Decode the `Proof`, `None` if the root hash or the lemma are missing or malformed.
+    pub fn into_proof<T>(mut self, algorithm: &'static dyn Hasher) -> Option<Proof<T>>
     where
         T: From<Vec<u8>>,
     {
@@ -91,6 +92,7 @@ impl ProofProto {
 }
 
 impl LemmaProto {
+    /// Encode `lemma` and its sub lemmas.
     pub fn from_lemma(lemma: Lemma) -> Self {
         let mut proto = Self::new();
 
@@ -119,6 +121,7 @@ impl LemmaProto {
         proto
     }
 
+    /// Decode the `Lemma`, `None` if it or one of its sub lemmas is malformed.
     pub fn into_lemma(mut self) -> Option<Lemma> {
         if self.node_hash.is_empty() {
             return None;
@@ -3,13 +3,13 @@
 #[cfg(feature = "serialization-serde")]
 extern crate serde_json;
 
-use ring::digest::{Algorithm, Context, SHA512};
+use crate::hasher::{HashContext, Hasher, SHA3_256};
 
 use crate::merkle_tree::hashutils::{HashUtils, Hashable};
 use crate::merkle_tree::merkletree::MerkleTree;
 use crate::merkle_tree::proof::Positioned;
 
-static DIGEST: &Algorithm = &SHA512;
+static DIGEST: &dyn Hasher = &SHA3_256;
 
 #[test]
 fn test_from_str_vec() {
@@ -169,6 +169,87 @@ fn test_nth_proof() {
     }
 }
 
+#[test]
+fn test_multi_proof() {
+    for &count in &[1, 2, 3, 10, 15, 16, 17, 22] {
+        let values = (1..=count).map(|x| vec![x as u8]).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(DIGEST, values.clone());
+        let root_hash = tree.root_hash();
+
+        for step in 1..=count {
+            let indices = (0..count).step_by(step).collect::<Vec<_>>();
+            let leaves = indices.iter().map(|i| values[*i].clone()).collect::<Vec<_>>();
+            let proof = tree.gen_multi_proof(&indices).expect("gen multi proof");
+            assert!(proof.validate(DIGEST, root_hash, &leaves, count));
+            // the proof is no longer than the single proofs of its leaves
+            assert!(proof.hashes.len() <= indices.len() * tree.height().max(1));
+
+            let mut wrong_leaves = leaves.clone();
+            wrong_leaves[0] = vec![0];
+            assert!(!proof.validate(DIGEST, root_hash, &wrong_leaves, count));
+            assert!(!proof.validate(DIGEST, root_hash, &leaves[1..], count));
+        }
+
+        // every leaf needs no other hash
+        let all = (0..count).collect::<Vec<_>>();
+        let proof = tree.gen_multi_proof(&all).unwrap();
+        assert!(proof.hashes.is_empty());
+        assert!(proof.validate(DIGEST, root_hash, &values, count));
+
+        assert!(tree.gen_multi_proof(&[count]).is_none());
+    }
+}
+
+#[test]
+fn test_mutate_multi_proof() {
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, values.clone());
+    let root_hash = tree.root_hash();
+    let proof = tree.gen_multi_proof(&[1, 4, 5]).unwrap();
+    let leaves = vec![values[1].clone(), values[4].clone(), values[5].clone()];
+    assert!(proof.validate(DIGEST, root_hash, &leaves, values.len()));
+
+    let mut wrong_hash = proof.clone();
+    wrong_hash.hashes[0] = vec![1, 2, 3];
+    assert!(!wrong_hash.validate(DIGEST, root_hash, &leaves, values.len()));
+
+    let mut extra_hash = proof.clone();
+    extra_hash.hashes.push(vec![1, 2, 3]);
+    assert!(!extra_hash.validate(DIGEST, root_hash, &leaves, values.len()));
+
+    let mut unsorted = proof.clone();
+    unsorted.indices = vec![4, 1, 5];
+    assert!(!unsorted.validate(DIGEST, root_hash, &leaves, values.len()));
+
+    let mut moved = proof;
+    moved.indices = vec![1, 4, 6];
+    assert!(!moved.validate(DIGEST, root_hash, &leaves, values.len()));
+}
+
+#[test]
+fn test_range_proof() {
+    for &count in &[1, 2, 3, 10, 17] {
+        let values = (1..=count).map(|x| vec![x as u8]).collect::<Vec<_>>();
+        let tree = MerkleTree::from_vec(DIGEST, values.clone());
+        let root_hash = tree.root_hash();
+
+        for start in 0..count {
+            for end in start + 1..=count {
+                let proof = tree.gen_range_proof(start..end).expect("gen range proof");
+                assert_eq!(proof.range(end - start), start..end);
+                assert!(proof.validate(DIGEST, root_hash, &values[start..end], count));
+                if end < count {
+                    // the leaves are bound to their position
+                    assert!(!proof.validate(DIGEST, root_hash, &values[start + 1..end + 1], count));
+                }
+            }
+        }
+
+        assert!(tree.gen_range_proof(0..0).is_none());
+        assert!(tree.gen_range_proof(0..count + 1).is_none());
+    }
+}
+
 #[test]
 fn test_mutate_proof_first_lemma() {
     let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
@@ -268,7 +349,7 @@ impl PublicKey {
 }
 
 impl Hashable for PublicKey {
-    fn update_context(&self, context: &mut Context) {
+    fn update_context(&self, context: &mut dyn HashContext) {
         context.update(&self.to_bytes());
     }
 }
@@ -304,3 +385,23 @@ fn test_serialize_proof_with_serde() {
         serde_json::from_str(&serialized).expect("deserialize proof")
     );
 }
+
+#[cfg(feature = "serialization-protobuf")]
+#[test]
+fn test_serialize_proof_with_protobuf() {
+    use crate::merkle_tree::Proof;
+
+    let values = (1..10).map(|x| vec![x]).collect::<Vec<_>>();
+    let tree = MerkleTree::from_vec(DIGEST, values);
+    let root_hash = tree.root_hash();
+
+    for n in 0..tree.count() {
+        let proof = tree.gen_nth_proof(n).unwrap();
+        let bytes = proof.clone().write_to_bytes().expect("serialize proof");
+        let decoded = Proof::<Vec<u8>>::parse_from_bytes(&bytes, DIGEST)
+            .expect("parse proof")
+            .expect("valid proof");
+        assert_eq!(proof, decoded);
+        assert!(decoded.validate_nth(root_hash, n, tree.count()));
+    }
+}
@@ -1,4 +1,5 @@
-use ring::digest::{Algorithm, Digest};
+use crate::digest::Digest;
+use crate::hasher::Hasher;
 
 use crate::merkle_tree::hashutils::{HashUtils, Hashable};
 
@@ -40,7 +41,7 @@ impl<T> Tree<T> {
     }
 
     /// Create a new leaf
-    pub fn new_leaf(algo: &'static Algorithm, value: T) -> Tree<T>
+    pub fn new_leaf(algo: &'static dyn Hasher, value: T) -> Tree<T>
     where
         T: Hashable,
     {
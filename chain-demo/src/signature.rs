@@ -0,0 +1,202 @@
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{IsIdentity, VartimeMultiscalarMul};
+use merlin::Transcript;
+use rand_core::{CryptoRng, OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// Schnorr signature over Ristretto, `s * G == R + c * P` where the challenge `c`
+/// is drawn from the signing transcript.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub r: CompressedRistretto,
+    pub s: Scalar,
+}
+
+/// Secret scalar and its public key, the secret is wiped on drop.
+pub struct Keypair {
+    secret: Scalar,
+    pub public: CompressedRistretto,
+}
+
+impl Keypair {
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let secret = Scalar::random(rng);
+        let public = (secret * RISTRETTO_BASEPOINT_POINT).compress();
+        Self { secret, public }
+    }
+
+    /// Keypair of a secret kept with `to_bytes`, `None` if it is not a canonical scalar.
+    pub fn from_bytes(bytes: [u8; 32]) -> Option<Self> {
+        let secret = Scalar::from_canonical_bytes(bytes)?;
+        let public = (secret * RISTRETTO_BASEPOINT_POINT).compress();
+        Some(Self { secret, public })
+    }
+
+    /// The secret, to be stored as safely as the keypair itself.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.secret.to_bytes()
+    }
+
+    /// Sign the messages already appended to `transcript`.
+    pub fn sign(&self, transcript: &mut Transcript) -> Signature {
+        // deterministic in the transcript and the secret, hedged with fresh randomness
+        let mut rng = transcript
+            .build_rng()
+            .rekey_with_witness_bytes(b"sign:nonce", self.secret.as_bytes())
+            .finalize(&mut OsRng);
+        let nonce = Scalar::random(&mut rng);
+        let r = (nonce * RISTRETTO_BASEPOINT_POINT).compress();
+        let c = challenge_scalar(transcript, &self.public, &r);
+        Signature { r, s: nonce + c * self.secret }
+    }
+}
+
+impl Drop for Keypair {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl Signature {
+    /// Check the signature of `public` over the messages appended to `transcript`.
+    pub fn verify(&self, public: &CompressedRistretto, transcript: &mut Transcript) -> bool {
+        let (pk, r) = match (public.decompress(), self.r.decompress()) {
+            // the identity as public key would accept any signature
+            (Some(pk), Some(r)) if !pk.is_identity() => (pk, r),
+            _ => return false,
+        };
+        let c = challenge_scalar(transcript, public, &self.r);
+        RistrettoPoint::vartime_double_scalar_mul_basepoint(&c, &pk, &-self.s) == -r
+    }
+}
+
+/// Half aggregation of Schnorr signatures: the nonce commitments are kept and
+/// the responses are folded into a single scalar, `s = sum(a_i * s_i)`, with
+/// weights `a_i` drawn from every statement at once.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AggregateSignature {
+    pub rs: Vec<CompressedRistretto>,
+    pub s: Scalar,
+}
+
+impl AggregateSignature {
+    /// Aggregate `signatures`, each made by the public key of its statement
+    /// over the messages of the statement's transcript.
+    pub fn aggregate(statements: Vec<(CompressedRistretto, Transcript)>, signatures: &[Signature]) -> Self {
+        assert_eq!(statements.len(), signatures.len(), "one signature per statement");
+        let rs: Vec<CompressedRistretto> = signatures.iter().map(|sig| sig.r).collect();
+        let (publics, cs) = challenges(statements, &rs);
+        let weights = weights(&publics, &rs, &cs);
+        let s = weights.iter().zip(signatures.iter()).map(|(a, sig)| a * sig.s).sum();
+        Self { rs, s }
+    }
+
+    /// Check the aggregate against the same statements, in the same order, with a
+    /// single multiscalar multiplication.
+    pub fn verify(&self, statements: Vec<(CompressedRistretto, Transcript)>) -> bool {
+        if statements.len() != self.rs.len() {
+            return false;
+        }
+        let mut points = Vec::with_capacity(2 * self.rs.len() + 1);
+        for ((public, _), r) in statements.iter().zip(self.rs.iter()) {
+            match (public.decompress(), r.decompress()) {
+                (Some(pk), Some(r)) if !pk.is_identity() => {
+                    points.push(pk);
+                    points.push(r);
+                }
+                _ => return false,
+            }
+        }
+        points.push(RISTRETTO_BASEPOINT_POINT);
+        let (publics, cs) = challenges(statements, &self.rs);
+        let weights = weights(&publics, &self.rs, &cs);
+        // sum(a_i * c_i * P_i + a_i * R_i) - s * G == 0
+        let mut scalars = Vec::with_capacity(points.len());
+        for (a, c) in weights.iter().zip(cs.iter()) {
+            scalars.push(a * c);
+            scalars.push(*a);
+        }
+        scalars.push(-self.s);
+        RistrettoPoint::vartime_multiscalar_mul(scalars, points).is_identity()
+    }
+}
+
+fn challenge_scalar(transcript: &mut Transcript, public: &CompressedRistretto, r: &CompressedRistretto) -> Scalar {
+    transcript.append_message(b"sign:pk", public.as_bytes());
+    transcript.append_message(b"sign:R", r.as_bytes());
+    let mut buf = [0u8; 64];
+    transcript.challenge_bytes(b"sign:c", &mut buf);
+    Scalar::from_bytes_mod_order_wide(&buf)
+}
+
+fn challenges(
+    statements: Vec<(CompressedRistretto, Transcript)>,
+    rs: &[CompressedRistretto],
+) -> (Vec<CompressedRistretto>, Vec<Scalar>) {
+    statements.into_iter().zip(rs.iter()).map(|((public, mut transcript), r)| {
+        let c = challenge_scalar(&mut transcript, &public, r);
+        (public, c)
+    }).unzip()
+}
+
+fn weights(publics: &[CompressedRistretto], rs: &[CompressedRistretto], cs: &[Scalar]) -> Vec<Scalar> {
+    let mut transcript = Transcript::new(b"chain-demo aggregate");
+    for ((public, r), c) in publics.iter().zip(rs.iter()).zip(cs.iter()) {
+        transcript.append_message(b"agg:pk", public.as_bytes());
+        transcript.append_message(b"agg:R", r.as_bytes());
+        transcript.append_message(b"agg:c", c.as_bytes());
+    }
+    (0..rs.len()).map(|_| {
+        let mut buf = [0u8; 64];
+        transcript.challenge_bytes(b"agg:a", &mut buf);
+        Scalar::from_bytes_mod_order_wide(&buf)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(msg: &[u8]) -> Transcript {
+        let mut t = Transcript::new(b"test");
+        t.append_message(b"msg", msg);
+        t
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let keypair = Keypair::generate(&mut OsRng);
+        let sig = keypair.sign(&mut transcript(b"hello"));
+        assert!(sig.verify(&keypair.public, &mut transcript(b"hello")));
+        assert!(!sig.verify(&keypair.public, &mut transcript(b"world")));
+        let other = Keypair::generate(&mut OsRng);
+        assert!(!sig.verify(&other.public, &mut transcript(b"hello")));
+        assert!(!Signature::default().verify(&keypair.public, &mut transcript(b"hello")));
+        assert!(!Signature::default().verify(&CompressedRistretto::default(), &mut transcript(b"hello")));
+    }
+
+    #[test]
+    fn test_aggregate_signature() {
+        let keypairs: Vec<Keypair> = (0..3).map(|_| Keypair::generate(&mut OsRng)).collect();
+        let msgs: [&[u8]; 3] = [b"one", b"two", b"three"];
+        let statements = |msgs: &[&[u8]]| -> Vec<(CompressedRistretto, Transcript)> {
+            keypairs.iter().zip(msgs.iter()).map(|(kp, msg)| (kp.public, transcript(msg))).collect()
+        };
+        let sigs: Vec<Signature> = keypairs.iter().zip(msgs.iter())
+            .map(|(kp, msg)| kp.sign(&mut transcript(msg)))
+            .collect();
+        let agg = AggregateSignature::aggregate(statements(&msgs), &sigs);
+        assert!(agg.verify(statements(&msgs)));
+        assert!(!agg.verify(statements(&[b"one", b"two", b"four"])));
+        assert!(!agg.verify(statements(&msgs[..2])));
+
+        // a single bad signature spoils the aggregate
+        let mut bad_sigs = sigs.clone();
+        bad_sigs[1] = keypairs[1].sign(&mut transcript(b"other"));
+        let agg = AggregateSignature::aggregate(statements(&msgs), &bad_sigs);
+        assert!(!agg.verify(statements(&msgs)));
+    }
+}